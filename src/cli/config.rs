@@ -0,0 +1,141 @@
+use crate::cli::AuthProvider;
+use crate::config::{Config, GoogleConfig, TrueLayerConfig};
+use crate::error::{AppError, Result};
+use crate::rules::Rules;
+use clap::Subcommand;
+use dialoguer::{Confirm, Input, Password};
+use std::fs;
+use tracing::{error, info, warn};
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Create a config file by prompting for credentials
+    Init {
+        /// Overwrite an existing config file
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Check the config file for problems
+    Validate,
+}
+
+impl ConfigCommand {
+    pub async fn execute(&self) -> Result<()> {
+        match self {
+            ConfigCommand::Init { force } => init(*force).await,
+            ConfigCommand::Validate => validate(),
+        }
+    }
+}
+
+async fn init(force: bool) -> Result<()> {
+    let config_path = Config::config_file()?;
+    if config_path.exists() && !force {
+        return Err(AppError::Config(format!(
+            "Config file already exists at {:?}, use --force to overwrite it",
+            config_path
+        )));
+    }
+
+    info!("TrueLayer credentials are listed under your app at https://console.truelayer.com/");
+    let truelayer = TrueLayerConfig {
+        client_id: prompt_input("TrueLayer client ID")?,
+        client_secret: prompt_password("TrueLayer client secret")?,
+        ..Default::default()
+    };
+
+    info!("Google credentials are listed at https://console.cloud.google.com/auth/clients");
+    let google = GoogleConfig {
+        client_id: prompt_input("Google client ID")?,
+        client_secret: prompt_password("Google client secret")?,
+        ..Default::default()
+    };
+
+    let config = Config {
+        truelayer,
+        google,
+        ..Default::default()
+    };
+    for problem in config.validate() {
+        warn!("{}", problem);
+    }
+
+    config.save(&config_path)?;
+    info!(path = ?config_path, environment = config.truelayer.environment(), "Config written");
+
+    for (provider, name) in [
+        (AuthProvider::Truelayer, "TrueLayer"),
+        (AuthProvider::Sheets, "Google Sheets"),
+    ] {
+        if prompt_confirm(&format!("Authenticate with {} now?", name))? {
+            provider.execute(false).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn validate() -> Result<()> {
+    let config_path = Config::config_file()?;
+    if !config_path.exists() {
+        return Err(AppError::Config(format!(
+            "Config file not found at {:?}, run `config init` to create one",
+            config_path
+        )));
+    }
+
+    let contents = fs::read_to_string(&config_path)?;
+    let (config, unknown_keys) = Config::parse(&contents)?;
+
+    let mut problems: Vec<String> = unknown_keys
+        .into_iter()
+        .map(|key| format!("{} is not a known setting, check for typos", key))
+        .chain(config.validate())
+        .collect();
+    if let Err(e) = Rules::load(&Config::rules_file()?) {
+        problems.push(e.to_string());
+    }
+
+    for problem in &problems {
+        error!("{}", problem);
+    }
+
+    if !problems.is_empty() {
+        return Err(AppError::Config(format!(
+            "Found {} problem(s) in {:?}",
+            problems.len(),
+            config_path
+        )));
+    }
+
+    info!(
+        path = ?config_path,
+        environment = config.truelayer.environment(),
+        "Config is valid"
+    );
+
+    Ok(())
+}
+
+fn prompt_input(prompt: &str) -> Result<String> {
+    Input::new()
+        .with_prompt(prompt)
+        .interact_text()
+        .map_err(|e| AppError::Config(format!("Failed to read input: {}", e)))
+}
+
+fn prompt_password(prompt: &str) -> Result<String> {
+    Password::new()
+        .with_prompt(prompt)
+        .interact()
+        .map_err(|e| AppError::Config(format!("Failed to read input: {}", e)))
+}
+
+fn prompt_confirm(prompt: &str) -> Result<bool> {
+    Confirm::new()
+        .with_prompt(prompt)
+        .default(true)
+        .interact()
+        .map_err(|e| AppError::Config(format!("Failed to read input: {}", e)))
+}
@@ -0,0 +1,122 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::export::{ExportFilter, ExportFormat, ExportRecord, ExportState, write_records};
+use crate::sheets::{SheetOperations, SheetsClient, sheet_title};
+use crate::sync::archive::parse_archive_tab_name;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use tracing::info;
+
+pub async fn execute(
+    format: ExportFormat,
+    cards: &[String],
+    filter: &ExportFilter,
+    output: Option<&Path>,
+    incremental: bool,
+    state_file: Option<&Path>,
+) -> Result<()> {
+    let config = Config::load()?;
+    let sheets_client = SheetsClient::new(&config.google)
+        .await?
+        .with_archive(config.google.archive_spreadsheet_id.as_deref())
+        .await?;
+
+    let mut records = read_records(&config, &sheets_client, cards, filter).await?;
+
+    // Each format keeps its own state, so that exporting in one doesn't skip the other
+    let state_file = match (incremental, state_file) {
+        (false, _) => None,
+        (true, Some(path)) => Some(path.to_path_buf()),
+        (true, None) => Some(Config::data_file(&format!(
+            "export-{}-state.json",
+            format.name()
+        ))?),
+    };
+    let mut state = match &state_file {
+        Some(path) => ExportState::load(path)?,
+        None => ExportState::default(),
+    };
+    records.retain(|record| !state.is_exported(record));
+
+    match output {
+        Some(path) => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            write_records(format, &records, &config.ledger, &mut writer)?;
+            writer.flush()?;
+            info!(count = records.len(), path = ?path, "Transactions exported");
+        }
+        None => {
+            let mut writer = io::stdout().lock();
+            write_records(format, &records, &config.ledger, &mut writer)?;
+            writer.flush()?;
+        }
+    }
+
+    // Only record the export once it has been written successfully
+    if let Some(path) = &state_file {
+        state.mark_exported(&records);
+        state.save(path)?;
+    }
+
+    Ok(())
+}
+
+/// Read the transactions for the selected cards, including their archive tabs, or every card if
+/// none are selected.
+pub(super) async fn read_records(
+    config: &Config,
+    sheets_client: &impl SheetOperations,
+    cards: &[String],
+    filter: &ExportFilter,
+) -> Result<Vec<ExportRecord>> {
+    let sheets = sheets_client.list_sheets().await?;
+
+    for card in cards {
+        if !sheets.iter().any(|s| sheet_title(s) == Some(card)) {
+            return Err(AppError::Sheets(format!(
+                "No sheet found for card {:?}",
+                card
+            )));
+        }
+    }
+
+    let mut records = Vec::new();
+    for sheet in &sheets {
+        let Some(title) = sheet_title(sheet) else {
+            continue;
+        };
+        // Archived transactions belong to the same card
+        let card_tab = parse_archive_tab_name(title).map_or(title, |(tab_name, _)| tab_name);
+        if !cards.is_empty() && !cards.iter().any(|c| c == title || c == card_tab) {
+            continue;
+        }
+
+        let data = sheets_client.read_sheet(sheet, title).await?;
+        let transactions = data
+            .transactions
+            .into_iter()
+            .filter(|t| filter.matches(t))
+            .collect();
+        records.extend(ExportRecord::from_tab(config, title, transactions));
+    }
+
+    // Archive tabs kept in the archive spreadsheet
+    for title in sheets_client.list_archives().await? {
+        let card_tab =
+            parse_archive_tab_name(&title).map_or(title.as_str(), |(tab_name, _)| tab_name);
+        if !cards.is_empty() && !cards.iter().any(|c| *c == title || c == card_tab) {
+            continue;
+        }
+
+        let data = sheets_client.read_archive(&title).await?;
+        let transactions = data
+            .transactions
+            .into_iter()
+            .filter(|t| filter.matches(t))
+            .collect();
+        records.extend(ExportRecord::from_tab(config, &title, transactions));
+    }
+
+    Ok(records)
+}
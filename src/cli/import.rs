@@ -0,0 +1,110 @@
+use crate::config::Config;
+use crate::currency::CurrencyConverter;
+use crate::error::{AppError, Result};
+use crate::import::{ImportFormat, ImportOptions, parse_transactions};
+use crate::models::Card;
+use crate::models::card::Provider;
+use crate::models::statement::StatementDays;
+use crate::rules::{ActionLog, Rules};
+use crate::sheets::{SheetOperations, SheetsClient, Snapshots};
+use crate::sync::audit::{append_audit_log, audit_entries, run_id};
+use crate::sync::{merge_transactions, warn_unconverted};
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+pub async fn execute(
+    file: &Path,
+    card: &str,
+    format: Option<ImportFormat>,
+    layout: Option<&str>,
+    currency: &str,
+    date_format: &str,
+) -> Result<()> {
+    let config = Config::load()?;
+    let rules = Rules::load(&Config::rules_file()?)?;
+
+    let format = match format {
+        Some(format) => format,
+        None => ImportFormat::from_path(file).ok_or_else(|| {
+            AppError::Import(format!(
+                "Can't tell the format of {:?} from its extension, use --format",
+                file
+            ))
+        })?,
+    };
+    let layout = layout.map(|name| config.csv_layout(name)).transpose()?;
+    if format == ImportFormat::Csv && layout.is_none() {
+        return Err(AppError::Import(
+            "Use --layout to say which columns to import from a CSV file".to_string(),
+        ));
+    }
+
+    let contents = fs::read_to_string(file)?;
+    let options = ImportOptions {
+        layout,
+        currency: currency.to_string(),
+        date_format: date_format.to_string(),
+    };
+    let transactions = parse_transactions(format, &contents, &options)?;
+    info!(count = transactions.len(), "Transactions read from file");
+
+    // The card's ID and name aren't known without Open Banking, so `--card` is used for both so
+    // that `[cards."<id or name>"]` overrides apply, and is the tab unless they set `tab_name`
+    let card = Card {
+        id: card.to_string(),
+        name: card.to_string(),
+        provider: Provider {
+            id: "import".to_string(),
+            name: "Import".to_string(),
+        },
+    };
+    let mut settings = config.card_settings(&card);
+    if settings.statement_day.is_none() {
+        let statement_days = StatementDays::load(&Config::cache_file("statement-days.json")?)?;
+        settings.statement_day = statement_days.get(&settings.tab_name);
+    }
+
+    let from_date = transactions
+        .iter()
+        .map(|t| t.timestamp.date_naive())
+        .min()
+        .unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let converter = CurrencyConverter::load(&config.currency, from_date).await?;
+
+    let sheets_client = SheetsClient::new(&config.google)
+        .await?
+        .with_snapshots(Snapshots::from_config(&config.snapshots)?)
+        .with_archive(config.google.archive_spreadsheet_id.as_deref())
+        .await?;
+    let mut actions = ActionLog::load(&Config::data_file("actions.jsonl")?)?;
+    let run_id = run_id(chrono::Utc::now());
+    let report = merge_transactions(
+        &sheets_client,
+        &card,
+        &settings,
+        &rules,
+        converter.as_ref(),
+        &mut actions,
+        transactions,
+    )
+    .await?;
+    warn_unconverted(std::slice::from_ref(&report), converter.as_ref());
+    actions.save()?;
+
+    let entries = audit_entries(&run_id, chrono::Utc::now(), &[report]);
+    append_audit_log(&Config::data_file("audit.jsonl")?, &entries)?;
+    if config.sync.history_tab
+        && let Err(e) = sheets_client.append_history(&entries).await
+    {
+        warn!(error = %e, "Failed to append to the History tab");
+    }
+
+    info!(
+        url = sheets_client.spreadsheet_url(),
+        sheet = settings.tab_name,
+        "Import completed"
+    );
+
+    Ok(())
+}
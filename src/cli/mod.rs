@@ -1,12 +1,28 @@
 mod auth;
+mod config;
+mod export;
+mod import;
+mod report;
+mod rules;
 mod show;
+mod snapshots;
 mod sync;
 
+use crate::config::Config;
 use crate::error::Result;
-use clap::{Parser, Subcommand};
+use crate::export::{ExportFilter, ExportFormat};
+use crate::import::ImportFormat;
+use crate::report::ReportFormat;
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
 pub use auth::AuthProvider;
+pub use config::ConfigCommand;
+pub use rules::RulesCommand;
 pub use show::ShowResource;
+pub use snapshots::SnapshotsCommand;
+pub use sync::SyncOutput;
 
 #[derive(Parser, Debug)]
 #[command(name = "credit-card-tracker")]
@@ -17,20 +33,102 @@ pub struct Cli {
     #[arg(short, long, action = clap::ArgAction::Count, global = true)]
     pub verbose: u8,
 
+    /// Config file to use instead of the default location
+    #[arg(short, long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Profile to use, from the [profiles.<name>] table in the config file
+    #[arg(short, long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Format of log lines, which are written to stderr
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    pub log_format: LogFormat,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
 impl Cli {
     pub async fn run(&self) -> Result<()> {
+        if let Some(path) = &self.config {
+            Config::set_config_file(path.clone());
+        }
+        if let Some(profile) = &self.profile {
+            Config::set_profile(profile)?;
+        }
+
         match &self.command {
             Commands::Auth { provider, reset } => provider.execute(*reset).await,
-            Commands::Sync => sync::execute().await,
+            Commands::Sync { output, interval } => sync::execute(*output, *interval).await,
             Commands::Show { resource } => resource.execute().await,
+            Commands::Config { command } => command.execute().await,
+            Commands::Rules { command } => command.execute().await,
+            Commands::Snapshots { command } => command.execute().await,
+            Commands::Restore { card, snapshot } => snapshots::restore(card, snapshot).await,
+            Commands::Export {
+                format,
+                cards,
+                from,
+                to,
+                unmatched,
+                output,
+                incremental,
+                state,
+            } => {
+                let filter = ExportFilter {
+                    from: *from,
+                    to: *to,
+                    unmatched_only: *unmatched,
+                };
+                export::execute(
+                    *format,
+                    cards,
+                    &filter,
+                    output.as_deref(),
+                    *incremental,
+                    state.as_deref(),
+                )
+                .await
+            }
+            Commands::Report {
+                format,
+                cards,
+                months,
+                days,
+                top,
+                output,
+            } => report::execute(*format, cards, *months, *days, *top, output.as_deref()).await,
+            Commands::Import {
+                file,
+                card,
+                format,
+                layout,
+                currency,
+                date_format,
+            } => {
+                import::execute(
+                    file,
+                    card,
+                    *format,
+                    layout.as_deref(),
+                    currency,
+                    date_format,
+                )
+                .await
+            }
         }
     }
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines, with progress bars
+    Text,
+    /// One JSON object per line, for log shippers
+    Json,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Authenticate with providers
@@ -44,11 +142,137 @@ pub enum Commands {
     },
 
     /// Sync transactions from TrueLayer to Google Sheets
-    Sync,
+    Sync {
+        /// Print a report of what was synced on each card to stdout
+        #[arg(short, long, value_enum, default_value = "text")]
+        output: SyncOutput,
+
+        /// Keep running and sync every MINUTES minutes, serving metrics if metrics.listen is set
+        #[arg(long, value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..))]
+        interval: Option<u64>,
+    },
 
     /// Show resources
     Show {
         #[command(subcommand)]
         resource: ShowResource,
     },
+
+    /// Create or check the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Test, list and revert the categorisation rules
+    Rules {
+        #[command(subcommand)]
+        command: RulesCommand,
+    },
+
+    /// List the snapshots taken of each sheet before it was written
+    Snapshots {
+        #[command(subcommand)]
+        command: SnapshotsCommand,
+    },
+
+    /// Roll a card's sheet back to a snapshot
+    Restore {
+        /// Sheet tab of the card
+        #[arg(long, value_name = "NAME")]
+        card: String,
+
+        /// ID of the snapshot (see `snapshots list`), or "latest"
+        #[arg(long, value_name = "ID")]
+        snapshot: String,
+    },
+
+    /// Export transactions from Google Sheets
+    Export {
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+
+        /// Card (sheet tab) to export, with its archive tabs, can be repeated. Defaults to all cards
+        #[arg(long = "card", value_name = "NAME")]
+        cards: Vec<String>,
+
+        /// Only export transactions on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        from: Option<NaiveDate>,
+
+        /// Only export transactions on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        to: Option<NaiveDate>,
+
+        /// Only export transactions that haven't been matched
+        #[arg(long)]
+        unmatched: bool,
+
+        /// File to write to. Defaults to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Only export transactions that haven't been exported before in this format
+        #[arg(short, long)]
+        incremental: bool,
+
+        /// State file for incremental exports. Defaults to one in the data directory
+        #[arg(long, value_name = "PATH", requires = "incremental")]
+        state: Option<PathBuf>,
+    },
+
+    /// Report outstanding debits, monthly spending, top merchants and trends
+    Report {
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: ReportFormat,
+
+        /// Card (sheet tab) to report on, with its archive tabs, can be repeated. Defaults to all cards
+        #[arg(long = "card", value_name = "NAME")]
+        cards: Vec<String>,
+
+        /// Number of months of spending to show, including the current month
+        #[arg(short, long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+        months: u32,
+
+        /// Length in days of the periods compared by the trend
+        #[arg(short, long, default_value_t = 30, value_parser = clap::value_parser!(i64).range(1..))]
+        days: i64,
+
+        /// Number of merchants to show
+        #[arg(short, long, default_value_t = 10)]
+        top: usize,
+
+        /// File to write to. Defaults to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Import transactions from a CSV, OFX or QIF statement
+    Import {
+        /// Statement file to import
+        file: PathBuf,
+
+        /// Card to import into, by its sheet tab, or by the ID or name of a [cards."<id or name>"]
+        /// table in the config file that sets tab_name
+        #[arg(long, value_name = "NAME")]
+        card: String,
+
+        /// File format. Defaults to the file extension
+        #[arg(short, long, value_enum)]
+        format: Option<ImportFormat>,
+
+        /// CSV layout, from [csv_layouts.<name>] in the config file or built in: amex, barclaycard
+        #[arg(short, long, value_name = "NAME")]
+        layout: Option<String>,
+
+        /// Currency of the amounts, when the file doesn't include it
+        #[arg(long, default_value = "GBP")]
+        currency: String,
+
+        /// Format of dates in QIF files, in strftime syntax
+        #[arg(long, default_value = "%d/%m/%Y")]
+        date_format: String,
+    },
 }
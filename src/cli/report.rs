@@ -0,0 +1,58 @@
+use super::export::read_records;
+use crate::config::Config;
+use crate::error::Result;
+use crate::export::ExportFilter;
+use crate::report::{Report, ReportFormat, ReportOptions, write_report};
+use crate::sheets::SheetsClient;
+use chrono::Local;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use tracing::{info, warn};
+
+pub async fn execute(
+    format: ReportFormat,
+    cards: &[String],
+    months: u32,
+    trend_days: i64,
+    top_merchants: usize,
+    output: Option<&Path>,
+) -> Result<()> {
+    let config = Config::load()?;
+    let sheets_client = SheetsClient::new(&config.google)
+        .await?
+        .with_archive(config.google.archive_spreadsheet_id.as_deref())
+        .await?;
+
+    let records = read_records(&config, &sheets_client, cards, &ExportFilter::default()).await?;
+    let options = ReportOptions {
+        today: Local::now().date_naive(),
+        months,
+        trend_days,
+        top_merchants,
+        base_currency: config.currency.base.clone(),
+    };
+    let report = Report::build(&records, &options);
+    if report.unconverted > 0 {
+        warn!(
+            count = report.unconverted,
+            "Transactions without a converted amount were left out of the report, sync to convert them"
+        );
+    }
+
+    match output {
+        Some(path) => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            write_report(&report, format, &mut writer)?;
+            writer.flush()?;
+            info!(path = ?path, "Report written");
+        }
+        None => {
+            let mut writer = io::stdout().lock();
+            write_report(&report, format, &mut writer)?;
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
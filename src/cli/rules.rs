@@ -0,0 +1,175 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::rules::{Action, ActionLog, Rules};
+use crate::sheets::{SheetOperations, SheetsClient, Snapshots};
+use crate::truelayer::{TrueLayerClient, TrueLayerOperations};
+use chrono::{Duration, Utc};
+use clap::Subcommand;
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+#[derive(Subcommand, Debug)]
+pub enum RulesCommand {
+    /// Show which rule matches each recent transaction
+    Test {
+        /// Number of days of transactions to fetch
+        #[arg(short, long, default_value_t = 30)]
+        days: i64,
+
+        /// Only test the card with this ID or name
+        #[arg(long, value_name = "NAME")]
+        card: Option<String>,
+    },
+
+    /// List the comments and matches made by rules
+    Log,
+
+    /// Revert comments and matches made by rules, so that they aren't made again
+    Revert {
+        /// IDs of the actions to revert (see `rules log`)
+        #[arg(required_unless_present = "rule")]
+        ids: Vec<u64>,
+
+        /// Revert every action made by this rule
+        #[arg(long, value_name = "NAME", conflicts_with = "ids")]
+        rule: Option<String>,
+    },
+}
+
+impl RulesCommand {
+    pub async fn execute(&self) -> Result<()> {
+        match self {
+            RulesCommand::Test { days, card } => test(*days, card.as_deref()).await,
+            RulesCommand::Log => log(),
+            RulesCommand::Revert { ids, rule } => revert(ids, rule.as_deref()).await,
+        }
+    }
+}
+
+async fn test(days: i64, card_filter: Option<&str>) -> Result<()> {
+    let config = Config::load()?;
+    let rules_path = Config::rules_file()?;
+    let rules = Rules::load(&rules_path)?;
+    if rules.is_empty() {
+        warn!(path = ?rules_path, "No rules found");
+    }
+
+    let client = TrueLayerClient::new(&config.truelayer).await?;
+    let cards: Vec<_> = client
+        .get_cards()
+        .await?
+        .into_iter()
+        .filter(|card| card_filter.is_none_or(|c| c == card.id || c == card.name))
+        .collect();
+    if cards.is_empty() {
+        return Err(AppError::TrueLayer("No cards found".to_string()));
+    }
+
+    let to_date = Utc::now();
+    let from_date = to_date - Duration::days(days);
+
+    for card in &cards {
+        let transactions = client
+            .get_card_transactions(&card.id, from_date, to_date)
+            .await?;
+
+        for transaction in &transactions {
+            let rule = rules.find(card, transaction);
+            let comment_rule = rules.find_comment(card, transaction);
+            info!(
+                card = card.name,
+                date = %transaction.timestamp.date_naive(),
+                amount = %transaction.amount,
+                rule = rule.map_or("-", |r| r.name.as_str()),
+                category = rule.and_then(|r| r.category.as_deref()).unwrap_or("-"),
+                comment = comment_rule.and_then(|r| r.comment.as_deref()).unwrap_or("-"),
+                "{}",
+                transaction.description
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn log() -> Result<()> {
+    let log = ActionLog::load(&Config::data_file("actions.jsonl")?)?;
+    if log.records().is_empty() {
+        warn!("No actions have been made by rules");
+    }
+
+    for record in log.records() {
+        let (action, transactions) = match &record.action {
+            Action::Comment {
+                transaction_id,
+                comment,
+            } => (format!("Comment {:?}", comment), transaction_id.clone()),
+            Action::Match {
+                debit_id,
+                credit_id,
+            } => ("Match".to_string(), format!("{} {}", debit_id, credit_id)),
+        };
+        info!(
+            id = record.id,
+            date = %record.timestamp.date_naive(),
+            sheet = record.sheet,
+            rule = record.rule,
+            transactions,
+            reverted = record.reverted,
+            "{}",
+            action
+        );
+    }
+
+    Ok(())
+}
+
+async fn revert(ids: &[u64], rule: Option<&str>) -> Result<()> {
+    let config = Config::load()?;
+    let mut log = ActionLog::load(&Config::data_file("actions.jsonl")?)?;
+
+    let mut ids_by_sheet: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    for record in log.records().iter().filter(|r| !r.reverted) {
+        let selected = match rule {
+            Some(rule) => record.rule == rule,
+            None => ids.contains(&record.id),
+        };
+        if selected {
+            ids_by_sheet
+                .entry(record.sheet.clone())
+                .or_default()
+                .push(record.id);
+        }
+    }
+    if ids_by_sheet.is_empty() {
+        return Err(AppError::Other(anyhow::anyhow!(
+            "No actions to revert, see `rules log`"
+        )));
+    }
+
+    let sheets_client = SheetsClient::new(&config.google)
+        .await?
+        .with_snapshots(Snapshots::from_config(&config.snapshots)?);
+    for (sheet_name, ids) in &ids_by_sheet {
+        let sheet = sheets_client.ensure_sheet(sheet_name).await?;
+        let mut data = sheets_client.read_sheet(&sheet, sheet_name).await?;
+
+        let reverted = log.revert(sheet_name, ids, &mut data.transactions);
+        if !reverted.is_empty() {
+            let settings = config.tab_settings(sheet_name);
+            sheets_client
+                .write_sheet(&sheet, sheet_name, &data, &settings.highlight)
+                .await?;
+        }
+        // Save after each sheet so the log matches what has been written
+        log.save()?;
+
+        info!(
+            sheet = sheet_name,
+            count = reverted.len(),
+            "Actions reverted"
+        );
+    }
+
+    Ok(())
+}
@@ -1,9 +1,14 @@
 use crate::config::Config;
 use crate::error::Result;
-use crate::sheets::SheetsClient;
+use crate::models::statement::{
+    StatementCurrency, StatementCycle, StatementDays, main_currency, summarise_statements,
+};
+use crate::sheets::{SheetOperations, SheetsClient, sheet_title};
+use crate::sync::archive::parse_archive_tab_name;
 use crate::truelayer::{TrueLayerClient, TrueLayerOperations};
+use chrono::Utc;
 use clap::Subcommand;
-use tracing::info;
+use tracing::{info, warn};
 
 #[derive(Subcommand, Debug)]
 pub enum ShowResource {
@@ -15,6 +20,12 @@ pub enum ShowResource {
 
     /// Show configuration and cache paths
     Paths,
+
+    /// Show the effective sync settings for each card
+    Config,
+
+    /// Show what remains unpaid on each closed statement
+    Statements,
 }
 
 impl ShowResource {
@@ -23,6 +34,8 @@ impl ShowResource {
             ShowResource::Cards => show_cards().await,
             ShowResource::Sheets => show_sheets().await,
             ShowResource::Paths => show_paths(),
+            ShowResource::Config => show_config().await,
+            ShowResource::Statements => show_statements().await,
         }
     }
 }
@@ -41,9 +54,19 @@ async fn show_cards() -> Result<()> {
 
 async fn show_sheets() -> Result<()> {
     let config = Config::load()?;
-    let sheets_client = SheetsClient::new(&config.google).await?;
+    let sheets_client = SheetsClient::new(&config.google)
+        .await?
+        .with_archive(config.google.archive_spreadsheet_id.as_deref())
+        .await?;
 
-    info!(url = sheets_client.spreadsheet_url(), "Spreadsheet");
+    info!(
+        profile = Config::profile(),
+        url = sheets_client.spreadsheet_url(),
+        "Spreadsheet"
+    );
+    if let Some(url) = sheets_client.archive_spreadsheet_url() {
+        info!(url, "Archive spreadsheet");
+    }
 
     Ok(())
 }
@@ -52,8 +75,118 @@ fn show_paths() -> Result<()> {
     let config_path = Config::config_file()?;
     let cache_dir = Config::cache_dir()?;
 
-    info!(path = ?config_path, "Config path");
-    info!(path = ?cache_dir, "Cache path");
+    info!(path = ?config_path, profile = Config::profile(), "Config path");
+    info!(path = ?Config::rules_file()?, "Rules path");
+    info!(path = ?Config::data_file("actions.jsonl")?, "Rule actions path");
+    info!(path = ?cache_dir, profile = Config::profile(), "Cache path");
+
+    Ok(())
+}
+
+async fn show_config() -> Result<()> {
+    let config = Config::load()?;
+    let client = TrueLayerClient::new(&config.truelayer).await?;
+    let cards = client.get_cards().await?;
+
+    for card in &cards {
+        let settings = config.card_settings(card);
+        info!(
+            id = card.id,
+            tab_name = settings.tab_name,
+            fetch_days = settings.fetch_days.num_days(),
+            reconcile_days = settings.reconcile_days.num_days(),
+            reconcile_strategy = ?settings.reconcile_strategy,
+            reconcile_tolerance = %settings.reconcile_tolerance,
+            age_warning_days = settings.age_warning_days,
+            statement_day = settings.statement_day,
+            highlight_debit = %settings.highlight.debit,
+            highlight_credit = %settings.highlight.credit,
+            "{}",
+            card.name
+        );
+    }
+
+    for key in config.cards.keys() {
+        if !cards
+            .iter()
+            .any(|card| &card.id == key || &card.name == key)
+        {
+            warn!(card = key, "Card override doesn't match any card");
+        }
+    }
+
+    Ok(())
+}
+
+async fn show_statements() -> Result<()> {
+    let config = Config::load()?;
+    let statement_days = StatementDays::load(&Config::cache_file("statement-days.json")?)?;
+    let sheets_client = SheetsClient::new(&config.google)
+        .await?
+        .with_archive(config.google.archive_spreadsheet_id.as_deref())
+        .await?;
+    let today = Utc::now().date_naive();
+
+    let sheets = sheets_client.list_sheets().await?;
+    let archives = sheets_client.list_archives().await?;
+    for sheet in &sheets {
+        // Archive tabs are summarised with their card
+        let Some(sheet_name) = sheet_title(sheet).filter(|t| parse_archive_tab_name(t).is_none())
+        else {
+            continue;
+        };
+        let settings = config.tab_settings(sheet_name);
+        let Some(day) = settings
+            .statement_day
+            .or_else(|| statement_days.get(sheet_name))
+        else {
+            warn!(
+                card = sheet_name,
+                "Statement day not known, sync the card or set statement_day"
+            );
+            continue;
+        };
+
+        let mut transactions = sheets_client
+            .read_sheet(sheet, sheet_name)
+            .await?
+            .transactions;
+        for archive in &sheets {
+            if let Some(title) = sheet_title(archive).filter(|t| {
+                parse_archive_tab_name(t).is_some_and(|(tab_name, _)| tab_name == sheet_name)
+            }) {
+                transactions.extend(sheets_client.read_sheet(archive, title).await?.transactions);
+            }
+        }
+        for title in archives.iter().filter(|t| {
+            parse_archive_tab_name(t).is_some_and(|(tab_name, _)| tab_name == sheet_name)
+        }) {
+            transactions.extend(sheets_client.read_archive(title).await?.transactions);
+        }
+
+        let card_currency = main_currency(&transactions).unwrap_or_default().to_string();
+        let currency = StatementCurrency::new(config.currency.base.as_deref(), &card_currency);
+        for summary in
+            summarise_statements(StatementCycle::new(day), &transactions, currency, today)
+        {
+            info!(
+                card = sheet_name,
+                closing_date = %summary.closing_date,
+                total = %summary.total,
+                unpaid = %summary.unpaid,
+                unpaid_count = summary.unpaid_count,
+                "Statement"
+            );
+            if summary.skipped > 0 {
+                warn!(
+                    card = sheet_name,
+                    closing_date = %summary.closing_date,
+                    count = summary.skipped,
+                    "Transactions in another currency or without a converted amount were left out of the statement"
+                );
+            }
+        }
+    }
 
     Ok(())
 }
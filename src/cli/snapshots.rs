@@ -0,0 +1,70 @@
+use crate::config::Config;
+use crate::error::Result;
+use crate::sheets::{SheetOperations, SheetsClient, Snapshots};
+use clap::Subcommand;
+use tracing::{info, warn};
+
+#[derive(Subcommand, Debug)]
+pub enum SnapshotsCommand {
+    /// List the snapshots taken before each sheet was written
+    List {
+        /// Only list the snapshots of this sheet tab
+        #[arg(long, value_name = "NAME")]
+        card: Option<String>,
+    },
+}
+
+impl SnapshotsCommand {
+    pub async fn execute(&self) -> Result<()> {
+        match self {
+            SnapshotsCommand::List { card } => list(card.as_deref()),
+        }
+    }
+}
+
+fn list(card: Option<&str>) -> Result<()> {
+    let config = Config::load()?;
+    let snapshots = Snapshots::from_config(&config.snapshots)?.list(card)?;
+    if snapshots.is_empty() {
+        warn!("No snapshots found");
+    }
+
+    for snapshot in snapshots {
+        info!(
+            card = snapshot.card,
+            taken_at = %snapshot.taken_at,
+            transactions = snapshot.transactions,
+            "{}",
+            snapshot.id
+        );
+    }
+
+    Ok(())
+}
+
+/// Write a snapshot back to a card's sheet. The sheet's current contents are snapshotted first,
+/// so that the restore can be undone.
+pub async fn restore(card: &str, snapshot: &str) -> Result<()> {
+    let config = Config::load()?;
+    let snapshots = Snapshots::from_config(&config.snapshots)?;
+    let data = snapshots.load(card, snapshot)?;
+
+    let sheets_client = SheetsClient::new(&config.google)
+        .await?
+        .with_snapshots(snapshots);
+    let sheet = sheets_client.ensure_sheet(card).await?;
+    let settings = config.tab_settings(card);
+    sheets_client
+        .write_sheet(&sheet, card, &data, &settings.highlight)
+        .await?;
+
+    info!(
+        card = card,
+        snapshot = snapshot,
+        count = data.transactions.len(),
+        url = sheets_client.spreadsheet_url(),
+        "Sheet restored"
+    );
+
+    Ok(())
+}
@@ -1,20 +1,124 @@
-use crate::config::Config;
-use crate::error::Result;
-use crate::sheets::SheetsClient;
+use crate::config::{Config, MetricsConfig};
+use crate::currency::CurrencyConverter;
+use crate::error::{AppError, Result};
+use crate::metrics::{self, metrics};
+use crate::models::statement::StatementDays;
+use crate::notify::notify;
+use crate::rules::{ActionLog, Rules};
+use crate::sheets::{SheetsClient, Snapshots};
 use crate::sync::SyncEngine;
+use crate::sync::audit::{append_audit_log, audit_entries, run_id};
+use crate::sync::report::SyncReport;
 use crate::truelayer::TrueLayerClient;
-use tracing::info;
+use chrono::Utc;
+use clap::ValueEnum;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncOutput {
+    /// Only log what happened
+    Text,
+    /// Also print the sync report as JSON
+    Json,
+}
+
+/// Sync once, or keep syncing every `interval` minutes if it's set.
+pub async fn execute(output: SyncOutput, interval: Option<u64>) -> Result<()> {
+    let Some(minutes) = interval else {
+        return run(output).await;
+    };
 
-pub async fn execute() -> Result<()> {
     let config = Config::load()?;
-    let truelayer_client = TrueLayerClient::new(&config.truelayer).await?;
-    let sheets_client = SheetsClient::new(&config.google).await?;
-    let url = sheets_client.spreadsheet_url();
+    if let Some(listen) = &config.metrics.listen {
+        metrics::serve(listen)?;
+    }
+    loop {
+        // Carry on after a failed sync, as the next one may succeed
+        if let Err(e) = run(output).await {
+            error!(error = %e, "Sync failed");
+        }
+        info!(minutes = minutes, "Waiting for the next sync");
+        tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+    }
+}
+
+async fn run(output: SyncOutput) -> Result<()> {
+    let config = Config::load()?;
+    let notify_config = config.notify.clone();
+    let metrics_config = config.metrics.clone();
+
+    // Notify about failures anywhere in the sync, such as expired authentication
+    let mut report = SyncReport {
+        run_id: run_id(Utc::now()),
+        ..Default::default()
+    };
+    let result = sync(config, &mut report).await;
+    if let Err(e) = &result {
+        report.errors.push(e.to_string());
+    }
+    notify(&notify_config, &report).await;
+    record_metrics(&metrics_config, &report);
+    if output == SyncOutput::Json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+    let url = result?;
 
-    let engine = SyncEngine::new(config.sync, truelayer_client, sheets_client);
-    engine.sync().await?;
+    let failed = report.failed_cards().count();
+    if failed > 0 {
+        return Err(AppError::Other(anyhow::anyhow!(
+            "{} of {} cards failed to sync",
+            failed,
+            report.cards.len()
+        )));
+    }
 
-    info!(url = url, "Sync completed");
+    info!(
+        url = url,
+        added = report.added(),
+        matched = report.matched(),
+        "Sync completed"
+    );
 
     Ok(())
 }
+
+/// Record the report in the metrics and write them to the textfile, if one is set. Failing to
+/// write them is logged rather than returned, so that it doesn't fail the sync.
+fn record_metrics(config: &MetricsConfig, report: &SyncReport) {
+    metrics().record_report(report, Utc::now());
+    if let Some(path) = &config.textfile
+        && let Err(e) = metrics().write_textfile(path)
+    {
+        warn!(path = %path.display(), error = %e, "Failed to write metrics");
+    }
+}
+
+/// Sync every card, returning the spreadsheet URL.
+async fn sync(config: Config, report: &mut SyncReport) -> Result<String> {
+    let rules = Rules::load(&Config::rules_file()?)?;
+    let from_date = (Utc::now() - config.max_fetch_days()).date_naive();
+    let converter = CurrencyConverter::load(&config.currency, from_date).await?;
+    let truelayer_client = TrueLayerClient::new(&config.truelayer).await?;
+    let sheets_client = SheetsClient::new(&config.google)
+        .await?
+        .with_snapshots(Snapshots::from_config(&config.snapshots)?)
+        .with_archive(config.google.archive_spreadsheet_id.as_deref())
+        .await?;
+    let url = sheets_client.spreadsheet_url();
+
+    let mut actions = ActionLog::load(&Config::data_file("actions.jsonl")?)?;
+    let statement_days_path = Config::cache_file("statement-days.json")?;
+    let mut statement_days = StatementDays::load(&statement_days_path)?;
+    let engine = SyncEngine::new(config, rules, converter, truelayer_client, sheets_client);
+    // Save the actions of the cards that did sync even if a later card fails
+    let result = engine.sync(&mut actions, &mut statement_days, report).await;
+    actions.save()?;
+    statement_days.save(&statement_days_path)?;
+    let entries = audit_entries(&report.run_id, Utc::now(), &report.cards);
+    append_audit_log(&Config::data_file("audit.jsonl")?, &entries)?;
+    engine.append_history(&entries).await;
+    result?;
+
+    Ok(url)
+}
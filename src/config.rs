@@ -1,23 +1,147 @@
 use crate::error::{AppError, Result};
+use crate::models::Card;
 use chrono::Duration;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::fs;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+use url::Url;
 
 const CONFIG_DIR_PREFIX: &str = "credit-card-tracker";
 
+/// Prefix of environment variables that override values from the config file.
+const ENV_PREFIX: &str = "CCT_";
+
+/// Config values that can be overridden by environment variables, as (section, key, kind).
+/// The variable name is the prefix followed by the section and key in upper case, with dots
+/// replaced by underscores, e.g. `CCT_TRUELAYER_CLIENT_ID` or `CCT_NOTIFY_SMTP_HOST`.
+///
+/// A `*` in the section stands for each entry of a table keyed by name, such as `[cards]`, and
+/// is replaced by the entry's key in upper case with anything other than letters and digits
+/// replaced by underscores, e.g. `CCT_CARDS_AMEX_GOLD_FETCH_DAYS` for `[cards."Amex Gold"]`.
+/// Only entries that are in the config file can be overridden.
+const ENV_OVERRIDES: &[(&str, &str, EnvKind)] = &[
+    ("truelayer", "client_id", EnvKind::String),
+    ("truelayer", "client_secret", EnvKind::String),
+    ("truelayer", "client_secret_command", EnvKind::String),
+    ("google", "client_id", EnvKind::String),
+    ("google", "client_secret", EnvKind::String),
+    ("google", "client_secret_command", EnvKind::String),
+    ("google", "spreadsheet_id", EnvKind::String),
+    ("google", "spreadsheet_name", EnvKind::String),
+    ("google", "archive_spreadsheet_id", EnvKind::String),
+    ("sync", "fetch_days", EnvKind::Integer),
+    ("sync", "reconcile_days", EnvKind::Integer),
+    ("sync", "reconcile_strategy", EnvKind::String),
+    ("sync", "reconcile_tolerance", EnvKind::String),
+    ("sync", "age_warning_days", EnvKind::Integer),
+    ("sync", "history_tab", EnvKind::Boolean),
+    ("sync", "archive_after_months", EnvKind::Integer),
+    ("sync", "highlight_debit", EnvKind::String),
+    ("sync", "highlight_credit", EnvKind::String),
+    ("cards.*", "fetch_days", EnvKind::Integer),
+    ("cards.*", "reconcile_days", EnvKind::Integer),
+    ("cards.*", "tab_name", EnvKind::String),
+    ("cards.*", "reconcile_strategy", EnvKind::String),
+    ("cards.*", "reconcile_tolerance", EnvKind::String),
+    ("cards.*", "age_warning_days", EnvKind::Integer),
+    ("cards.*", "archive_after_months", EnvKind::Integer),
+    ("cards.*", "highlight_debit", EnvKind::String),
+    ("cards.*", "highlight_credit", EnvKind::String),
+    ("cards.*", "ledger_account", EnvKind::String),
+    ("cards.*", "statement_day", EnvKind::Integer),
+    ("ledger", "card_account_prefix", EnvKind::String),
+    ("ledger", "expense_account", EnvKind::String),
+    ("ledger", "payment_account", EnvKind::String),
+    ("currency", "base", EnvKind::String),
+    ("currency", "rates_file", EnvKind::String),
+    ("notify", "consent_warning_days", EnvKind::Integer),
+    ("notify.webhook", "url", EnvKind::String),
+    ("notify.webhook", "events", EnvKind::List),
+    ("notify.smtp", "host", EnvKind::String),
+    ("notify.smtp", "port", EnvKind::Integer),
+    ("notify.smtp", "username", EnvKind::String),
+    ("notify.smtp", "password", EnvKind::String),
+    ("notify.smtp", "password_command", EnvKind::String),
+    ("notify.smtp", "from", EnvKind::String),
+    ("notify.smtp", "to", EnvKind::List),
+    ("notify.smtp", "events", EnvKind::List),
+    ("notify.desktop", "events", EnvKind::List),
+    ("metrics", "textfile", EnvKind::String),
+    ("metrics", "listen", EnvKind::String),
+    ("snapshots", "retain", EnvKind::Integer),
+    ("csv_layouts.*", "date", EnvKind::String),
+    ("csv_layouts.*", "date_format", EnvKind::String),
+    ("csv_layouts.*", "description", EnvKind::String),
+    ("csv_layouts.*", "amount", EnvKind::String),
+    ("csv_layouts.*", "charges_positive", EnvKind::Boolean),
+    ("csv_layouts.*", "id", EnvKind::String),
+    ("csv_layouts.*", "currency", EnvKind::String),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum EnvKind {
+    String,
+    Integer,
+    Boolean,
+    /// Comma separated strings
+    List,
+}
+
+/// Config file selected with `--config`, instead of the XDG location.
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Profile selected with `--profile`, which overlays `[profiles.<name>]` onto the config and
+/// keeps its own token caches.
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Table containing the config for each profile.
+const PROFILES_KEY: &str = "profiles";
+
+// Google Sheets limits the length of tab names.
+const MAX_TAB_NAME_LENGTH: usize = 100;
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Config {
     pub truelayer: TrueLayerConfig,
     pub google: GoogleConfig,
     #[serde(default)]
     pub sync: SyncConfig,
+    /// Per-card overrides, keyed by card ID or name.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub cards: HashMap<String, CardConfig>,
+    #[serde(default)]
+    pub ledger: LedgerConfig,
+    #[serde(default)]
+    pub currency: CurrencyConfig,
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub snapshots: SnapshotConfig,
+    /// Column mappings for importing CSV statements, keyed by layout name.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub csv_layouts: HashMap<String, CsvLayout>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct TrueLayerConfig {
     pub client_id: String,
+    #[serde(default)]
     pub client_secret: String,
+    /// Shell command that prints the client secret, instead of `client_secret`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret_command: Option<String>,
 }
 
 impl TrueLayerConfig {
@@ -26,6 +150,13 @@ impl TrueLayerConfig {
         self.client_id.starts_with("sandbox-")
     }
 
+    pub fn environment(&self) -> &'static str {
+        match self.is_sandbox() {
+            true => "sandbox",
+            false => "live",
+        }
+    }
+
     pub fn auth_url(&self) -> String {
         match self.is_sandbox() {
             true => "https://auth.truelayer-sandbox.com".to_string(),
@@ -51,15 +182,43 @@ impl TrueLayerConfig {
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct GoogleConfig {
     pub client_id: String,
+    #[serde(default)]
     pub client_secret: String,
+    /// Shell command that prints the client secret, instead of `client_secret`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret_command: Option<String>,
+    /// ID of an existing spreadsheet to use, instead of finding one by name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spreadsheet_id: Option<String>,
+    /// Name of the spreadsheet to find or create
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spreadsheet_name: Option<String>,
+    /// ID of an existing spreadsheet to archive old transactions to, instead of tabs in the
+    /// same spreadsheet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_spreadsheet_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct SyncConfig {
     #[serde(with = "duration_days")]
     pub fetch_days: Duration,
     #[serde(with = "duration_days")]
     pub reconcile_days: Duration,
+    pub reconcile_strategy: ReconcileStrategy,
+    /// How far apart a debit and credit's amounts can be and still match
+    pub reconcile_tolerance: Tolerance,
+    /// Warn at the end of sync about unmatched transactions older than this many days
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_warning_days: Option<u32>,
+    /// Also append the audit log to a hidden History tab in the spreadsheet
+    pub history_tab: bool,
+    /// Move matched transactions older than this many months into a tab for each year
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_after_months: Option<u32>,
+    pub highlight_debit: Colour,
+    pub highlight_credit: Colour,
 }
 
 impl Default for SyncConfig {
@@ -67,24 +226,505 @@ impl Default for SyncConfig {
         Self {
             fetch_days: Duration::days(60),
             reconcile_days: Duration::days(60),
+            reconcile_strategy: ReconcileStrategy::default(),
+            reconcile_tolerance: Tolerance::default(),
+            age_warning_days: None,
+            history_tab: false,
+            archive_after_months: None,
+            highlight_debit: Colour::new(0xfc, 0xe8, 0xb2), // Light yellow
+            highlight_credit: Colour::new(0xb2, 0xe8, 0xb2), // Light green
+        }
+    }
+}
+
+/// Overrides for a single card. Any value that isn't set falls back to `SyncConfig`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct CardConfig {
+    #[serde(with = "duration_days_option", skip_serializing_if = "Option::is_none")]
+    pub fetch_days: Option<Duration>,
+    #[serde(with = "duration_days_option", skip_serializing_if = "Option::is_none")]
+    pub reconcile_days: Option<Duration>,
+    /// Name of the sheet tab, instead of the card name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tab_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconcile_strategy: Option<ReconcileStrategy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconcile_tolerance: Option<Tolerance>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_warning_days: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_after_months: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_debit: Option<Colour>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_credit: Option<Colour>,
+    /// Account for the card in ledger and beancount exports
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ledger_account: Option<String>,
+    /// Day of the month that statements close, instead of learning it from the card's balance
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement_day: Option<u32>,
+}
+
+/// Conversion of amounts into a single currency for totals.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct CurrencyConfig {
+    /// Currency to convert amounts into, such as "GBP". Nothing is converted unless it's set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    /// CSV file of exchange rates with `date,currency,rate` columns, where the rate is the
+    /// number of units of the currency per unit of the base currency, instead of ECB rates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rates_file: Option<PathBuf>,
+}
+
+/// Events after a sync that notifications can be sent for.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyEvent {
+    /// Transactions added to the sheets
+    NewTransactions,
+    /// Debits and credits matched by the sync
+    NewMatches,
+    /// Debits added by the sync that haven't been matched
+    Unmatched,
+    /// Unmatched transactions older than `age_warning_days`
+    Overdue,
+    /// Errors that stopped the sync
+    Errors,
+    /// TrueLayer consent expiring within `consent_warning_days`
+    ConsentExpiry,
+}
+
+impl NotifyEvent {
+    pub const ALL: &[Self] = &[
+        Self::NewTransactions,
+        Self::NewMatches,
+        Self::Unmatched,
+        Self::Overdue,
+        Self::Errors,
+        Self::ConsentExpiry,
+    ];
+}
+
+fn all_notify_events() -> Vec<NotifyEvent> {
+    NotifyEvent::ALL.to_vec()
+}
+
+/// Notifications sent after each sync. Each output is only used if its table is set.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct NotifyConfig {
+    /// Notify about TrueLayer consent that expires within this many days
+    pub consent_warning_days: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<WebhookConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp: Option<SmtpConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub desktop: Option<DesktopConfig>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            consent_warning_days: 7,
+            webhook: None,
+            smtp: None,
+            desktop: None,
+        }
+    }
+}
+
+/// POST a JSON summary of each sync to a URL.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default = "all_notify_events")]
+    pub events: Vec<NotifyEvent>,
+}
+
+/// Email a summary of each sync.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// 465 for TLS, otherwise STARTTLS is used
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: String,
+    /// Shell command that prints the password, instead of `password`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_command: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default = "all_notify_events")]
+    pub events: Vec<NotifyEvent>,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+/// Show a desktop notification after each sync.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DesktopConfig {
+    #[serde(default = "all_notify_events")]
+    pub events: Vec<NotifyEvent>,
+}
+
+impl Default for DesktopConfig {
+    fn default() -> Self {
+        Self {
+            events: all_notify_events(),
         }
     }
 }
 
+/// Prometheus metrics about each sync.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// File to write the metrics to after each sync, for node_exporter's textfile collector
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub textfile: Option<PathBuf>,
+    /// Address such as "127.0.0.1:9464" to serve `/metrics` on while `sync --interval` runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<String>,
+}
+
+/// Local copies of each sheet, taken before it's written.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// Number of snapshots to keep for each sheet, or 0 to not take any
+    pub retain: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self { retain: 10 }
+    }
+}
+
+/// Accounts used for postings in ledger and beancount exports.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct LedgerConfig {
+    /// Parent of each card's account, unless the card sets `ledger_account`
+    pub card_account_prefix: String,
+    /// Other side of charges
+    pub expense_account: String,
+    /// Other side of payments and refunds
+    pub payment_account: String,
+}
+
+impl Default for LedgerConfig {
+    fn default() -> Self {
+        Self {
+            card_account_prefix: "Liabilities:CreditCards".to_string(),
+            expense_account: "Expenses:Uncategorised".to_string(),
+            payment_account: "Assets:Unknown".to_string(),
+        }
+    }
+}
+
+/// Column mapping for a card issuer's CSV statements. Columns are found by their header.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CsvLayout {
+    /// Header of the transaction date column
+    pub date: String,
+    /// Format of the date column, in `strftime` syntax
+    #[serde(default = "default_csv_date_format")]
+    pub date_format: String,
+    /// Header of the description column
+    pub description: String,
+    /// Header of the amount column
+    pub amount: String,
+    /// Whether charges are positive amounts in the file, which is the opposite of the sheet
+    #[serde(default)]
+    pub charges_positive: bool,
+    /// Header of a column with a unique reference for each transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Header of the currency column, if the file has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+}
+
+/// CSV layouts that are available without any config, by name.
+const BUILTIN_CSV_LAYOUTS: &[&str] = &["amex", "barclaycard"];
+
+impl CsvLayout {
+    /// Get a built-in layout for a common card issuer.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "amex" => Some(Self {
+                date: "Date".to_string(),
+                date_format: default_csv_date_format(),
+                description: "Description".to_string(),
+                amount: "Amount".to_string(),
+                charges_positive: true,
+                id: Some("Reference".to_string()),
+                currency: None,
+            }),
+            "barclaycard" => Some(Self {
+                date: "Transaction Date".to_string(),
+                date_format: default_csv_date_format(),
+                description: "Description".to_string(),
+                amount: "Amount".to_string(),
+                charges_positive: false,
+                id: None,
+                currency: None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn default_csv_date_format() -> String {
+    "%d/%m/%Y".to_string()
+}
+
+/// How unmatched debits are paired with credits of the same amount.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReconcileStrategy {
+    /// Match the earliest credit within the window
+    #[default]
+    Earliest,
+    /// Match the credit closest in time to the debit
+    Nearest,
+    /// Match the earliest credit from the same merchant as the debit
+    Merchant,
+    /// Don't match automatically
+    None,
+}
+
+/// Largest difference between the amounts of a matched debit and credit, written as an amount
+/// such as `"0.50"` or a percentage of the debit such as `"2%"` in the config file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Tolerance {
+    /// Amounts must be equal
+    #[default]
+    Exact,
+    Absolute(Decimal),
+    Percent(Decimal),
+}
+
+impl Tolerance {
+    /// The largest allowed difference when matching a debit of this amount.
+    pub fn allowance(&self, amount: Decimal) -> Decimal {
+        match self {
+            Tolerance::Exact => Decimal::ZERO,
+            Tolerance::Absolute(allowance) => *allowance,
+            Tolerance::Percent(percent) => amount.abs() * percent / Decimal::ONE_HUNDRED,
+        }
+    }
+}
+
+impl fmt::Display for Tolerance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tolerance::Exact => write!(f, "0"),
+            Tolerance::Absolute(allowance) => write!(f, "{}", allowance),
+            Tolerance::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+impl FromStr for Tolerance {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            AppError::Config(format!(
+                "Invalid tolerance {:?}, expected an amount such as \"0.50\" or a percentage such as \"2%\"",
+                s
+            ))
+        };
+
+        let s = s.trim();
+        let (number, percent) = match s.strip_suffix('%') {
+            Some(number) => (number.trim_end(), true),
+            None => (s, false),
+        };
+        let value = Decimal::from_str(number).map_err(|_| invalid())?;
+        if value.is_sign_negative() {
+            return Err(invalid());
+        }
+
+        Ok(match (value.is_zero(), percent) {
+            (true, _) => Tolerance::Exact,
+            (false, true) => Tolerance::Percent(value),
+            (false, false) => Tolerance::Absolute(value),
+        })
+    }
+}
+
+impl Serialize for Tolerance {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Tolerance {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Allow plain numbers as well as strings
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Text(String),
+            Integer(i64),
+            Float(f64),
+        }
+
+        let s = match Raw::deserialize(deserializer)? {
+            Raw::Text(s) => s,
+            Raw::Integer(n) => n.to_string(),
+            Raw::Float(n) => n.to_string(),
+        };
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// An RGB colour, written as `"#rrggbb"` in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Colour {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Colour {
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self { red, green, blue }
+    }
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+impl FromStr for Colour {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || AppError::Config(format!("Invalid colour {:?}, expected \"#rrggbb\"", s));
+
+        let hex = s.strip_prefix('#').ok_or_else(invalid)?;
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+
+        let component = |range: std::ops::Range<usize>| {
+            u8::from_str_radix(&hex[range], 16).map_err(|_| invalid())
+        };
+        Ok(Self::new(
+            component(0..2)?,
+            component(2..4)?,
+            component(4..6)?,
+        ))
+    }
+}
+
+impl Serialize for Colour {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Colour {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Colours used to highlight unmatched transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HighlightColours {
+    pub debit: Colour,
+    pub credit: Colour,
+}
+
+/// Effective settings for a card, after applying any overrides from `[cards."<id or name>"]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CardSettings {
+    pub tab_name: String,
+    #[serde(with = "duration_days")]
+    pub fetch_days: Duration,
+    #[serde(with = "duration_days")]
+    pub reconcile_days: Duration,
+    pub reconcile_strategy: ReconcileStrategy,
+    pub reconcile_tolerance: Tolerance,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_warning_days: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_after_months: Option<u32>,
+    pub highlight: HighlightColours,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement_day: Option<u32>,
+}
+
 impl Config {
     pub fn load() -> Result<Self> {
         let config_path = Self::config_file()?;
 
-        if !config_path.exists() {
-            return Err(AppError::Config(format!(
-                "Config file not found at {:?}. Please create one.",
-                config_path
-            )));
+        // Allow the file to be omitted when everything is set by environment variables
+        let has_env_overrides = std::env::vars().any(|(name, _)| name.starts_with(ENV_PREFIX));
+        let contents = match config_path.exists() {
+            true => fs::read_to_string(&config_path)?,
+            false if has_env_overrides => String::new(),
+            false => {
+                return Err(AppError::Config(format!(
+                    "Config file not found at {:?}. Please create one.",
+                    config_path
+                )));
+            }
+        };
+
+        let (mut config, unknown_keys) = Self::parse(&contents)?;
+        for key in unknown_keys {
+            warn!(key, "Unknown config key");
         }
 
-        let contents = fs::read_to_string(&config_path)?;
-        let config: Config = toml::from_str(&contents)
-            .map_err(|e| AppError::Config(format!("Failed to parse config: {}", e)))?;
+        resolve_secret(
+            "truelayer.client_secret",
+            &mut config.truelayer.client_secret,
+            config.truelayer.client_secret_command.as_deref(),
+        )?;
+        resolve_secret(
+            "google.client_secret",
+            &mut config.google.client_secret,
+            config.google.client_secret_command.as_deref(),
+        )?;
+        if let Some(smtp) = &mut config.notify.smtp {
+            resolve_secret(
+                "notify.smtp.password",
+                &mut smtp.password,
+                smtp.password_command.as_deref(),
+            )?;
+        }
 
         if config.truelayer.client_id.is_empty() || config.truelayer.client_secret.is_empty() {
             return Err(AppError::Config(
@@ -101,31 +741,630 @@ impl Config {
         Ok(config)
     }
 
+    /// Parse the contents of a config file and apply overrides from environment variables,
+    /// also returning the paths of any unrecognised keys.
+    ///
+    /// Secret commands aren't run, so that the config can be checked without side effects.
+    pub fn parse(contents: &str) -> Result<(Self, Vec<String>)> {
+        Self::parse_with_env(contents, Self::profile(), |name| std::env::var(name).ok())
+    }
+
+    fn parse_with_env(
+        contents: &str,
+        profile: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<(Self, Vec<String>)> {
+        let mut table: toml::Table = contents
+            .parse()
+            .map_err(|e| AppError::Config(format!("Failed to parse config: {}", e)))?;
+        apply_profile(&mut table, profile)?;
+        apply_env_overrides(&mut table, env)?;
+
+        let mut unknown_keys = Vec::new();
+        let config = serde_ignored::deserialize(toml::Value::Table(table), |path| {
+            unknown_keys.push(path.to_string())
+        })
+        .map_err(|e| AppError::Config(format!("Failed to parse config: {}", e)))?;
+        unknown_keys.sort();
+
+        Ok((config, unknown_keys))
+    }
+
+    /// Check that values are set and within range, returning a description of each problem.
+    pub fn validate(&self) -> Vec<String> {
+        let truelayer_hint = "copy it from https://console.truelayer.com/";
+        let google_hint = "copy it from https://console.cloud.google.com/auth/clients";
+        let credentials = [
+            (
+                "truelayer.client_id",
+                &self.truelayer.client_id,
+                truelayer_hint,
+            ),
+            (
+                "truelayer.client_secret",
+                &self.truelayer.client_secret,
+                truelayer_hint,
+            ),
+            ("google.client_id", &self.google.client_id, google_hint),
+            (
+                "google.client_secret",
+                &self.google.client_secret,
+                google_hint,
+            ),
+        ];
+
+        let mut problems: Vec<String> = credentials
+            .iter()
+            .filter(|(_, value, _)| is_unset(value))
+            .filter(|(key, _, _)| match *key {
+                "truelayer.client_secret" => self.truelayer.client_secret_command.is_none(),
+                "google.client_secret" => self.google.client_secret_command.is_none(),
+                _ => true,
+            })
+            .map(|(key, _, hint)| format!("{} is not set, {}", key, hint))
+            .collect();
+
+        for (section, secret, command) in [
+            (
+                "truelayer",
+                &self.truelayer.client_secret,
+                &self.truelayer.client_secret_command,
+            ),
+            (
+                "google",
+                &self.google.client_secret,
+                &self.google.client_secret_command,
+            ),
+        ] {
+            if !secret.is_empty() && command.is_some() {
+                problems.push(format!(
+                    "{}.client_secret and {}.client_secret_command are both set, remove one",
+                    section, section
+                ));
+            }
+        }
+
+        if !self.truelayer.is_sandbox() && self.truelayer.client_id.contains("sandbox") {
+            problems.push(
+                "truelayer.client_id looks like a sandbox ID but doesn't start with \"sandbox-\", \
+                 so the live environment will be used"
+                    .to_string(),
+            );
+        }
+
+        let google_client_id = &self.google.client_id;
+        if !google_client_id.is_empty()
+            && !google_client_id.ends_with(".apps.googleusercontent.com")
+        {
+            problems.push(
+                "google.client_id should end with \".apps.googleusercontent.com\", \
+                 check that it's the ID of a Desktop client"
+                    .to_string(),
+            );
+        }
+
+        if self.google.spreadsheet_id.is_some() && self.google.spreadsheet_name.is_some() {
+            problems.push(
+                "google.spreadsheet_id and google.spreadsheet_name are both set, remove one"
+                    .to_string(),
+            );
+        }
+
+        if let Some(base) = &self.currency.base
+            && !is_currency_code(base)
+        {
+            problems.push(format!(
+                "currency.base must be a three letter currency code such as \"GBP\", not {:?}",
+                base
+            ));
+        }
+
+        if let Some(webhook) = &self.notify.webhook
+            && !Url::parse(&webhook.url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+        {
+            problems.push("notify.webhook.url must be an http or https URL".to_string());
+        }
+        if let Some(smtp) = &self.notify.smtp {
+            if smtp.to.is_empty() {
+                problems.push("notify.smtp.to must have at least one address".to_string());
+            }
+            if !smtp.password.is_empty() && smtp.password_command.is_some() {
+                problems.push(
+                    "notify.smtp.password and notify.smtp.password_command are both set, remove one"
+                        .to_string(),
+                );
+            }
+        }
+
+        if let Some(listen) = &self.metrics.listen
+            && listen.parse::<SocketAddr>().is_err()
+        {
+            problems.push(format!(
+                "metrics.listen must be an address and port such as \"127.0.0.1:9464\", not {:?}",
+                listen
+            ));
+        }
+
+        if self.sync.archive_after_months == Some(0) {
+            problems.push("sync.archive_after_months must be at least 1".to_string());
+        }
+
+        let mut days = vec![
+            ("sync.fetch_days".to_string(), self.sync.fetch_days, 1),
+            (
+                "sync.reconcile_days".to_string(),
+                self.sync.reconcile_days,
+                0,
+            ),
+        ];
+        for (name, card) in &self.cards {
+            if let Some(fetch_days) = card.fetch_days {
+                days.push((format!("cards.\"{}\".fetch_days", name), fetch_days, 1));
+            }
+            if let Some(reconcile_days) = card.reconcile_days {
+                days.push((
+                    format!("cards.\"{}\".reconcile_days", name),
+                    reconcile_days,
+                    0,
+                ));
+            }
+            if let Some(tab_name) = &card.tab_name
+                && (tab_name.is_empty() || tab_name.chars().count() > MAX_TAB_NAME_LENGTH)
+            {
+                problems.push(format!(
+                    "cards.\"{}\".tab_name must be between 1 and {} characters",
+                    name, MAX_TAB_NAME_LENGTH
+                ));
+            }
+            if let Some(day) = card.statement_day
+                && !(1..=31).contains(&day)
+            {
+                problems.push(format!(
+                    "cards.\"{}\".statement_day must be between 1 and 31",
+                    name
+                ));
+            }
+            if card.archive_after_months == Some(0) {
+                problems.push(format!(
+                    "cards.\"{}\".archive_after_months must be at least 1",
+                    name
+                ));
+            }
+        }
+
+        problems.extend(
+            days.into_iter()
+                .filter(|(_, days, min)| days.num_days() < *min)
+                .map(|(key, _, min)| format!("{} must be at least {}", key, min)),
+        );
+
+        problems
+    }
+
+    /// Write the config file, readable only by the current user because it contains secrets.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = toml::to_string(self)
+            .map_err(|e| AppError::Config(format!("Failed to serialize config: {}", e)))?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        // The mode only applies to new files, so restrict a file that was already there too
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        file.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Resolve the settings for a card, preferring overrides keyed by ID over those keyed by name.
+    pub fn card_settings(&self, card: &Card) -> CardSettings {
+        let overrides = self
+            .cards
+            .get(&card.id)
+            .or_else(|| self.cards.get(&card.name))
+            .cloned()
+            .unwrap_or_default();
+
+        self.settings_with(overrides, &card.name)
+    }
+
+    /// Longest fetch window of any card, to know how far back data such as exchange rates is
+    /// needed.
+    pub fn max_fetch_days(&self) -> Duration {
+        self.cards
+            .values()
+            .filter_map(|card| card.fetch_days)
+            .chain([self.sync.fetch_days])
+            .max()
+            .unwrap_or(self.sync.fetch_days)
+    }
+
+    /// Resolve the settings for a card's sheet tab, for commands that only know the tab.
+    pub fn tab_settings(&self, tab_name: &str) -> CardSettings {
+        let overrides = self.tab_overrides(tab_name).cloned().unwrap_or_default();
+        self.settings_with(overrides, tab_name)
+    }
+
+    fn settings_with(&self, overrides: CardConfig, default_tab_name: &str) -> CardSettings {
+        CardSettings {
+            tab_name: overrides
+                .tab_name
+                .unwrap_or_else(|| default_tab_name.to_string()),
+            fetch_days: overrides.fetch_days.unwrap_or(self.sync.fetch_days),
+            reconcile_days: overrides.reconcile_days.unwrap_or(self.sync.reconcile_days),
+            reconcile_strategy: overrides
+                .reconcile_strategy
+                .unwrap_or(self.sync.reconcile_strategy),
+            reconcile_tolerance: overrides
+                .reconcile_tolerance
+                .unwrap_or(self.sync.reconcile_tolerance),
+            age_warning_days: overrides.age_warning_days.or(self.sync.age_warning_days),
+            archive_after_months: overrides
+                .archive_after_months
+                .or(self.sync.archive_after_months),
+            highlight: HighlightColours {
+                debit: overrides
+                    .highlight_debit
+                    .unwrap_or(self.sync.highlight_debit),
+                credit: overrides
+                    .highlight_credit
+                    .unwrap_or(self.sync.highlight_credit),
+            },
+            statement_day: overrides.statement_day,
+        }
+    }
+
+    /// Get the ledger account for a card's sheet tab, from the `[cards]` override for the tab or
+    /// else named after the tab.
+    pub fn ledger_account(&self, tab_name: &str) -> String {
+        self.tab_overrides(tab_name)
+            .and_then(|card| card.ledger_account.clone())
+            .unwrap_or_else(|| {
+                format!(
+                    "{}:{}",
+                    self.ledger.card_account_prefix,
+                    account_component(tab_name)
+                )
+            })
+    }
+
+    /// Find the `[cards]` override for a sheet tab, by its `tab_name` or else by its key.
+    fn tab_overrides(&self, tab_name: &str) -> Option<&CardConfig> {
+        self.cards
+            .values()
+            .find(|card| card.tab_name.as_deref() == Some(tab_name))
+            .or_else(|| {
+                self.cards
+                    .iter()
+                    .find(|(key, card)| card.tab_name.is_none() && *key == tab_name)
+                    .map(|(_, card)| card)
+            })
+    }
+
+    /// Find a CSV layout by name, preferring `[csv_layouts.<name>]` over the built-in layouts.
+    pub fn csv_layout(&self, name: &str) -> Result<CsvLayout> {
+        self.csv_layouts
+            .get(name)
+            .cloned()
+            .or_else(|| CsvLayout::builtin(name))
+            .ok_or_else(|| {
+                let mut names: Vec<&str> = self.csv_layouts.keys().map(String::as_str).collect();
+                names.extend(BUILTIN_CSV_LAYOUTS);
+                names.sort();
+                names.dedup();
+                AppError::Config(format!(
+                    "CSV layout {:?} not found, available layouts: {}",
+                    name,
+                    names.join(", ")
+                ))
+            })
+    }
+
     fn xdg_dirs() -> xdg::BaseDirectories {
         xdg::BaseDirectories::with_prefix(CONFIG_DIR_PREFIX)
     }
 
+    /// Use a config file other than the default. Must be called before the config is loaded.
+    pub fn set_config_file(path: PathBuf) {
+        let _ = CONFIG_FILE.set(path);
+    }
+
+    /// Select a profile. Must be called before the config is loaded or any paths are used.
+    pub fn set_profile(name: &str) -> Result<()> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(AppError::Config(format!(
+                "Invalid profile name {:?}, use only letters, numbers, '-' and '_'",
+                name
+            )));
+        }
+
+        let _ = PROFILE.set(name.to_string());
+        Ok(())
+    }
+
+    /// Get the selected profile, if any.
+    pub fn profile() -> Option<&'static str> {
+        PROFILE.get().map(String::as_str)
+    }
+
     /// Get the config file path
     pub fn config_file() -> Result<PathBuf> {
+        if let Some(path) = CONFIG_FILE.get() {
+            return Ok(path.clone());
+        }
+
         let xdg_dirs = Self::xdg_dirs();
         xdg_dirs
             .place_config_file("config.toml")
             .map_err(|e| AppError::Config(format!("Failed to create config directory: {}", e)))
     }
 
-    /// Get the cache directory path
+    /// Get the categorisation rules file path, which is next to the config file
+    pub fn rules_file() -> Result<PathBuf> {
+        Ok(Self::config_file()?.with_file_name("rules.toml"))
+    }
+
+    /// Get the cache directory path, which is separate for each profile
     pub fn cache_dir() -> Result<PathBuf> {
         let xdg = Self::xdg_dirs();
-        xdg.get_cache_home()
-            .ok_or_else(|| AppError::Config("Failed to determine cache directory".to_string()))
+        let cache_home = xdg
+            .get_cache_home()
+            .ok_or_else(|| AppError::Config("Failed to determine cache directory".to_string()))?;
+
+        Ok(match Self::profile() {
+            Some(profile) => cache_home.join(profile),
+            None => cache_home,
+        })
     }
 
     /// Get a cache file path
     pub fn cache_file(filename: &str) -> Result<PathBuf> {
         let xdg = Self::xdg_dirs();
-        xdg.place_cache_file(filename)
+        let path = match Self::profile() {
+            Some(profile) => PathBuf::from(profile).join(filename),
+            None => PathBuf::from(filename),
+        };
+
+        xdg.place_cache_file(path)
             .map_err(|e| AppError::Config(format!("Failed to create cache file path: {}", e)))
     }
+
+    /// Get a data file path, for records that should be kept unlike the cache
+    pub fn data_file(filename: &str) -> Result<PathBuf> {
+        let xdg = Self::xdg_dirs();
+        let path = match Self::profile() {
+            Some(profile) => PathBuf::from(profile).join(filename),
+            None => PathBuf::from(filename),
+        };
+
+        xdg.place_data_file(path)
+            .map_err(|e| AppError::Config(format!("Failed to create data file path: {}", e)))
+    }
+}
+
+/// Overlay the selected profile's table onto the rest of the config.
+fn apply_profile(table: &mut toml::Table, profile: Option<&str>) -> Result<()> {
+    let profiles = match table.remove(PROFILES_KEY) {
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => {
+            return Err(AppError::Config(format!(
+                "Config key {} must be a table",
+                PROFILES_KEY
+            )));
+        }
+        None => toml::Table::new(),
+    };
+
+    let Some(profile) = profile else {
+        return Ok(());
+    };
+
+    match profiles.get(profile) {
+        Some(toml::Value::Table(overlay)) => {
+            merge_tables(table, overlay.clone());
+            Ok(())
+        }
+        _ => Err(AppError::Config(format!(
+            "Profile {:?} not found, add a [{}.{}] table to the config file",
+            profile, PROFILES_KEY, profile
+        ))),
+    }
+}
+
+/// Recursively merge `overlay` into `base`, replacing any values that aren't tables.
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Set values in the parsed config from any `CCT_*` environment variables.
+fn apply_env_overrides(
+    table: &mut toml::Table,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    for (section, key, kind) in ENV_OVERRIDES {
+        for path in env_override_paths(table, section) {
+            let name = format!(
+                "{}{}_{}",
+                ENV_PREFIX,
+                path.iter()
+                    .map(|part| env_name_part(part))
+                    .collect::<Vec<_>>()
+                    .join("_"),
+                key
+            )
+            .to_uppercase();
+            let Some(value) = env(&name) else {
+                continue;
+            };
+
+            let value =
+                match kind {
+                    EnvKind::String => toml::Value::String(value),
+                    EnvKind::Integer => toml::Value::Integer(value.parse().map_err(|e| {
+                        AppError::Config(format!("Invalid value for {}: {}", name, e))
+                    })?),
+                    EnvKind::Boolean => toml::Value::Boolean(value.parse().map_err(|e| {
+                        AppError::Config(format!("Invalid value for {}: {}", name, e))
+                    })?),
+                    EnvKind::List => toml::Value::Array(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|item| !item.is_empty())
+                            .map(|item| toml::Value::String(item.to_string()))
+                            .collect(),
+                    ),
+                };
+
+            let mut section_table = &mut *table;
+            for part in &path {
+                section_table = section_table
+                    .entry(part.clone())
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                    .as_table_mut()
+                    .ok_or_else(|| {
+                        AppError::Config(format!("Config key {} must be a table", path.join(".")))
+                    })?;
+            }
+
+            debug!(name, "Overriding config value from environment");
+            section_table.insert(key.to_string(), value);
+        }
+    }
+
+    Ok(())
+}
+
+/// Paths of the tables that a section of `ENV_OVERRIDES` refers to, with any `*` replaced by
+/// the key of each entry of that table in the config.
+fn env_override_paths(table: &toml::Table, section: &str) -> Vec<Vec<String>> {
+    let mut paths = vec![Vec::new()];
+    for part in section.split('.') {
+        if part != "*" {
+            paths
+                .iter_mut()
+                .for_each(|path| path.push(part.to_string()));
+            continue;
+        }
+
+        paths = paths
+            .into_iter()
+            .flat_map(|path| {
+                let entries = path
+                    .iter()
+                    .try_fold(table, |table, part| table.get(part)?.as_table())
+                    .map(|entries| entries.keys().cloned().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                entries.into_iter().map(move |key| {
+                    let mut path = path.clone();
+                    path.push(key);
+                    path
+                })
+            })
+            .collect();
+    }
+    paths
+}
+
+/// A config key as part of an environment variable name, which can only have letters, digits
+/// and underscores.
+fn env_name_part(part: &str) -> String {
+    part.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+/// Run a secret command, if configured, and use its output as the secret.
+fn resolve_secret(key: &str, secret: &mut String, command: Option<&str>) -> Result<()> {
+    let Some(command) = command else {
+        return Ok(());
+    };
+
+    if !secret.is_empty() {
+        return Err(AppError::Config(format!(
+            "{} and {}_command are both set, remove one",
+            key, key
+        )));
+    }
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|e| AppError::Config(format!("Failed to run {}_command: {}", key, e)))?;
+
+    if !output.status.success() {
+        return Err(AppError::Config(format!(
+            "{}_command failed ({}): {}",
+            key,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // Only use the first line, like `pass show`, and ignore trailing newlines
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|e| AppError::Config(format!("{}_command output is not UTF-8: {}", key, e)))?;
+    *secret = stdout.lines().next().unwrap_or_default().trim().to_string();
+
+    if secret.is_empty() {
+        return Err(AppError::Config(format!(
+            "{}_command didn't output a secret",
+            key
+        )));
+    }
+
+    Ok(())
+}
+
+/// Convert a name into an account name component, which must start with a capital letter or
+/// digit and only contain letters, digits and hyphens.
+fn account_component(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+
+    match words.is_empty() {
+        true => "Card".to_string(),
+        false => words.join("-"),
+    }
+}
+
+/// Whether a value looks like an ISO 4217 currency code.
+pub(crate) fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// Whether a value is empty or still the placeholder from `config.example.toml`.
+fn is_unset(value: &str) -> bool {
+    value.is_empty() || value == "TODO"
 }
 
 mod duration_days {
@@ -147,9 +1386,33 @@ mod duration_days {
     }
 }
 
+mod duration_days_option {
+    use super::*;
+
+    pub fn serialize<S>(
+        source: &Option<Duration>,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        source.map(|d| d.num_days()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let days: Option<i64> = Deserialize::deserialize(deserializer)?;
+        Ok(days.map(Duration::days))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::card::test_helpers::mock_card;
+    use rust_decimal::prelude::dec;
 
     #[test]
     fn test_config_serialization() {
@@ -157,12 +1420,15 @@ mod tests {
             truelayer: TrueLayerConfig {
                 client_id: "test_id".to_string(),
                 client_secret: "test_secret".to_string(),
+                ..Default::default()
             },
             google: GoogleConfig {
                 client_id: "test_client_id".to_string(),
                 client_secret: "test_client_secret".to_string(),
+                ..Default::default()
             },
             sync: SyncConfig::default(),
+            ..Default::default()
         };
 
         let serialized = toml::to_string(&config).unwrap();
@@ -177,6 +1443,7 @@ mod tests {
         let sync_config = SyncConfig {
             fetch_days: Duration::days(30),
             reconcile_days: Duration::days(7),
+            ..Default::default()
         };
 
         #[derive(Serialize, Deserialize)]
@@ -195,11 +1462,706 @@ mod tests {
         assert_eq!(deserialized.sync.reconcile_days.num_days(), 7);
     }
 
+    #[test]
+    fn test_parse_tolerance() {
+        assert_eq!("0".parse::<Tolerance>().unwrap(), Tolerance::Exact);
+        assert_eq!(
+            "0.50".parse::<Tolerance>().unwrap(),
+            Tolerance::Absolute(dec!(0.50))
+        );
+        assert_eq!(
+            "2.5 %".parse::<Tolerance>().unwrap(),
+            Tolerance::Percent(dec!(2.5))
+        );
+        assert!("-1".parse::<Tolerance>().is_err());
+        assert!("lots".parse::<Tolerance>().is_err());
+
+        let config: Config = toml::from_str(
+            r#"
+            [truelayer]
+            client_id = "id"
+            client_secret = "secret"
+
+            [google]
+            client_id = "id"
+            client_secret = "secret"
+
+            [sync]
+            reconcile_tolerance = 1
+
+            [cards.Amex]
+            reconcile_tolerance = "3%"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.sync.reconcile_tolerance,
+            Tolerance::Absolute(dec!(1))
+        );
+        assert_eq!(
+            config.cards["Amex"].reconcile_tolerance,
+            Some(Tolerance::Percent(dec!(3)))
+        );
+    }
+
+    #[test]
+    fn test_card_settings_defaults() {
+        let config = Config::default();
+        let card = mock_card();
+
+        assert_eq!(
+            config.card_settings(&card),
+            CardSettings {
+                tab_name: card.name.clone(),
+                fetch_days: config.sync.fetch_days,
+                reconcile_days: config.sync.reconcile_days,
+                reconcile_strategy: ReconcileStrategy::Earliest,
+                reconcile_tolerance: Tolerance::Exact,
+                age_warning_days: None,
+                archive_after_months: None,
+                highlight: HighlightColours {
+                    debit: config.sync.highlight_debit,
+                    credit: config.sync.highlight_credit,
+                },
+                statement_day: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_card_settings_overrides() {
+        let config: Config = toml::from_str(
+            r##"
+            [truelayer]
+            client_id = "id"
+            client_secret = "secret"
+
+            [google]
+            client_id = "id"
+            client_secret = "secret"
+
+            [sync]
+            reconcile_days = 30
+            age_warning_days = 45
+            archive_after_months = 12
+
+            [cards."Amex Card"]
+            reconcile_days = 90
+            age_warning_days = 20
+            archive_after_months = 24
+            tab_name = "Amex"
+            highlight_debit = "#ff0000"
+
+            [cards.acc_123]
+            fetch_days = 7
+            reconcile_strategy = "nearest"
+            "##,
+        )
+        .unwrap();
+
+        let card = mock_card();
+        let settings = config.card_settings(&card);
+        assert_eq!(
+            settings.fetch_days,
+            Duration::days(7),
+            "overrides keyed by ID should take precedence"
+        );
+        assert_eq!(settings.reconcile_days, Duration::days(30));
+        assert_eq!(settings.reconcile_strategy, ReconcileStrategy::Nearest);
+        assert_eq!(settings.tab_name, card.name);
+        assert_eq!(settings.age_warning_days, Some(45));
+        assert_eq!(settings.archive_after_months, Some(12));
+
+        let card = Card {
+            id: "acc_456".to_string(),
+            ..mock_card()
+        };
+        let settings = config.card_settings(&card);
+        assert_eq!(settings.reconcile_days, Duration::days(90));
+        assert_eq!(settings.tab_name, "Amex");
+        assert_eq!(settings.age_warning_days, Some(20));
+        assert_eq!(settings.archive_after_months, Some(24));
+        assert_eq!(settings.highlight.debit, Colour::new(0xff, 0, 0));
+        assert_eq!(settings.highlight.credit, config.sync.highlight_credit);
+    }
+
+    #[test]
+    fn test_parse_reports_unknown_keys() {
+        let (config, unknown_keys) = Config::parse(
+            r#"
+            [truelayer]
+            client_id = "id"
+            client_secret = "secret"
+            client_sekret = "typo"
+
+            [google]
+            client_id = "id"
+            client_secret = "secret"
+
+            [sync]
+            fetch_dayz = 30
+
+            [cards.Amex]
+            reconcile_days = 90
+            colour = "red"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.truelayer.client_id, "id");
+        assert_eq!(
+            unknown_keys,
+            vec![
+                "cards.Amex.colour",
+                "sync.fetch_dayz",
+                "truelayer.client_sekret"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_with_env_overrides() {
+        let env = HashMap::from([
+            ("CCT_TRUELAYER_CLIENT_SECRET", "env_secret"),
+            ("CCT_GOOGLE_CLIENT_ID", "env_id"),
+            ("CCT_GOOGLE_CLIENT_SECRET", "123"),
+            ("CCT_SYNC_FETCH_DAYS", "14"),
+        ]);
+        let (config, unknown_keys) = Config::parse_with_env(
+            r#"
+            [truelayer]
+            client_id = "id"
+            client_secret = "file_secret"
+            "#,
+            None,
+            |name| env.get(name).map(|v| v.to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(unknown_keys, Vec::<String>::new());
+        assert_eq!(config.truelayer.client_id, "id");
+        assert_eq!(config.truelayer.client_secret, "env_secret");
+        assert_eq!(config.google.client_id, "env_id");
+        assert_eq!(
+            config.google.client_secret, "123",
+            "numeric strings should stay strings"
+        );
+        assert_eq!(config.sync.fetch_days, Duration::days(14));
+        assert_eq!(config.sync.reconcile_days, Duration::days(60));
+    }
+
+    #[test]
+    fn test_parse_with_nested_env_overrides() {
+        let env = HashMap::from([
+            ("CCT_CARDS_AMEX_GOLD_FETCH_DAYS", "14"),
+            ("CCT_CARDS_ACC_123_TAB_NAME", "Visa"),
+            ("CCT_NOTIFY_SMTP_HOST", "smtp.example.com"),
+            ("CCT_NOTIFY_SMTP_FROM", "tracker@example.com"),
+            ("CCT_NOTIFY_SMTP_TO", "me@example.com, you@example.com"),
+            ("CCT_SNAPSHOTS_RETAIN", "3"),
+            ("CCT_METRICS_LISTEN", "127.0.0.1:9464"),
+        ]);
+        let (config, unknown_keys) = Config::parse_with_env(
+            r#"
+            [truelayer]
+            client_id = "id"
+            client_secret = "secret"
+
+            [google]
+            client_id = "id"
+            client_secret = "secret"
+
+            [cards."Amex Gold"]
+            reconcile_days = 30
+
+            [cards.acc_123]
+            "#,
+            None,
+            |name| env.get(name).map(|v| v.to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(unknown_keys, Vec::<String>::new());
+        assert_eq!(
+            config.cards["Amex Gold"].fetch_days,
+            Some(Duration::days(14))
+        );
+        assert_eq!(config.cards["acc_123"].tab_name.as_deref(), Some("Visa"));
+        let smtp = config.notify.smtp.unwrap();
+        assert_eq!(smtp.host, "smtp.example.com");
+        assert_eq!(smtp.to, vec!["me@example.com", "you@example.com"]);
+        assert_eq!(config.snapshots.retain, 3);
+        assert_eq!(config.metrics.listen.as_deref(), Some("127.0.0.1:9464"));
+    }
+
+    /// Every key in the config, so that adding one without an environment override fails
+    /// `test_every_key_has_env_override`.
+    const EVERY_KEY: &str = r##"
+        [truelayer]
+        client_id = "id"
+        client_secret = "secret"
+        client_secret_command = "pass"
+
+        [google]
+        client_id = "id"
+        client_secret = "secret"
+        client_secret_command = "pass"
+        spreadsheet_id = "id"
+        spreadsheet_name = "name"
+        archive_spreadsheet_id = "id"
+
+        [sync]
+        fetch_days = 1
+        reconcile_days = 1
+        reconcile_strategy = "nearest"
+        reconcile_tolerance = "1%"
+        age_warning_days = 1
+        history_tab = true
+        archive_after_months = 1
+        highlight_debit = "#ffffff"
+        highlight_credit = "#ffffff"
+
+        [cards.Amex]
+        fetch_days = 1
+        reconcile_days = 1
+        tab_name = "Amex"
+        reconcile_strategy = "nearest"
+        reconcile_tolerance = "1%"
+        age_warning_days = 1
+        archive_after_months = 1
+        highlight_debit = "#ffffff"
+        highlight_credit = "#ffffff"
+        ledger_account = "Liabilities:Amex"
+        statement_day = 1
+
+        [ledger]
+        card_account_prefix = "Liabilities"
+        expense_account = "Expenses"
+        payment_account = "Assets"
+
+        [currency]
+        base = "GBP"
+        rates_file = "rates.csv"
+
+        [notify]
+        consent_warning_days = 1
+
+        [notify.webhook]
+        url = "https://example.com"
+        events = ["errors"]
+
+        [notify.smtp]
+        host = "smtp.example.com"
+        port = 465
+        username = "me"
+        password = "secret"
+        password_command = "pass"
+        from = "me@example.com"
+        to = ["me@example.com"]
+        events = ["errors"]
+
+        [notify.desktop]
+        events = ["errors"]
+
+        [metrics]
+        textfile = "metrics.prom"
+        listen = "127.0.0.1:9464"
+
+        [snapshots]
+        retain = 1
+
+        [csv_layouts.bank]
+        date = "Date"
+        date_format = "%d/%m/%Y"
+        description = "Description"
+        amount = "Amount"
+        charges_positive = true
+        id = "Reference"
+        currency = "Currency"
+    "##;
+
+    /// Add the (section, key) of every value in a table to `keys`, with `*` for the entries of
+    /// tables keyed by name.
+    fn collect_keys(table: &toml::Table, section: &str, keys: &mut Vec<(String, String)>) {
+        for (key, value) in table {
+            match value.as_table() {
+                Some(child) => {
+                    let name = match section {
+                        "cards" | "csv_layouts" => "*",
+                        _ => key,
+                    };
+                    let child_section = match section {
+                        "" => name.to_string(),
+                        _ => format!("{}.{}", section, name),
+                    };
+                    collect_keys(child, &child_section, keys);
+                }
+                None => keys.push((section.to_string(), key.clone())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_key_has_env_override() {
+        let (config, unknown_keys) = Config::parse_with_env(EVERY_KEY, None, |_| None).unwrap();
+        assert_eq!(unknown_keys, Vec::<String>::new());
+
+        // Serialise the config so that keys missing from EVERY_KEY are noticed, as they'd be
+        // left out
+        let table: toml::Table = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        let expected: toml::Table = toml::from_str(EVERY_KEY).unwrap();
+        let mut keys = Vec::new();
+        collect_keys(&table, "", &mut keys);
+        let mut expected_keys = Vec::new();
+        collect_keys(&expected, "", &mut expected_keys);
+        keys.sort();
+        expected_keys.sort();
+        assert_eq!(keys, expected_keys, "EVERY_KEY should set every key");
+
+        let missing: Vec<&(String, String)> = keys
+            .iter()
+            .filter(|(section, key)| {
+                !ENV_OVERRIDES
+                    .iter()
+                    .any(|(s, k, _)| s == section && k == key)
+            })
+            .collect();
+        assert_eq!(missing, Vec::<&(String, String)>::new());
+    }
+
+    #[test]
+    fn test_save_restricts_existing_file() {
+        let path = std::env::temp_dir().join(format!(
+            "credit-card-tracker-config-save-{}",
+            std::process::id()
+        ));
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let (config, _) = Config::parse_with_env(EVERY_KEY, None, |_| None).unwrap();
+        config.save(&path).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_parse_with_invalid_env_override() {
+        let env = HashMap::from([("CCT_SYNC_FETCH_DAYS", "two weeks")]);
+        let result = Config::parse_with_env("", None, |name| env.get(name).map(|v| v.to_string()));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_with_profile() {
+        let contents = r#"
+            [truelayer]
+            client_id = "id"
+            client_secret = "secret"
+
+            [google]
+            client_id = "id"
+            client_secret = "secret"
+
+            [cards.Amex]
+            reconcile_days = 90
+
+            [profiles.business.truelayer]
+            client_id = "business_id"
+
+            [profiles.business.cards.Amex]
+            fetch_days = 7
+
+            [profiles.business.cards.Visa]
+            reconcile_days = 7
+        "#;
+
+        let (config, unknown_keys) = Config::parse_with_env(contents, None, |_| None).unwrap();
+        assert_eq!(unknown_keys, Vec::<String>::new());
+        assert_eq!(config.truelayer.client_id, "id");
+        assert_eq!(config.cards.len(), 1);
+
+        let (config, _) = Config::parse_with_env(contents, Some("business"), |_| None).unwrap();
+        assert_eq!(config.truelayer.client_id, "business_id");
+        assert_eq!(config.truelayer.client_secret, "secret");
+        assert_eq!(config.cards.len(), 2);
+        assert_eq!(config.cards["Amex"].fetch_days, Some(Duration::days(7)));
+        assert_eq!(
+            config.cards["Amex"].reconcile_days,
+            Some(Duration::days(90)),
+            "profile tables should be merged, not replaced"
+        );
+
+        let result = Config::parse_with_env(contents, Some("missing"), |_| None);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_set_profile_rejects_invalid_names() {
+        assert!(Config::set_profile("").is_err());
+        assert!(Config::set_profile("../personal").is_err());
+        assert!(Config::set_profile("my profile").is_err());
+    }
+
+    #[test]
+    fn test_resolve_secret() {
+        let mut secret = String::new();
+        resolve_secret("google.client_secret", &mut secret, None).unwrap();
+        assert_eq!(secret, "", "should be unchanged without a command");
+
+        resolve_secret(
+            "google.client_secret",
+            &mut secret,
+            Some("printf 's3cret\\nextra\\n'"),
+        )
+        .unwrap();
+        assert_eq!(secret, "s3cret", "should use the first line of output");
+
+        let mut secret = "already set".to_string();
+        assert!(resolve_secret("google.client_secret", &mut secret, Some("echo s3cret")).is_err());
+
+        let mut secret = String::new();
+        assert!(resolve_secret("google.client_secret", &mut secret, Some("exit 1")).is_err());
+        assert!(resolve_secret("google.client_secret", &mut secret, Some("true")).is_err());
+    }
+
+    #[test]
+    fn test_validate_valid() {
+        let config = Config {
+            truelayer: TrueLayerConfig {
+                client_id: "sandbox-abc123".to_string(),
+                client_secret: "secret".to_string(),
+                ..Default::default()
+            },
+            google: GoogleConfig {
+                client_id: "123.apps.googleusercontent.com".to_string(),
+                client_secret: "secret".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(config.validate(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_validate_problems() {
+        let config = Config {
+            truelayer: TrueLayerConfig {
+                client_id: "abc-sandbox".to_string(),
+                client_secret: "TODO".to_string(),
+                ..Default::default()
+            },
+            google: GoogleConfig {
+                client_id: "123".to_string(),
+                client_secret: "secret".to_string(),
+                client_secret_command: Some("pass show google".to_string()),
+                spreadsheet_id: Some("abc".to_string()),
+                spreadsheet_name: Some("Cards".to_string()),
+                archive_spreadsheet_id: None,
+            },
+            sync: SyncConfig {
+                fetch_days: Duration::days(0),
+                reconcile_days: Duration::days(-1),
+                archive_after_months: Some(0),
+                ..Default::default()
+            },
+            cards: HashMap::from([(
+                "Amex".to_string(),
+                CardConfig {
+                    fetch_days: Some(Duration::days(-7)),
+                    tab_name: Some(String::new()),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+
+        let problems = config.validate();
+        let expected = [
+            "truelayer.client_secret is not set",
+            "google.client_secret and google.client_secret_command are both set",
+            "truelayer.client_id looks like a sandbox ID",
+            "google.client_id should end with",
+            "google.spreadsheet_id and google.spreadsheet_name are both set",
+            "sync.archive_after_months must be at least 1",
+            "cards.\"Amex\".tab_name must be between 1 and 100 characters",
+            "sync.fetch_days must be at least 1",
+            "sync.reconcile_days must be at least 0",
+            "cards.\"Amex\".fetch_days must be at least 1",
+        ];
+        assert_eq!(problems.len(), expected.len(), "{:?}", problems);
+        for (problem, expected) in problems.iter().zip(expected) {
+            assert!(problem.starts_with(expected), "{:?}", problem);
+        }
+    }
+
+    #[test]
+    fn test_notify_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [truelayer]
+            client_id = "id"
+            client_secret = "secret"
+
+            [google]
+            client_id = "id"
+            client_secret = "secret"
+
+            [notify.webhook]
+            url = "ftp://example.com/hook"
+            events = ["errors", "consent_expiry"]
+
+            [notify.smtp]
+            host = "smtp.example.com"
+            from = "tracker@example.com"
+            to = []
+
+            [notify.desktop]
+            "#,
+        )
+        .unwrap();
+
+        let notify = &config.notify;
+        assert_eq!(notify.consent_warning_days, 7);
+        assert_eq!(
+            notify.webhook.as_ref().unwrap().events,
+            vec![NotifyEvent::Errors, NotifyEvent::ConsentExpiry]
+        );
+        assert_eq!(notify.smtp.as_ref().unwrap().port, 587);
+        assert_eq!(notify.desktop, Some(DesktopConfig::default()));
+
+        let problems = config.validate();
+        assert!(problems.contains(&"notify.webhook.url must be an http or https URL".to_string()));
+        assert!(problems.contains(&"notify.smtp.to must have at least one address".to_string()));
+    }
+
+    #[test]
+    fn test_metrics_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [truelayer]
+            client_id = "id"
+            client_secret = "secret"
+
+            [google]
+            client_id = "id"
+            client_secret = "secret"
+
+            [metrics]
+            textfile = "/var/lib/node_exporter/textfile/cards.prom"
+            listen = "localhost"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.metrics.textfile,
+            Some(PathBuf::from("/var/lib/node_exporter/textfile/cards.prom"))
+        );
+        assert!(config.validate().contains(
+            &"metrics.listen must be an address and port such as \"127.0.0.1:9464\", not \"localhost\""
+                .to_string()
+        ));
+
+        let config = Config {
+            metrics: MetricsConfig {
+                textfile: None,
+                listen: Some("127.0.0.1:9464".to_string()),
+            },
+            ..config
+        };
+        assert!(
+            !config
+                .validate()
+                .iter()
+                .any(|problem| problem.starts_with("metrics"))
+        );
+    }
+
+    #[test]
+    fn test_csv_layout() {
+        let config: Config = toml::from_str(
+            r##"
+            [truelayer]
+            client_id = "id"
+            client_secret = "secret"
+
+            [google]
+            client_id = "id"
+            client_secret = "secret"
+
+            [csv_layouts.amex]
+            date = "Posted"
+            description = "Merchant"
+            amount = "Value"
+            "##,
+        )
+        .unwrap();
+
+        let layout = config.csv_layout("amex").unwrap();
+        assert_eq!(layout.date, "Posted", "config should take precedence");
+        assert_eq!(layout.date_format, "%d/%m/%Y");
+        assert!(!layout.charges_positive);
+
+        let layout = config.csv_layout("barclaycard").unwrap();
+        assert_eq!(layout, CsvLayout::builtin("barclaycard").unwrap());
+
+        let err = config.csv_layout("monzo").unwrap_err().to_string();
+        assert!(err.contains("amex, barclaycard"), "{}", err);
+    }
+
+    #[test]
+    fn test_ledger_account() {
+        let config: Config = toml::from_str(
+            r##"
+            [truelayer]
+            client_id = "id"
+            client_secret = "secret"
+
+            [google]
+            client_id = "id"
+            client_secret = "secret"
+
+            [cards.acc_123]
+            tab_name = "Amex"
+            ledger_account = "Liabilities:Amex"
+
+            [cards.Visa]
+            ledger_account = "Liabilities:Visa"
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(config.ledger_account("Amex"), "Liabilities:Amex");
+        assert_eq!(config.ledger_account("Visa"), "Liabilities:Visa");
+        assert_eq!(
+            config.ledger_account("British Airways American Express® C"),
+            "Liabilities:CreditCards:British-Airways-American-Express-C"
+        );
+        assert_eq!(config.ledger_account("®"), "Liabilities:CreditCards:Card");
+    }
+
+    #[test]
+    fn test_colour_parsing() {
+        assert_eq!(
+            "#b2E8b2".parse::<Colour>().unwrap(),
+            Colour::new(0xb2, 0xe8, 0xb2)
+        );
+        assert_eq!(Colour::new(0xb2, 0xe8, 0xb2).to_string(), "#b2e8b2");
+        assert!("b2e8b2".parse::<Colour>().is_err());
+        assert!("#b2e8b".parse::<Colour>().is_err());
+        assert!("#b2e8bz".parse::<Colour>().is_err());
+    }
+
     #[test]
     fn test_environment_sandbox() {
         let config = TrueLayerConfig {
             client_id: "sandbox-abc123".to_string(),
             client_secret: "secret".to_string(),
+            ..Default::default()
         };
         assert!(config.is_sandbox());
         assert_eq!(config.auth_url(), "https://auth.truelayer-sandbox.com");
@@ -212,6 +2174,7 @@ mod tests {
         let config = TrueLayerConfig {
             client_id: "live-abc123".to_string(),
             client_secret: "secret".to_string(),
+            ..Default::default()
         };
         assert!(!config.is_sandbox());
         assert_eq!(config.auth_url(), "https://auth.truelayer.com");
@@ -0,0 +1,148 @@
+use super::ExchangeRates;
+use crate::error::{AppError, Result};
+use chrono::{Days, NaiveDate};
+use rust_decimal::Decimal;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// Cache of the daily reference rates downloaded from the ECB.
+pub(super) const CACHE_FILE: &str = "ecb-rates.csv";
+
+/// Daily euro reference rates for every currency, as CSV.
+const ECB_RATES_URL: &str = "https://data-api.ecb.europa.eu/service/data/EXR/D..EUR.SP00.A";
+
+/// How long downloaded rates are used before checking for newer ones.
+const CACHE_MAX_AGE: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Load ECB rates from the cache, downloading them again if the cache is stale or doesn't go
+/// back as far as `from_date`.
+pub(super) async fn load(cache_path: &Path, from_date: NaiveDate) -> Result<ExchangeRates> {
+    let cached = match cache_path.exists() {
+        true => Some(parse(&fs::read_to_string(cache_path)?)?),
+        false => None,
+    };
+
+    let is_fresh = fs::metadata(cache_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < CACHE_MAX_AGE);
+    let start = match cached.as_ref().and_then(ExchangeRates::first_date) {
+        Some(first_date) if is_fresh && first_date <= from_date => {
+            return Ok(cached.unwrap_or_default());
+        }
+        Some(first_date) => first_date.min(from_date),
+        None => from_date,
+    };
+    // Go back far enough to have a rate for transactions at weekends and holidays
+    let start = start
+        .checked_sub_days(Days::new(super::MAX_RATE_AGE_DAYS))
+        .unwrap_or(start);
+
+    match download(start).await {
+        Ok(contents) => {
+            let rates = parse(&contents)?;
+            fs::write(cache_path, contents)?;
+            info!(from = %start, "Exchange rates downloaded from the ECB");
+            Ok(rates)
+        }
+        Err(e) => match cached {
+            Some(rates) => {
+                warn!(error = %e, "Failed to download exchange rates, using cached rates");
+                Ok(rates)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+async fn download(start: NaiveDate) -> Result<String> {
+    let response = reqwest::Client::new()
+        .get(ECB_RATES_URL)
+        .query(&[
+            ("format", "csvdata"),
+            ("detail", "dataonly"),
+            ("startPeriod", &start.to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(response.text().await?)
+}
+
+/// Parse the ECB's CSV, which has a row per currency and day with the number of units of the
+/// currency per euro.
+fn parse(contents: &str) -> Result<ExchangeRates> {
+    let mut reader = csv::Reader::from_reader(contents.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| AppError::Other(e.into()))?
+        .clone();
+    let column = |name: &str| {
+        headers.iter().position(|h| h == name).ok_or_else(|| {
+            AppError::Other(anyhow::anyhow!(
+                "ECB exchange rates are missing the {} column",
+                name
+            ))
+        })
+    };
+    let (currency_idx, date_idx, rate_idx) = (
+        column("CURRENCY")?,
+        column("TIME_PERIOD")?,
+        column("OBS_VALUE")?,
+    );
+
+    let mut rates = ExchangeRates::new("EUR");
+    for record in reader.records() {
+        let record = record.map_err(|e| AppError::Other(e.into()))?;
+        let (Some(currency), Some(date), Some(rate)) = (
+            record.get(currency_idx),
+            record.get(date_idx),
+            record.get(rate_idx),
+        ) else {
+            continue;
+        };
+
+        // Days without a rate have an empty or NaN value
+        let (Ok(date), Ok(rate)) = (
+            NaiveDate::parse_from_str(date, "%Y-%m-%d"),
+            Decimal::from_str(rate),
+        ) else {
+            continue;
+        };
+        rates.insert(date, currency, rate);
+    }
+
+    Ok(rates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::dec;
+
+    #[test]
+    fn test_parse() {
+        let rates = parse(
+            "KEY,FREQ,CURRENCY,CURRENCY_DENOM,EXR_TYPE,EXR_SUFFIX,TIME_PERIOD,OBS_VALUE\n\
+             EXR.D.GBP.EUR.SP00.A,D,GBP,EUR,SP00,A,2025-01-02,0.8300\n\
+             EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2025-01-02,1.0375\n\
+             EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2025-01-03,NaN\n",
+        )
+        .unwrap();
+
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+        assert_eq!(rates.first_date(), Some(date(2)));
+        assert_eq!(
+            rates.convert(dec!(10.375), "USD", "GBP", date(3)),
+            Some(dec!(8.30)),
+            "days without a rate should use the previous day's"
+        );
+
+        assert!(parse("KEY,CURRENCY\n").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+mod ecb;
+
+use crate::config::{Config, CurrencyConfig};
+use crate::error::{AppError, Result};
+use crate::models::Transaction;
+use chrono::{Days, NaiveDate};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::str::FromStr;
+use tracing::debug;
+
+/// Days to look back for a rate when there isn't one on the transaction date, such as at
+/// weekends and on bank holidays.
+const MAX_RATE_AGE_DAYS: u64 = 7;
+
+/// Daily exchange rates, as units of each currency per unit of the quote currency.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExchangeRates {
+    quote: String,
+    rates: BTreeMap<NaiveDate, HashMap<String, Decimal>>,
+}
+
+impl ExchangeRates {
+    pub fn new(quote: &str) -> Self {
+        Self {
+            quote: quote.to_string(),
+            rates: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, date: NaiveDate, currency: &str, rate: Decimal) {
+        self.rates
+            .entry(date)
+            .or_default()
+            .insert(currency.to_string(), rate);
+    }
+
+    /// Earliest date with any rates.
+    pub fn first_date(&self) -> Option<NaiveDate> {
+        self.rates.keys().next().copied()
+    }
+
+    /// Read rates from CSV with `date,currency,rate` columns.
+    pub fn from_csv(contents: &str, quote: &str) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(contents.as_bytes());
+
+        let mut rates = Self::new(quote);
+        for (idx, record) in reader.records().enumerate() {
+            let invalid = |message: &str| {
+                AppError::Config(format!("Exchange rates line {}: {}", idx + 2, message))
+            };
+
+            let record = record.map_err(|e| invalid(&e.to_string()))?;
+            let (Some(date), Some(currency), Some(rate)) =
+                (record.get(0), record.get(1), record.get(2))
+            else {
+                return Err(invalid("expected date, currency and rate"));
+            };
+
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| invalid(&format!("invalid date {:?}", date)))?;
+            let rate = Decimal::from_str(rate)
+                .ok()
+                .filter(|rate| rate.is_sign_positive() && !rate.is_zero())
+                .ok_or_else(|| invalid(&format!("invalid rate {:?}", rate)))?;
+            rates.insert(date, currency, rate);
+        }
+
+        Ok(rates)
+    }
+
+    /// Get the rate for a currency on a date, or the latest rate in the days before it.
+    fn rate_on(&self, currency: &str, date: NaiveDate) -> Option<Decimal> {
+        if currency == self.quote {
+            return Some(Decimal::ONE);
+        }
+
+        let earliest = date.checked_sub_days(Days::new(MAX_RATE_AGE_DAYS))?;
+        self.rates
+            .range(earliest..=date)
+            .rev()
+            .find_map(|(_, rates)| rates.get(currency).copied())
+    }
+
+    /// Convert an amount between currencies at the rate on a date, rounded to the cent.
+    pub fn convert(
+        &self,
+        amount: Decimal,
+        from: &str,
+        to: &str,
+        date: NaiveDate,
+    ) -> Option<Decimal> {
+        if from == to {
+            return Some(amount);
+        }
+
+        let from_rate = self.rate_on(from, date)?;
+        let to_rate = self.rate_on(to, date)?;
+        Some((amount / from_rate * to_rate).round_dp(2))
+    }
+}
+
+/// Converts transaction amounts into the base currency.
+#[derive(Debug, Clone)]
+pub struct CurrencyConverter {
+    base: String,
+    rates: ExchangeRates,
+}
+
+impl CurrencyConverter {
+    pub fn new(base: &str, rates: ExchangeRates) -> Self {
+        Self {
+            base: base.to_string(),
+            rates,
+        }
+    }
+
+    /// Load the rates needed to convert transactions from `from_date` onwards, from the rates
+    /// file or else the ECB. Returns `None` if no base currency is configured.
+    pub async fn load(config: &CurrencyConfig, from_date: NaiveDate) -> Result<Option<Self>> {
+        let Some(base) = &config.base else {
+            return Ok(None);
+        };
+
+        let rates = match &config.rates_file {
+            Some(path) => {
+                let contents = fs::read_to_string(path).map_err(|e| {
+                    AppError::Config(format!("Failed to read rates file {:?}: {}", path, e))
+                })?;
+                ExchangeRates::from_csv(&contents, base)?
+            }
+            None => ecb::load(&Config::cache_file(ecb::CACHE_FILE)?, from_date).await?,
+        };
+        debug!(base, first_date = ?rates.first_date(), "Exchange rates loaded");
+
+        Ok(Some(Self::new(base, rates)))
+    }
+
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Convert a transaction's amount into the base currency at the rate on its date, if
+    /// there is one.
+    pub fn convert(&self, transaction: &Transaction) -> Option<Decimal> {
+        self.rates.convert(
+            transaction.amount,
+            &transaction.currency,
+            &self.base,
+            transaction.timestamp.date_naive(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::dec;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap()
+    }
+
+    fn mock_rates() -> ExchangeRates {
+        let mut rates = ExchangeRates::new("EUR");
+        rates.insert(date(2), "GBP", dec!(0.80));
+        rates.insert(date(2), "USD", dec!(1.00));
+        rates.insert(date(3), "GBP", dec!(0.85));
+        rates.insert(date(3), "USD", dec!(1.10));
+        rates
+    }
+
+    #[test]
+    fn test_convert_uses_rate_on_date() {
+        let rates = mock_rates();
+
+        assert_eq!(
+            rates.convert(dec!(-10.00), "USD", "GBP", date(2)),
+            Some(dec!(-8.00))
+        );
+        assert_eq!(
+            rates.convert(dec!(-11.00), "USD", "GBP", date(3)),
+            Some(dec!(-8.50))
+        );
+        assert_eq!(
+            rates.convert(dec!(10.00), "EUR", "GBP", date(3)),
+            Some(dec!(8.50))
+        );
+        assert_eq!(
+            rates.convert(dec!(10.00), "GBP", "GBP", date(1)),
+            Some(dec!(10.00))
+        );
+    }
+
+    #[test]
+    fn test_convert_falls_back_to_earlier_rates() {
+        let rates = mock_rates();
+
+        // Weekend, so the rate from Friday is used
+        assert_eq!(
+            rates.convert(dec!(11.00), "USD", "GBP", date(5)),
+            Some(dec!(8.50))
+        );
+        assert_eq!(rates.convert(dec!(11.00), "USD", "GBP", date(1)), None);
+        assert_eq!(rates.convert(dec!(11.00), "USD", "GBP", date(20)), None);
+        assert_eq!(rates.convert(dec!(11.00), "JPY", "GBP", date(3)), None);
+    }
+
+    #[test]
+    fn test_from_csv() {
+        let rates = ExchangeRates::from_csv(
+            "date,currency,rate\n2025-01-02,USD,1.25\n2025-01-02, EUR ,1.20\n",
+            "GBP",
+        )
+        .unwrap();
+
+        assert_eq!(rates.first_date(), Some(date(2)));
+        assert_eq!(
+            rates.convert(dec!(12.50), "USD", "GBP", date(2)),
+            Some(dec!(10.00))
+        );
+        assert_eq!(
+            rates.convert(dec!(10.00), "GBP", "EUR", date(2)),
+            Some(dec!(12.00))
+        );
+
+        let error = ExchangeRates::from_csv("date,currency,rate\n2025-01-02,USD,0\n", "GBP")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 2"), "{}", error);
+    }
+}
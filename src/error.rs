@@ -14,6 +14,9 @@ pub enum AppError {
     #[error("Configuration error: {0}")]
     Config(String),
 
+    #[error("Import error: {0}")]
+    Import(String),
+
     #[error("HTTP request failed: {0}")]
     Http(#[from] reqwest::Error),
 
@@ -0,0 +1,62 @@
+use super::ExportRecord;
+use crate::error::{AppError, Result};
+use crate::models::{ToSheetRows, Transaction};
+use serde_json::Value;
+use std::io::Write;
+
+/// Write records as CSV, with a "Card" column followed by the same columns as the spreadsheet.
+pub(super) fn write(records: &[ExportRecord], writer: &mut impl Write) -> Result<()> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+
+    let transactions: Vec<Transaction> = records.iter().map(|r| r.transaction.clone()).collect();
+    let rows = transactions.to_sheet_rows()?;
+
+    for (idx, row) in rows.iter().enumerate() {
+        let card = match idx {
+            0 => "Card",
+            _ => &records[idx - 1].card,
+        };
+
+        let fields = std::iter::once(card.to_string()).chain(row.iter().map(|v| match v {
+            Value::Null => String::new(),
+            _ => Transaction::value_to_string(v),
+        }));
+
+        csv_writer
+            .write_record(fields)
+            .map_err(|e| AppError::Other(e.into()))?;
+    }
+
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::test_helpers::mock_records;
+
+    #[test]
+    fn test_write_csv() {
+        let mut output = Vec::new();
+        write(&mock_records(), &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Card,Timestamp,Description,Merchant,Amount,Currency,Converted Amount,Type,ID,Matched ID,Match Confidence,Age (days),Comments,Category,Tags,Rule,Statement,Statement Total\n\
+             Amex,2024-11-23T10:00:00Z,mock transaction: tx_1,,-12.34,GBP,,Debit,tx_1,tx_2,,,Dinner & drinks,,,,,\n\
+             Amex,2024-11-24T10:00:00Z,mock transaction: tx_2,,12.34,GBP,,Credit,tx_2,tx_1,,,,,,,,\n"
+        );
+    }
+
+    #[test]
+    fn test_write_csv_empty() {
+        let mut output = Vec::new();
+        write(&[], &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Card,Timestamp,Description,Merchant,Amount,Currency,Converted Amount,Type,ID,Matched ID,Match Confidence,Age (days),Comments,Category,Tags,Rule,Statement,Statement Total\n"
+        );
+    }
+}
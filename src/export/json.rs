@@ -0,0 +1,92 @@
+use super::ExportRecord;
+use crate::error::Result;
+use crate::models::Transaction;
+use serde::Serialize;
+use std::io::Write;
+
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    #[serde(rename = "Card")]
+    card: &'a str,
+    #[serde(flatten)]
+    transaction: &'a Transaction,
+}
+
+/// Write records as newline-delimited JSON, using the same keys as the spreadsheet columns.
+pub(super) fn write(records: &[ExportRecord], writer: &mut impl Write) -> Result<()> {
+    for record in records {
+        let json = JsonRecord {
+            card: &record.card,
+            transaction: &record.transaction,
+        };
+        serde_json::to_writer(&mut *writer, &json)?;
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::test_helpers::mock_records;
+    use serde_json::{Value, json};
+
+    #[test]
+    fn test_write_json() {
+        let mut output = Vec::new();
+        write(&mock_records(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                json!({
+                    "Card": "Amex",
+                    "Timestamp": "2024-11-23T10:00:00Z",
+                    "Description": "mock transaction: tx_1",
+                    "Merchant": null,
+                    "Amount": "-12.34",
+                    "Currency": "GBP",
+                    "Converted Amount": null,
+                    "Type": "Debit",
+                    "ID": "tx_1",
+                    "Matched ID": "tx_2",
+                    "Match Confidence": null,
+                    "Age (days)": null,
+                    "Comments": "Dinner & drinks",
+                    "Category": null,
+                    "Tags": null,
+                    "Rule": null,
+                    "Statement": null,
+                    "Statement Total": null,
+                }),
+                json!({
+                    "Card": "Amex",
+                    "Timestamp": "2024-11-24T10:00:00Z",
+                    "Description": "mock transaction: tx_2",
+                    "Merchant": null,
+                    "Amount": "12.34",
+                    "Currency": "GBP",
+                    "Converted Amount": null,
+                    "Type": "Credit",
+                    "ID": "tx_2",
+                    "Matched ID": "tx_1",
+                    "Match Confidence": null,
+                    "Age (days)": null,
+                    "Comments": null,
+                    "Category": null,
+                    "Tags": null,
+                    "Rule": null,
+                    "Statement": null,
+                    "Statement Total": null,
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use super::ExportRecord;
+use crate::config::LedgerConfig;
+use crate::error::Result;
+use crate::models::Transaction;
+use crate::models::transaction::TransactionType;
+use std::io::Write;
+
+/// Write records as beancount transactions, with matched pairs sharing a `^match-` link.
+pub(super) fn write_beancount(
+    records: &[ExportRecord],
+    config: &LedgerConfig,
+    writer: &mut impl Write,
+) -> Result<()> {
+    for record in records {
+        let transaction = &record.transaction;
+
+        write!(
+            writer,
+            "{} * {}",
+            transaction.timestamp.format("%Y-%m-%d"),
+            beancount_string(&transaction.description)
+        )?;
+        if let Some(link) = match_link(transaction) {
+            write!(writer, " ^{}", link)?;
+        }
+        writeln!(writer)?;
+
+        writeln!(writer, "  id: {}", beancount_string(&transaction.id))?;
+        if let Some(matched_id) = &transaction.matched_id {
+            writeln!(writer, "  matched_id: {}", beancount_string(matched_id))?;
+        }
+        if let Some(comments) = &transaction.comments {
+            writeln!(writer, "  comment: {}", beancount_string(comments))?;
+        }
+
+        write_postings(writer, record, config, "  ")?;
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Write records as ledger transactions, which hledger can also read, with the IDs as tags.
+pub(super) fn write_ledger(
+    records: &[ExportRecord],
+    config: &LedgerConfig,
+    writer: &mut impl Write,
+) -> Result<()> {
+    for record in records {
+        let transaction = &record.transaction;
+
+        writeln!(
+            writer,
+            "{} * {}",
+            transaction.timestamp.format("%Y/%m/%d"),
+            single_line(&transaction.description)
+        )?;
+
+        writeln!(writer, "    ; id: {}", single_line(&transaction.id))?;
+        if let Some(matched_id) = &transaction.matched_id {
+            writeln!(writer, "    ; matched_id: {}", single_line(matched_id))?;
+        }
+        if let Some(comments) = &transaction.comments {
+            for line in comments.lines() {
+                writeln!(writer, "    ; {}", line)?;
+            }
+        }
+
+        write_postings(writer, record, config, "    ")?;
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Write the posting to the card's account and the balancing posting, whose amount is implied.
+fn write_postings(
+    writer: &mut impl Write,
+    record: &ExportRecord,
+    config: &LedgerConfig,
+    indent: &str,
+) -> Result<()> {
+    let transaction = &record.transaction;
+    let counter_account = match transaction.type_ {
+        TransactionType::Debit => &config.expense_account,
+        TransactionType::Credit => &config.payment_account,
+    };
+
+    writeln!(
+        writer,
+        "{}{}  {} {}",
+        indent, record.account, transaction.amount, transaction.currency
+    )?;
+    writeln!(writer, "{}{}", indent, counter_account)?;
+
+    Ok(())
+}
+
+/// Name of a link shared by both transactions of a matched pair.
+fn match_link(transaction: &Transaction) -> Option<String> {
+    let matched_id = transaction.matched_id.as_deref()?;
+    let id = transaction.id.as_str().min(matched_id);
+
+    let id: String = id
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/' | '.') {
+                true => c,
+                false => '-',
+            },
+        )
+        .collect();
+    Some(format!("match-{}", id))
+}
+
+fn beancount_string(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        single_line(value)
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+    )
+}
+
+fn single_line(value: &str) -> String {
+    value.lines().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::test_helpers::mock_records;
+
+    #[test]
+    fn test_write_beancount() {
+        let mut output = Vec::new();
+        write_beancount(&mock_records(), &LedgerConfig::default(), &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+2024-11-23 * \"mock transaction: tx_1\" ^match-tx_1
+  id: \"tx_1\"
+  matched_id: \"tx_2\"
+  comment: \"Dinner & drinks\"
+  Liabilities:Amex  -12.34 GBP
+  Expenses:Uncategorised
+
+2024-11-24 * \"mock transaction: tx_2\" ^match-tx_1
+  id: \"tx_2\"
+  matched_id: \"tx_1\"
+  Liabilities:Amex  12.34 GBP
+  Assets:Unknown
+
+"
+        );
+    }
+
+    #[test]
+    fn test_write_ledger() {
+        let mut records = mock_records();
+        records[0].transaction.comments = Some("Dinner\nand drinks".to_string());
+        records.truncate(1);
+
+        let mut output = Vec::new();
+        write_ledger(&records, &LedgerConfig::default(), &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+2024/11/23 * mock transaction: tx_1
+    ; id: tx_1
+    ; matched_id: tx_2
+    ; Dinner
+    ; and drinks
+    Liabilities:Amex  -12.34 GBP
+    Expenses:Uncategorised
+
+"
+        );
+    }
+
+    #[test]
+    fn test_beancount_string() {
+        assert_eq!(
+            beancount_string("a \"quoted\"\\path\nline"),
+            "\"a \\\"quoted\\\"\\\\path line\""
+        );
+    }
+
+    #[test]
+    fn test_match_link() {
+        let mut transaction = mock_records().remove(0).transaction;
+        transaction.id = "tx#9".to_string();
+        transaction.matched_id = Some("tx 8".to_string());
+        assert_eq!(match_link(&transaction).as_deref(), Some("match-tx-8"));
+
+        transaction.matched_id = None;
+        assert_eq!(match_link(&transaction), None);
+    }
+}
@@ -0,0 +1,232 @@
+mod csv;
+mod json;
+mod ledger;
+mod ofx;
+mod state;
+
+use crate::config::{Config, LedgerConfig};
+use crate::error::Result;
+use crate::models::Transaction;
+use crate::sync::archive::parse_archive_tab_name;
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use std::io::Write;
+
+pub use state::ExportState;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma-separated values, with the same columns as the spreadsheet
+    Csv,
+    /// Newline-delimited JSON, one transaction per line
+    Json,
+    /// Open Financial Exchange, one statement per card
+    Ofx,
+    /// Beancount transactions, with the card and its counter account as postings
+    Beancount,
+    /// Ledger and hledger transactions, with the card and its counter account as postings
+    Ledger,
+}
+
+impl ExportFormat {
+    /// Name of the format, as given on the command line.
+    pub fn name(&self) -> String {
+        self.to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// A transaction along with the card (sheet tab) that it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportRecord {
+    pub card: String,
+    /// Ledger account of the card
+    pub account: String,
+    pub transaction: Transaction,
+}
+
+impl ExportRecord {
+    /// Records for the transactions read from a sheet tab. Transactions in an archive tab
+    /// belong to the card that they were archived from, so that archiving them doesn't make
+    /// them look like new transactions on another card.
+    pub fn from_tab(config: &Config, title: &str, transactions: Vec<Transaction>) -> Vec<Self> {
+        let card = parse_archive_tab_name(title).map_or(title, |(tab_name, _)| tab_name);
+        let account = config.ledger_account(card);
+        transactions
+            .into_iter()
+            .map(|transaction| Self {
+                card: card.to_string(),
+                account: account.clone(),
+                transaction,
+            })
+            .collect()
+    }
+}
+
+/// Criteria for which transactions to export.
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    /// Earliest date to include
+    pub from: Option<NaiveDate>,
+    /// Latest date to include
+    pub to: Option<NaiveDate>,
+    /// Only include transactions that haven't been matched
+    pub unmatched_only: bool,
+}
+
+impl ExportFilter {
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        let date = transaction.timestamp.date_naive();
+
+        self.from.is_none_or(|from| date >= from)
+            && self.to.is_none_or(|to| date <= to)
+            && !(self.unmatched_only && transaction.matched_id.is_some())
+    }
+}
+
+/// Write records in the given format.
+pub fn write_records(
+    format: ExportFormat,
+    records: &[ExportRecord],
+    ledger_config: &LedgerConfig,
+    writer: &mut impl Write,
+) -> Result<()> {
+    match format {
+        ExportFormat::Csv => csv::write(records, writer),
+        ExportFormat::Json => json::write(records, writer),
+        ExportFormat::Ofx => ofx::write(records, writer),
+        ExportFormat::Beancount => ledger::write_beancount(records, ledger_config, writer),
+        ExportFormat::Ledger => ledger::write_ledger(records, ledger_config, writer),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    pub(crate) fn mock_records() -> Vec<ExportRecord> {
+        let debit = Transaction {
+            matched_id: Some("tx_2".to_string()),
+            comments: Some("Dinner & drinks".to_string()),
+            ..mock_transaction(
+                "tx_1",
+                dec!(-12.34),
+                TransactionType::Debit,
+                mock_datetime(2024, 11, 23),
+            )
+        };
+        let credit = Transaction {
+            matched_id: Some("tx_1".to_string()),
+            ..mock_transaction(
+                "tx_2",
+                dec!(12.34),
+                TransactionType::Credit,
+                mock_datetime(2024, 11, 24),
+            )
+        };
+
+        vec![
+            ExportRecord {
+                card: "Amex".to_string(),
+                account: "Liabilities:Amex".to_string(),
+                transaction: debit,
+            },
+            ExportRecord {
+                card: "Amex".to_string(),
+                account: "Liabilities:Amex".to_string(),
+                transaction: credit,
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    #[test]
+    fn test_filter_by_date() {
+        let transaction = mock_transaction(
+            "tx_1",
+            dec!(-12.34),
+            TransactionType::Debit,
+            mock_datetime(2024, 11, 23),
+        );
+        let date = |day| NaiveDate::from_ymd_opt(2024, 11, day);
+
+        assert!(ExportFilter::default().matches(&transaction));
+        assert!(
+            ExportFilter {
+                from: date(23),
+                to: date(23),
+                ..Default::default()
+            }
+            .matches(&transaction),
+            "date range should be inclusive"
+        );
+        assert!(
+            !ExportFilter {
+                from: date(24),
+                ..Default::default()
+            }
+            .matches(&transaction)
+        );
+        assert!(
+            !ExportFilter {
+                to: date(22),
+                ..Default::default()
+            }
+            .matches(&transaction)
+        );
+    }
+
+    #[test]
+    fn test_filter_unmatched_only() {
+        let unmatched = mock_transaction(
+            "tx_1",
+            dec!(-12.34),
+            TransactionType::Debit,
+            mock_datetime(2024, 11, 23),
+        );
+        let matched = Transaction {
+            matched_id: Some("tx_2".to_string()),
+            ..unmatched.clone()
+        };
+        let filter = ExportFilter {
+            unmatched_only: true,
+            ..Default::default()
+        };
+
+        assert!(filter.matches(&unmatched));
+        assert!(!filter.matches(&matched));
+    }
+
+    #[test]
+    fn test_archived_transactions_are_not_exported_again() {
+        let config = Config::default();
+        let transactions: Vec<Transaction> = test_helpers::mock_records()
+            .into_iter()
+            .map(|record| record.transaction)
+            .collect();
+
+        let mut state = ExportState::default();
+        let exported = ExportRecord::from_tab(&config, "Amex", transactions.clone());
+        state.mark_exported(&exported);
+
+        // The transactions are then moved to the card's archive tab
+        let mut archived = ExportRecord::from_tab(&config, "Amex (2024)", transactions);
+        assert_eq!(
+            archived, exported,
+            "archived records should belong to the card"
+        );
+        archived.retain(|record| !state.is_exported(record));
+        assert_eq!(archived, vec![]);
+    }
+}
@@ -0,0 +1,194 @@
+use super::ExportRecord;
+use crate::error::Result;
+use crate::models::transaction::TransactionType;
+use chrono::{DateTime, Utc};
+use std::io::Write;
+
+// Currency for statements without any transactions.
+const DEFAULT_CURRENCY: &str = "GBP";
+
+// OFX limits the length of the payee name.
+const MAX_NAME_LENGTH: usize = 32;
+
+/// Write records as an OFX 2.2 document, with a credit card statement for each card.
+///
+/// Balances aren't known, so statements only contain the transaction list. The matched ID and
+/// comments are included in the memo of each transaction.
+pub(super) fn write(records: &[ExportRecord], writer: &mut impl Write) -> Result<()> {
+    let now = format_datetime(&Utc::now());
+
+    writeln!(
+        writer,
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>"#
+    )?;
+    writeln!(
+        writer,
+        r#"<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE" OLDFILEUID="NONE" NEWFILEUID="NONE"?>"#
+    )?;
+    writeln!(writer, "<OFX>")?;
+    writeln!(writer, "<SIGNONMSGSRSV1><SONRS>")?;
+    writeln!(
+        writer,
+        "<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>"
+    )?;
+    writeln!(
+        writer,
+        "<DTSERVER>{}</DTSERVER><LANGUAGE>ENG</LANGUAGE>",
+        now
+    )?;
+    writeln!(writer, "</SONRS></SIGNONMSGSRSV1>")?;
+    writeln!(writer, "<CREDITCARDMSGSRSV1>")?;
+
+    let mut cards: Vec<&str> = Vec::new();
+    for record in records {
+        if !cards.contains(&record.card.as_str()) {
+            cards.push(&record.card);
+        }
+    }
+
+    for (idx, card) in cards.iter().enumerate() {
+        let card_records: Vec<&ExportRecord> = records.iter().filter(|r| r.card == *card).collect();
+        write_statement(idx, card, &card_records, writer)?;
+    }
+
+    writeln!(writer, "</CREDITCARDMSGSRSV1>")?;
+    writeln!(writer, "</OFX>")?;
+
+    Ok(())
+}
+
+fn write_statement(
+    idx: usize,
+    card: &str,
+    records: &[&ExportRecord],
+    writer: &mut impl Write,
+) -> Result<()> {
+    let currency = records
+        .first()
+        .map(|r| r.transaction.currency.as_str())
+        .unwrap_or(DEFAULT_CURRENCY);
+    let start = records.iter().map(|r| r.transaction.timestamp).min();
+    let end = records.iter().map(|r| r.transaction.timestamp).max();
+
+    writeln!(writer, "<CCSTMTTRNRS>")?;
+    writeln!(writer, "<TRNUID>{}</TRNUID>", idx)?;
+    writeln!(
+        writer,
+        "<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>"
+    )?;
+    writeln!(writer, "<CCSTMTRS>")?;
+    writeln!(writer, "<CURDEF>{}</CURDEF>", escape(currency))?;
+    writeln!(
+        writer,
+        "<CCACCTFROM><ACCTID>{}</ACCTID></CCACCTFROM>",
+        escape(card)
+    )?;
+    writeln!(writer, "<BANKTRANLIST>")?;
+    if let (Some(start), Some(end)) = (start, end) {
+        writeln!(
+            writer,
+            "<DTSTART>{}</DTSTART><DTEND>{}</DTEND>",
+            format_datetime(&start),
+            format_datetime(&end)
+        )?;
+    }
+
+    for record in records {
+        let transaction = &record.transaction;
+        let transaction_type = match transaction.type_ {
+            TransactionType::Debit => "DEBIT",
+            TransactionType::Credit => "CREDIT",
+        };
+        let name: String = transaction
+            .description
+            .chars()
+            .take(MAX_NAME_LENGTH)
+            .collect();
+
+        let memo: Vec<String> = [
+            transaction
+                .matched_id
+                .as_ref()
+                .map(|id| format!("Matched ID: {}", id)),
+            transaction.comments.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        writeln!(writer, "<STMTTRN>")?;
+        writeln!(writer, "<TRNTYPE>{}</TRNTYPE>", transaction_type)?;
+        writeln!(
+            writer,
+            "<DTPOSTED>{}</DTPOSTED>",
+            format_datetime(&transaction.timestamp)
+        )?;
+        writeln!(writer, "<TRNAMT>{}</TRNAMT>", transaction.amount)?;
+        writeln!(writer, "<FITID>{}</FITID>", escape(&transaction.id))?;
+        writeln!(writer, "<NAME>{}</NAME>", escape(&name))?;
+        if !memo.is_empty() {
+            writeln!(writer, "<MEMO>{}</MEMO>", escape(&memo.join("; ")))?;
+        }
+        writeln!(writer, "</STMTTRN>")?;
+    }
+
+    writeln!(writer, "</BANKTRANLIST>")?;
+    writeln!(writer, "</CCSTMTRS>")?;
+    writeln!(writer, "</CCSTMTTRNRS>")?;
+
+    Ok(())
+}
+
+fn format_datetime(datetime: &DateTime<Utc>) -> String {
+    datetime.format("%Y%m%d%H%M%S[0:GMT]").to_string()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::test_helpers::mock_records;
+
+    #[test]
+    fn test_write_ofx() {
+        let mut output = Vec::new();
+        write(&mock_records(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("<?xml"));
+        assert_eq!(
+            output.matches("<CCSTMTRS>").count(),
+            1,
+            "should have one statement per card"
+        );
+        assert!(output.contains("<CCACCTFROM><ACCTID>Amex</ACCTID></CCACCTFROM>"));
+        assert!(output.contains(
+            "<DTSTART>20241123100000[0:GMT]</DTSTART><DTEND>20241124100000[0:GMT]</DTEND>"
+        ));
+        assert_eq!(output.matches("<STMTTRN>").count(), 2);
+        assert!(output.contains(
+            "<STMTTRN>\n\
+             <TRNTYPE>DEBIT</TRNTYPE>\n\
+             <DTPOSTED>20241123100000[0:GMT]</DTPOSTED>\n\
+             <TRNAMT>-12.34</TRNAMT>\n\
+             <FITID>tx_1</FITID>\n\
+             <NAME>mock transaction: tx_1</NAME>\n\
+             <MEMO>Matched ID: tx_2; Dinner &amp; drinks</MEMO>\n\
+             </STMTTRN>"
+        ));
+        assert!(output.trim_end().ends_with("</OFX>"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("A&B <C>"), "A&amp;B &lt;C&gt;");
+    }
+}
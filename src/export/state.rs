@@ -0,0 +1,89 @@
+use super::ExportRecord;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// The transactions that have already been exported, so that an incremental export only
+/// writes new ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExportState {
+    /// Transaction IDs, keyed by card
+    exported: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ExportState {
+    /// Load the state from a file, which is empty if the file doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_exported(&self, record: &ExportRecord) -> bool {
+        self.exported
+            .get(&record.card)
+            .is_some_and(|ids| ids.contains(&record.transaction.id))
+    }
+
+    pub fn mark_exported(&mut self, records: &[ExportRecord]) {
+        for record in records {
+            self.exported
+                .entry(record.card.clone())
+                .or_default()
+                .insert(record.transaction.id.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::test_helpers::mock_records;
+
+    #[test]
+    fn test_mark_exported() {
+        let records = mock_records();
+        let mut state = ExportState::default();
+        assert!(!state.is_exported(&records[0]));
+
+        state.mark_exported(&records[..1]);
+        assert!(state.is_exported(&records[0]));
+        assert!(!state.is_exported(&records[1]));
+
+        let other_card = ExportRecord {
+            card: "Visa".to_string(),
+            ..records[0].clone()
+        };
+        assert!(
+            !state.is_exported(&other_card),
+            "IDs should only be compared within a card"
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!(
+            "credit-card-tracker-export-state-{}.json",
+            std::process::id()
+        ));
+        assert_eq!(ExportState::load(&path).unwrap(), ExportState::default());
+
+        let mut state = ExportState::default();
+        state.mark_exported(&mock_records());
+        state.save(&path).unwrap();
+
+        let loaded = ExportState::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, state);
+    }
+}
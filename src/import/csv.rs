@@ -0,0 +1,129 @@
+use super::{has_credit_debit_suffix, new_transaction, parse_amount, parse_date};
+use crate::config::CsvLayout;
+use crate::error::{AppError, Result};
+use crate::models::Transaction;
+
+/// Parse a CSV statement, finding columns by the headers in the layout.
+pub(super) fn parse(
+    contents: &str,
+    layout: &CsvLayout,
+    currency: &str,
+) -> Result<Vec<Transaction>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| AppError::Import(format!("Failed to read CSV headers: {}", e)))?
+        .clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .ok_or_else(|| AppError::Import(format!("CSV has no {:?} column", name)))
+    };
+
+    let date_idx = column(&layout.date)?;
+    let description_idx = column(&layout.description)?;
+    let amount_idx = column(&layout.amount)?;
+    let id_idx = layout.id.as_deref().map(column).transpose()?;
+    let currency_idx = layout.currency.as_deref().map(column).transpose()?;
+
+    let mut transactions = Vec::new();
+    for (idx, record) in reader.records().enumerate() {
+        let record = record
+            .map_err(|e| AppError::Import(format!("Failed to read CSV row {}: {}", idx + 2, e)))?;
+        let field = |idx: usize| record.get(idx).unwrap_or_default();
+
+        // Skip blank lines and footers, such as a closing balance
+        if field(date_idx).is_empty() {
+            continue;
+        }
+
+        let mut amount = parse_amount(field(amount_idx))?;
+        if layout.charges_positive && !has_credit_debit_suffix(field(amount_idx)) {
+            amount = -amount;
+        }
+
+        transactions.push(new_transaction(
+            parse_date(field(date_idx), &layout.date_format)?,
+            field(description_idx),
+            amount,
+            currency_idx
+                .map(field)
+                .filter(|c| !c.is_empty())
+                .unwrap_or(currency),
+            id_idx.map(field),
+        ));
+    }
+
+    Ok(transactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use rust_decimal::prelude::dec;
+
+    #[test]
+    fn test_parse_amex() {
+        let contents = "\
+Date,Description,Amount,Extended Details,Reference
+23/11/2024,TESCO STORES,12.34,,'AT243280032000010063714'
+24/11/2024,PAYMENT RECEIVED - THANK YOU,-50.00,,'AT243290045000010012345'
+";
+        let layout = CsvLayout::builtin("amex").unwrap();
+        let transactions = parse(contents, &layout, "GBP").unwrap();
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].description, "TESCO STORES");
+        assert_eq!(
+            transactions[0].amount,
+            dec!(-12.34),
+            "charges should be negated"
+        );
+        assert_eq!(transactions[0].type_, TransactionType::Debit);
+        assert_eq!(transactions[0].id, "AT243280032000010063714");
+        assert_eq!(
+            transactions[0].timestamp.to_rfc3339(),
+            "2024-11-23T00:00:00+00:00"
+        );
+        assert_eq!(transactions[1].amount, dec!(50.00));
+        assert_eq!(transactions[1].type_, TransactionType::Credit);
+    }
+
+    #[test]
+    fn test_parse_custom_layout() {
+        let contents = "\
+Posted,Merchant,Value,Ccy
+2024-11-23,Cafe,-3.50,EUR
+
+2024-11-24,Shop,-1.00,
+";
+        let layout = CsvLayout {
+            date: "posted".to_string(),
+            date_format: "%Y-%m-%d".to_string(),
+            description: "Merchant".to_string(),
+            amount: "Value".to_string(),
+            charges_positive: false,
+            id: None,
+            currency: Some("Ccy".to_string()),
+        };
+        let transactions = parse(contents, &layout, "GBP").unwrap();
+
+        assert_eq!(transactions.len(), 2, "blank lines should be skipped");
+        assert_eq!(transactions[0].currency, "EUR");
+        assert_eq!(transactions[1].currency, "GBP");
+        assert!(transactions[0].id.is_empty(), "IDs are assigned later");
+    }
+
+    #[test]
+    fn test_parse_missing_column() {
+        let layout = CsvLayout::builtin("amex").unwrap();
+        let err = parse("Date,Amount\n", &layout, "GBP").unwrap_err();
+        assert!(err.to_string().contains("\"Description\""), "{}", err);
+    }
+}
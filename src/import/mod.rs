@@ -0,0 +1,297 @@
+mod csv;
+mod ofx;
+mod qif;
+
+use crate::config::CsvLayout;
+use crate::error::{AppError, Result};
+use crate::models::Transaction;
+use crate::models::transaction::TransactionType;
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use regex::Regex;
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Prefix of the IDs generated for transactions that don't have one in the file.
+const GENERATED_ID_PREFIX: &str = "import_";
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// Comma-separated values, with columns described by a CSV layout
+    Csv,
+    /// Open Financial Exchange (OFX or QFX), either SGML or XML
+    Ofx,
+    /// Quicken Interchange Format
+    Qif,
+}
+
+impl ImportFormat {
+    /// Guess the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" => Some(Self::Csv),
+            "ofx" | "qfx" => Some(Self::Ofx),
+            "qif" => Some(Self::Qif),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for values that statement files don't always include.
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Column mapping, required for CSV files
+    pub layout: Option<CsvLayout>,
+    /// Currency of the amounts, unless the file says otherwise
+    pub currency: String,
+    /// Format of dates in QIF files, in `strftime` syntax
+    pub date_format: String,
+}
+
+/// Parse the contents of a statement file into transactions, in the order they appear.
+pub fn parse_transactions(
+    format: ImportFormat,
+    contents: &str,
+    options: &ImportOptions,
+) -> Result<Vec<Transaction>> {
+    let mut transactions = match format {
+        ImportFormat::Csv => {
+            let layout = options.layout.as_ref().ok_or_else(|| {
+                AppError::Import("A CSV layout is needed to import CSV files".to_string())
+            })?;
+            csv::parse(contents, layout, &options.currency)?
+        }
+        ImportFormat::Ofx => ofx::parse(contents, &options.currency)?,
+        ImportFormat::Qif => qif::parse(contents, &options.date_format, &options.currency)?,
+    };
+
+    assign_ids(&mut transactions);
+
+    Ok(transactions)
+}
+
+/// Build a transaction from the values in a statement, with the type taken from the sign.
+fn new_transaction(
+    timestamp: DateTime<Utc>,
+    description: &str,
+    amount: Decimal,
+    currency: &str,
+    id: Option<&str>,
+) -> Transaction {
+    let type_ = match amount.is_sign_negative() {
+        true => TransactionType::Debit,
+        false => TransactionType::Credit,
+    };
+
+    Transaction {
+        timestamp,
+        description: description.trim().to_string(),
+        merchant: None,
+        amount,
+        currency: currency.to_string(),
+        converted_amount: None,
+        type_,
+        // Some issuers quote references so that spreadsheets don't treat them as numbers
+        id: id
+            .map(|id| id.trim().trim_matches('\''))
+            .unwrap_or_default()
+            .to_string(),
+        matched_id: None,
+        match_confidence: None,
+        age_days: None,
+        comments: None,
+        category: None,
+        tags: None,
+        rule: None,
+        statement: None,
+        statement_total: None,
+    }
+}
+
+/// An amount in a statement, with an optional sign, thousands separators and decimal point, in
+/// parentheses or followed by CR or DR.
+static AMOUNT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?<open>\()?(?<sign>[-+])?(?<number>\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+(?:\.\d+)?|\.\d+)(?<close>\))?(?<suffix>CR|DR)?$",
+    )
+    .expect("amount pattern should be valid")
+});
+
+/// Parse an amount, ignoring thousands separators, currency symbols and whitespace.
+///
+/// Amounts in parentheses or followed by DR are negative, as a debit, and those followed by CR
+/// are positive. Amounts in other formats, such as with a decimal comma, are an error rather
+/// than being misread.
+fn parse_amount(value: &str) -> Result<Decimal> {
+    let invalid =
+        |reason: &str| AppError::Import(format!("Invalid amount {:?}: {}", value, reason));
+
+    let cleaned: String = value
+        .chars()
+        .filter(|c| c.is_ascii() && !c.is_ascii_whitespace() && *c != '$')
+        .collect::<String>()
+        .to_ascii_uppercase();
+    let captures = AMOUNT
+        .captures(&cleaned)
+        .ok_or_else(|| invalid("unrecognised format"))?;
+
+    let parenthesised = match (captures.name("open"), captures.name("close")) {
+        (Some(_), Some(_)) => true,
+        (None, None) => false,
+        _ => return Err(invalid("unbalanced parentheses")),
+    };
+    let sign = captures.name("sign").map(|m| m.as_str());
+    let suffix = captures.name("suffix").map(|m| m.as_str());
+    if [parenthesised, sign.is_some(), suffix.is_some()]
+        .iter()
+        .filter(|&&marked| marked)
+        .count()
+        > 1
+    {
+        return Err(invalid("more than one sign"));
+    }
+
+    let amount: Decimal = captures["number"]
+        .replace(',', "")
+        .parse()
+        .map_err(|e: rust_decimal::Error| invalid(&e.to_string()))?;
+    let negative = parenthesised || sign == Some("-") || suffix == Some("DR");
+
+    Ok(if negative { -amount } else { amount })
+}
+
+/// Whether an amount is marked as a credit or debit, so its sign doesn't depend on the layout.
+fn has_credit_debit_suffix(value: &str) -> bool {
+    let value = value.trim_end().to_ascii_uppercase();
+    value.ends_with("CR") || value.ends_with("DR")
+}
+
+/// Parse a date, as midnight UTC because statements don't include the time.
+fn parse_date(value: &str, format: &str) -> Result<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(value.trim(), format)
+        .map_err(|e| AppError::Import(format!("Invalid date {:?}: {}", value, e)))?;
+
+    Ok(date.and_time(chrono::NaiveTime::MIN).and_utc())
+}
+
+/// Give each transaction without an ID one derived from its contents, so that importing the
+/// same file again updates the same rows.
+///
+/// Identical transactions, such as two coffees on the same day, are told apart by how many
+/// times they have appeared before in the file.
+fn assign_ids(transactions: &mut [Transaction]) {
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+
+    for transaction in transactions.iter_mut().filter(|t| t.id.is_empty()) {
+        let key = format!(
+            "{}|{}|{}|{}",
+            transaction.timestamp.to_rfc3339(),
+            transaction.amount.normalize(),
+            transaction.currency,
+            transaction.description
+        );
+        let occurrence = occurrences.entry(key.clone()).or_default();
+
+        let digest = Sha256::digest(format!("{}|{}", key, occurrence));
+        *occurrence += 1;
+
+        transaction.id = format!("{}{:x}", GENERATED_ID_PREFIX, digest)
+            [..GENERATED_ID_PREFIX.len() + 16]
+            .to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::test_helpers::mock_datetime;
+    use rust_decimal::prelude::dec;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            ImportFormat::from_path(Path::new("statement.CSV")),
+            Some(ImportFormat::Csv)
+        );
+        assert_eq!(
+            ImportFormat::from_path(Path::new("statement.qfx")),
+            Some(ImportFormat::Ofx)
+        );
+        assert_eq!(
+            ImportFormat::from_path(Path::new("statement.qif")),
+            Some(ImportFormat::Qif)
+        );
+        assert_eq!(ImportFormat::from_path(Path::new("statement")), None);
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("£1,234.56").unwrap(), dec!(1234.56));
+        assert_eq!(parse_amount(" -12.30 ").unwrap(), dec!(-12.30));
+        assert!(parse_amount("n/a").is_err());
+        assert_eq!(parse_amount("(1,234.56)").unwrap(), dec!(-1234.56));
+        assert_eq!(parse_amount("£12.30 DR").unwrap(), dec!(-12.30));
+        assert_eq!(parse_amount("12.30cr").unwrap(), dec!(12.30));
+        assert_eq!(parse_amount(".50").unwrap(), dec!(0.50));
+        assert!(
+            parse_amount("1.234,56").is_err(),
+            "decimal commas should not be misread"
+        );
+        assert!(parse_amount("1,23").is_err());
+        assert!(parse_amount("(12.30").is_err());
+        assert!(parse_amount("-12.30 CR").is_err());
+    }
+
+    #[test]
+    fn test_assign_ids_is_stable() {
+        let coffee = new_transaction(
+            mock_datetime(2024, 11, 23),
+            "Coffee",
+            dec!(-3.50),
+            "GBP",
+            None,
+        );
+        let mut transactions = vec![coffee.clone(), coffee.clone()];
+        assign_ids(&mut transactions);
+
+        assert!(transactions[0].id.starts_with(GENERATED_ID_PREFIX));
+        assert_ne!(
+            transactions[0].id, transactions[1].id,
+            "identical transactions should get different IDs"
+        );
+
+        let mut again = vec![coffee.clone(), coffee];
+        assign_ids(&mut again);
+        assert_eq!(
+            transactions, again,
+            "IDs should be the same on every import"
+        );
+    }
+
+    #[test]
+    fn test_assign_ids_keeps_existing() {
+        let mut transactions = vec![new_transaction(
+            mock_datetime(2024, 11, 23),
+            "Coffee",
+            dec!(-3.50),
+            "GBP",
+            Some("ref_1"),
+        )];
+        assign_ids(&mut transactions);
+        assert_eq!(transactions[0].id, "ref_1");
+    }
+
+    #[test]
+    fn test_csv_requires_layout() {
+        let options = ImportOptions {
+            layout: None,
+            currency: "GBP".to_string(),
+            date_format: "%d/%m/%Y".to_string(),
+        };
+        assert!(parse_transactions(ImportFormat::Csv, "", &options).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use super::{new_transaction, parse_amount};
+use crate::error::{AppError, Result};
+use crate::models::Transaction;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+/// Parse an OFX statement.
+///
+/// Both the SGML format of OFX 1.x, where elements aren't closed, and the XML format of OFX 2.x
+/// are supported by only reading the text that follows each opening tag.
+pub(super) fn parse(contents: &str, currency: &str) -> Result<Vec<Transaction>> {
+    let currency = element(contents, "CURDEF").unwrap_or_else(|| currency.to_string());
+
+    let mut transactions = Vec::new();
+    let mut rest = contents;
+    while let Some(start) = rest.find("<STMTTRN>") {
+        rest = &rest[start + "<STMTTRN>".len()..];
+        let end = rest.find("</STMTTRN>").unwrap_or(rest.len());
+        let block = &rest[..end];
+        rest = &rest[end..];
+
+        let required = |tag: &str| {
+            element(block, tag)
+                .ok_or_else(|| AppError::Import(format!("OFX transaction has no {}", tag)))
+        };
+
+        let description = element(block, "NAME")
+            .or_else(|| element(block, "MEMO"))
+            .unwrap_or_default();
+
+        transactions.push(new_transaction(
+            parse_datetime(&required("DTPOSTED")?)?,
+            &description,
+            parse_amount(&required("TRNAMT")?)?,
+            &currency,
+            element(block, "FITID").as_deref(),
+        ));
+    }
+
+    Ok(transactions)
+}
+
+/// Get the unescaped text that follows the first `<tag>` in the contents.
+fn element(contents: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = contents.find(&open)? + open.len();
+    let text = &contents[start..];
+    let end = text.find('<').unwrap_or(text.len());
+
+    let value = unescape(text[..end].trim());
+    (!value.is_empty()).then_some(value)
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Parse an OFX date, `YYYYMMDD[HHMMSS[.XXX]][[offset[:TZ]]]`, where the offset is in hours.
+fn parse_datetime(value: &str) -> Result<DateTime<Utc>> {
+    let invalid = || AppError::Import(format!("Invalid OFX date {:?}", value));
+
+    let (datetime, offset) = match value.split_once('[') {
+        Some((datetime, zone)) => (datetime, Some(zone.trim_end_matches(']'))),
+        None => (value, None),
+    };
+    let datetime = datetime.split('.').next().unwrap_or_default();
+
+    let date = NaiveDate::parse_from_str(datetime.get(..8).ok_or_else(invalid)?, "%Y%m%d")
+        .map_err(|_| invalid())?;
+    let time = match datetime.get(8..) {
+        Some(time) if !time.is_empty() => {
+            NaiveTime::parse_from_str(time, "%H%M%S").map_err(|_| invalid())?
+        }
+        _ => NaiveTime::MIN,
+    };
+
+    let offset_minutes = match offset {
+        Some(offset) => {
+            let hours: f64 = offset
+                .split(':')
+                .next()
+                .unwrap_or_default()
+                .parse()
+                .map_err(|_| invalid())?;
+            (hours * 60.0).round() as i64
+        }
+        None => 0,
+    };
+
+    Ok((NaiveDateTime::new(date, time) - Duration::minutes(offset_minutes)).and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use rust_decimal::prelude::dec;
+
+    #[test]
+    fn test_parse_sgml() {
+        let contents = "\
+OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+
+<OFX>
+<CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+<CURDEF>EUR
+<BANKTRANLIST>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20241123
+<TRNAMT>-12.34
+<FITID>2024112301
+<NAME>M&amp;S SIMPLY FOOD
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20241124120000[-5:EST]
+<TRNAMT>50.00
+<FITID>2024112401
+<MEMO>Payment
+</STMTTRN>
+</BANKTRANLIST>
+</CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1>
+</OFX>
+";
+        let transactions = parse(contents, "GBP").unwrap();
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].description, "M&S SIMPLY FOOD");
+        assert_eq!(transactions[0].amount, dec!(-12.34));
+        assert_eq!(transactions[0].type_, TransactionType::Debit);
+        assert_eq!(transactions[0].currency, "EUR");
+        assert_eq!(transactions[0].id, "2024112301");
+        assert_eq!(transactions[1].description, "Payment");
+        assert_eq!(
+            transactions[1].timestamp.to_rfc3339(),
+            "2024-11-24T17:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_parse_xml() {
+        let contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+<OFX><CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS><BANKTRANLIST>
+<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20241123100000.000[0:GMT]</DTPOSTED><TRNAMT>-3.50</TRNAMT><NAME>Cafe</NAME></STMTTRN>
+</BANKTRANLIST></CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1></OFX>
+"#;
+        let transactions = parse(contents, "GBP").unwrap();
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].currency, "GBP");
+        assert_eq!(
+            transactions[0].timestamp.to_rfc3339(),
+            "2024-11-23T10:00:00+00:00"
+        );
+        assert!(transactions[0].id.is_empty());
+    }
+
+    #[test]
+    fn test_parse_missing_amount() {
+        let contents = "<STMTTRN><DTPOSTED>20241123</STMTTRN>";
+        assert!(parse(contents, "GBP").is_err());
+    }
+
+    #[test]
+    fn test_parse_datetime() {
+        assert!(parse_datetime("2024112").is_err());
+        assert!(parse_datetime("20241123[x]").is_err());
+        assert_eq!(
+            parse_datetime("20241123000000[+5.5:IST]")
+                .unwrap()
+                .to_rfc3339(),
+            "2024-11-22T18:30:00+00:00"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use super::{new_transaction, parse_amount, parse_date};
+use crate::error::{AppError, Result};
+use crate::models::Transaction;
+
+/// Parse a QIF statement, where each line starts with a field code and `^` ends a record.
+///
+/// QIF doesn't say how dates are written, so they are parsed with `date_format`.
+pub(super) fn parse(contents: &str, date_format: &str, currency: &str) -> Result<Vec<Transaction>> {
+    let mut transactions = Vec::new();
+    let mut record = Record::default();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let mut chars = line.chars();
+        let Some(code) = chars.next() else {
+            continue;
+        };
+        let value = chars.as_str().trim();
+
+        match code {
+            '!' => continue,
+            'D' => record.date = Some(value.to_string()),
+            'T' | 'U' => record.amount = Some(value.to_string()),
+            'P' => record.payee = Some(value.to_string()),
+            'M' => record.memo = Some(value.to_string()),
+            'N' => record.number = Some(value.to_string()),
+            '^' => {
+                let record = std::mem::take(&mut record);
+                transactions.push(record.into_transaction(idx + 1, date_format, currency)?);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(transactions)
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    date: Option<String>,
+    amount: Option<String>,
+    payee: Option<String>,
+    memo: Option<String>,
+    number: Option<String>,
+}
+
+impl Record {
+    fn into_transaction(
+        self,
+        line: usize,
+        date_format: &str,
+        currency: &str,
+    ) -> Result<Transaction> {
+        let missing = |field: &str| {
+            AppError::Import(format!(
+                "QIF record ending on line {} has no {}",
+                line, field
+            ))
+        };
+
+        // Quicken writes years after 1999 as two digits following a `'`
+        let date = self.date.ok_or_else(|| missing("date"))?;
+        let timestamp = match date.split_once('\'') {
+            Some((day_month, year)) => parse_date(
+                &format!("{}/{}", day_month, year),
+                &date_format.replace("%Y", "%y"),
+            )?,
+            None => parse_date(&date, date_format)?,
+        };
+        let amount = parse_amount(&self.amount.ok_or_else(|| missing("amount"))?)?;
+        let description = self.payee.or(self.memo).unwrap_or_default();
+
+        Ok(new_transaction(
+            timestamp,
+            &description,
+            amount,
+            currency,
+            self.number.as_deref(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use rust_decimal::prelude::dec;
+
+    #[test]
+    fn test_parse() {
+        let contents = "\
+!Type:CCard
+D23/11/2024
+T-1,234.56
+PAIRLINE TICKETS
+^
+D24/11'24
+U50.00
+MPayment received
+^
+";
+        let transactions = parse(contents, "%d/%m/%Y", "GBP").unwrap();
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].description, "AIRLINE TICKETS");
+        assert_eq!(transactions[0].amount, dec!(-1234.56));
+        assert_eq!(transactions[0].type_, TransactionType::Debit);
+        assert_eq!(transactions[1].description, "Payment received");
+        assert_eq!(
+            transactions[1].timestamp.to_rfc3339(),
+            "2024-11-24T00:00:00+00:00",
+            "two digit years should be accepted"
+        );
+        assert_eq!(transactions[1].type_, TransactionType::Credit);
+    }
+
+    #[test]
+    fn test_parse_us_dates() {
+        let contents = "D11/23/2024\nT-1.00\n^\n";
+        let transactions = parse(contents, "%m/%d/%Y", "USD").unwrap();
+        assert_eq!(
+            transactions[0].timestamp.date_naive().to_string(),
+            "2024-11-23"
+        );
+        assert_eq!(transactions[0].currency, "USD");
+    }
+
+    #[test]
+    fn test_parse_missing_amount() {
+        let err = parse("D23/11/2024\n^\n", "%d/%m/%Y", "GBP").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+    }
+}
@@ -1,17 +1,26 @@
 mod cli;
 mod config;
+mod currency;
 mod error;
+mod export;
+mod import;
+mod metrics;
 mod models;
+mod notify;
+mod report;
+mod rules;
 mod sheets;
 mod sync;
 mod truelayer;
 
 use clap::Parser;
 
-use crate::cli::Cli;
-use tracing::error;
+use crate::cli::{Cli, LogFormat};
+use crate::metrics::MetricsLayer;
+use tracing::{Level, error};
 use tracing_indicatif::IndicatifLayer;
-use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::{EnvFilter, Layer, fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() {
@@ -23,11 +32,37 @@ async fn main() {
         _ => "hyper=info,h2=info,debug",      // -vv: debug for everything, except HTTP libs
     };
 
-    let indicatif_layer = IndicatifLayer::new();
+    let filter = || EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(log_level));
+
+    // JSON lines go straight to stderr, as progress bars would break them up
+    let (text_layer, json_layer, indicatif_layer) = match cli.log_format {
+        LogFormat::Text => {
+            let indicatif_layer = IndicatifLayer::new();
+            let text_layer = fmt::layer()
+                .with_writer(indicatif_layer.get_stderr_writer())
+                .with_filter(filter());
+            (
+                Some(text_layer),
+                None,
+                Some(indicatif_layer.with_filter(filter())),
+            )
+        }
+        LogFormat::Json => {
+            let json_layer = fmt::layer()
+                .json()
+                .with_writer(std::io::stderr)
+                .with_filter(filter());
+            (None, Some(json_layer), None)
+        }
+    };
+    // Card sync durations are recorded from this crate's spans whatever the log level
+    let metrics_layer =
+        MetricsLayer.with_filter(Targets::new().with_target("credit_card_tracker", Level::TRACE));
     tracing_subscriber::registry()
-        .with(fmt::layer().with_writer(indicatif_layer.get_stderr_writer()))
+        .with(text_layer)
+        .with(json_layer)
         .with(indicatif_layer)
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(log_level)))
+        .with(metrics_layer)
         .init();
 
     if let Err(e) = cli.run().await {
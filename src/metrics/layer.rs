@@ -0,0 +1,123 @@
+use super::metrics;
+use std::fmt;
+use std::time::Instant;
+use tracing::Subscriber;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Name of the span around each card in `SyncEngine::sync_card`, which is timed.
+const CARD_SPAN: &str = "Syncing card";
+
+/// Records the time taken by each "Syncing card" span as the sync duration of the card's tab,
+/// the label of the other card metrics.
+pub struct MetricsLayer;
+
+/// Start of a card's span.
+struct CardTiming {
+    tab: String,
+    started: Instant,
+}
+
+#[derive(Default)]
+struct CardVisitor {
+    tab: Option<String>,
+}
+
+impl Visit for CardVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "tab" {
+            self.tab = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "tab" {
+            self.tab = Some(format!("{:?}", value));
+        }
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != CARD_SPAN {
+            return;
+        }
+
+        let mut visitor = CardVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(tab) = visitor.tab
+            && let Some(span) = ctx.span(id)
+        {
+            span.extensions_mut().insert(CardTiming {
+                tab,
+                started: Instant::now(),
+            });
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        if let Some(timing) = span.extensions().get::<CardTiming>() {
+            metrics().set(
+                "card_sync_duration_seconds",
+                vec![("card", timing.tab.clone())],
+                timing.started.elapsed().as_secs_f64(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{api_error, sheet_rows_written};
+    use tracing::{debug, info_span};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_layer_times_card_spans() {
+        let subscriber = tracing_subscriber::registry().with(MetricsLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            info_span!(CARD_SPAN, card = %"Layer Test", tab = %"Layer Test Card").in_scope(|| {
+                api_error("layer_test", Some(503));
+                sheet_rows_written("Layer Test Card", 12);
+            });
+        });
+
+        let output = metrics().render();
+        assert!(
+            output.contains("cct_api_errors_total{provider=\"layer_test\",status=\"503\"} 1\n"),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("cct_sheet_rows_written_total{sheet=\"Layer Test Card\"} 12\n"),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("cct_card_sync_duration_seconds{card=\"Layer Test Card\"} "),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_layer_ignores_unknown_metrics() {
+        let subscriber = tracing_subscriber::registry().with(MetricsLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            debug!(metric = "layer_test_unknown_total", "Unknown metric");
+            metrics().set("layer_test_unknown", Vec::new(), 1.0);
+        });
+
+        assert!(!metrics().render().contains("layer_test_unknown"));
+    }
+}
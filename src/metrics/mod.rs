@@ -0,0 +1,461 @@
+mod layer;
+
+pub use layer::MetricsLayer;
+
+use crate::error::{AppError, Result};
+use crate::sync::report::SyncReport;
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use tiny_http::{Header, Response, Server};
+use tracing::{debug, info, warn};
+
+/// Prefix of every metric name.
+const PREFIX: &str = "cct_";
+
+/// Content type of the Prometheus text format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// Metrics recorded by this process, from tracing events and sync reports.
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Gauge,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+        }
+    }
+}
+
+struct Definition {
+    name: &'static str,
+    kind: Kind,
+    help: &'static str,
+}
+
+const fn metric(name: &'static str, kind: Kind, help: &'static str) -> Definition {
+    Definition { name, kind, help }
+}
+
+/// Every metric, in the order they're written.
+const DEFINITIONS: &[Definition] = &[
+    metric("sync_runs_total", Kind::Counter, "Syncs run, by result"),
+    metric(
+        "sync_success",
+        Kind::Gauge,
+        "Whether the last sync succeeded on every card",
+    ),
+    metric(
+        "sync_last_run_timestamp_seconds",
+        Kind::Gauge,
+        "When the last sync finished",
+    ),
+    metric(
+        "consent_expiry_timestamp_seconds",
+        Kind::Gauge,
+        "When the TrueLayer consent expires",
+    ),
+    metric(
+        "card_sync_duration_seconds",
+        Kind::Gauge,
+        "Time taken to sync the card in the last sync",
+    ),
+    metric(
+        "card_sync_failed",
+        Kind::Gauge,
+        "Whether the card failed in the last sync",
+    ),
+    metric(
+        "card_transactions_fetched",
+        Kind::Gauge,
+        "Transactions fetched for the card in the last sync",
+    ),
+    metric(
+        "card_transactions_added",
+        Kind::Gauge,
+        "Transactions added to the card's sheet in the last sync",
+    ),
+    metric(
+        "card_transactions_updated",
+        Kind::Gauge,
+        "Transactions whose details changed in the last sync",
+    ),
+    metric(
+        "card_matches",
+        Kind::Gauge,
+        "Pairs of transactions matched in the last sync",
+    ),
+    metric(
+        "card_unmatched_debits",
+        Kind::Gauge,
+        "Debits that haven't been matched",
+    ),
+    metric(
+        "card_unmatched_debits_amount",
+        Kind::Gauge,
+        "Total of the debits that haven't been matched, which is negative",
+    ),
+    metric(
+        "sheet_rows_written_total",
+        Kind::Counter,
+        "Transaction rows written to each sheet",
+    ),
+    metric(
+        "api_errors_total",
+        Kind::Counter,
+        "API requests that failed, by provider and status code",
+    ),
+    metric(
+        "token_refreshes_total",
+        Kind::Counter,
+        "Access token refreshes, by provider and outcome",
+    ),
+];
+
+fn definition(name: &str) -> Option<&'static Definition> {
+    DEFINITIONS
+        .iter()
+        .find(|definition| definition.name == name)
+}
+
+/// Count a failed API request, by provider and status code if there was a response.
+pub fn api_error(provider: &str, status: Option<u16>) {
+    let status = status.map_or_else(|| "none".to_string(), |status| status.to_string());
+    metrics().increment(
+        "api_errors_total",
+        vec![("provider", provider.to_string()), ("status", status)],
+        1.0,
+    );
+}
+
+/// Count an access token refresh, by provider and whether it succeeded.
+pub fn token_refresh(provider: &str, success: bool) {
+    let outcome = match success {
+        true => "success",
+        false => "failure",
+    };
+    metrics().increment(
+        "token_refreshes_total",
+        vec![
+            ("provider", provider.to_string()),
+            ("outcome", outcome.to_string()),
+        ],
+        1.0,
+    );
+}
+
+/// Count the transaction rows written to a sheet.
+pub fn sheet_rows_written(sheet: &str, rows: usize) {
+    metrics().increment(
+        "sheet_rows_written_total",
+        vec![("sheet", sheet.to_string())],
+        rows as f64,
+    );
+}
+
+/// Label names and values of a sample.
+type Labels = Vec<(&'static str, String)>;
+
+/// Samples of each metric, keyed by name and then labels.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    samples: Mutex<BTreeMap<&'static str, BTreeMap<Labels, f64>>>,
+}
+
+impl Metrics {
+    /// Add `value` to a counter. Metrics that aren't defined are ignored.
+    pub fn increment(&self, name: &str, labels: Labels, value: f64) {
+        let Some(definition) = definition(name) else {
+            debug!(name = %name, "Ignoring unknown metric");
+            return;
+        };
+        let mut samples = self.samples.lock().unwrap();
+        *samples
+            .entry(definition.name)
+            .or_default()
+            .entry(labels)
+            .or_default() += value;
+    }
+
+    /// Set a gauge. Metrics that aren't defined are ignored.
+    pub fn set(&self, name: &str, labels: Labels, value: f64) {
+        let Some(definition) = definition(name) else {
+            debug!(name = %name, "Ignoring unknown metric");
+            return;
+        };
+        let mut samples = self.samples.lock().unwrap();
+        samples
+            .entry(definition.name)
+            .or_default()
+            .insert(labels, value);
+    }
+
+    /// Record the result of a sync that finished at `now`, replacing the card gauges of the
+    /// previous sync so that removed cards aren't reported.
+    pub fn record_report(&self, report: &SyncReport, now: DateTime<Utc>) {
+        {
+            let mut samples = self.samples.lock().unwrap();
+            for name in [
+                "card_sync_failed",
+                "card_transactions_fetched",
+                "card_transactions_added",
+                "card_transactions_updated",
+                "card_matches",
+                "card_unmatched_debits",
+                "card_unmatched_debits_amount",
+            ] {
+                samples.remove(name);
+            }
+            // Durations are recorded as each card is synced, so only those of removed cards go
+            if let Some(durations) = samples.get_mut("card_sync_duration_seconds") {
+                durations.retain(|labels, _| {
+                    report
+                        .cards
+                        .iter()
+                        .any(|card| labels.iter().any(|(_, value)| *value == card.card))
+                });
+            }
+        }
+
+        let result = match report.is_success() {
+            true => "success",
+            false => "failure",
+        };
+        self.increment("sync_runs_total", vec![("result", result.to_string())], 1.0);
+        self.set(
+            "sync_success",
+            Vec::new(),
+            f64::from(u8::from(report.is_success())),
+        );
+        self.set(
+            "sync_last_run_timestamp_seconds",
+            Vec::new(),
+            now.timestamp() as f64,
+        );
+        if let Some(expires_at) = report.consent_expires_at {
+            self.set(
+                "consent_expiry_timestamp_seconds",
+                Vec::new(),
+                expires_at.timestamp() as f64,
+            );
+        }
+
+        for card in &report.cards {
+            let labels = || vec![("card", card.card.clone())];
+            self.set(
+                "card_sync_failed",
+                labels(),
+                f64::from(u8::from(card.error.is_some())),
+            );
+            if card.error.is_some() {
+                continue;
+            }
+            self.set("card_transactions_fetched", labels(), card.fetched as f64);
+            self.set("card_transactions_added", labels(), card.added as f64);
+            self.set("card_transactions_updated", labels(), card.updated as f64);
+            self.set("card_matches", labels(), card.matched as f64);
+            self.set(
+                "card_unmatched_debits",
+                labels(),
+                card.unmatched_count as f64,
+            );
+            self.set(
+                "card_unmatched_debits_amount",
+                labels(),
+                card.unmatched_total.to_f64().unwrap_or_default(),
+            );
+        }
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let samples = self.samples.lock().unwrap();
+        let mut output = String::new();
+
+        for definition in DEFINITIONS {
+            let Some(family) = samples.get(definition.name) else {
+                continue;
+            };
+            let name = format!("{}{}", PREFIX, definition.name);
+            output.push_str(&format!("# HELP {} {}\n", name, definition.help));
+            output.push_str(&format!("# TYPE {} {}\n", name, definition.kind.name()));
+
+            for (labels, value) in family {
+                let labels: Vec<String> = labels
+                    .iter()
+                    .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+                    .collect();
+                match labels.is_empty() {
+                    true => output.push_str(&format!("{} {}\n", name, value)),
+                    false => {
+                        output.push_str(&format!("{}{{{}}} {}\n", name, labels.join(","), value))
+                    }
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Write the metrics to a file for node_exporter's textfile collector.
+    pub fn write_textfile(&self, path: &Path) -> Result<()> {
+        // Write to a temporary file and rename it, so that a partial file is never collected
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, self.render())?;
+        fs::rename(&temp_path, path)?;
+
+        debug!(path = %path.display(), "Metrics written");
+        Ok(())
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serve `/metrics` on `address` in a background thread.
+pub fn serve(address: &str) -> Result<()> {
+    let server = Server::http(address)
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Failed to bind to {}: {}", address, e)))?;
+    let content_type = Header::from_bytes("Content-Type", CONTENT_TYPE)
+        .map_err(|_| AppError::Other(anyhow::anyhow!("Invalid content type")))?;
+    info!(address = address, "Serving metrics on /metrics");
+
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = match request.url() {
+                "/metrics" => {
+                    Response::from_string(metrics().render()).with_header(content_type.clone())
+                }
+                _ => Response::from_string("Not found").with_status_code(404),
+            };
+            if let Err(e) = request.respond(response) {
+                warn!(error = %e, "Failed to send metrics");
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::report::CardReport;
+    use chrono::TimeZone;
+    use rust_decimal::prelude::dec;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.increment(
+            "api_errors_total",
+            vec![
+                ("provider", "truelayer".to_string()),
+                ("status", "429".to_string()),
+            ],
+            1.0,
+        );
+        metrics.increment(
+            "api_errors_total",
+            vec![
+                ("provider", "truelayer".to_string()),
+                ("status", "429".to_string()),
+            ],
+            1.0,
+        );
+        metrics.set(
+            "card_sync_duration_seconds",
+            vec![("card", "Amex \"Gold\"".to_string())],
+            1.5,
+        );
+        metrics.increment("not_a_metric", Vec::new(), 1.0);
+
+        assert_eq!(
+            metrics.render(),
+            "# HELP cct_card_sync_duration_seconds Time taken to sync the card in the last sync\n\
+             # TYPE cct_card_sync_duration_seconds gauge\n\
+             cct_card_sync_duration_seconds{card=\"Amex \\\"Gold\\\"\"} 1.5\n\
+             # HELP cct_api_errors_total API requests that failed, by provider and status code\n\
+             # TYPE cct_api_errors_total counter\n\
+             cct_api_errors_total{provider=\"truelayer\",status=\"429\"} 2\n"
+        );
+    }
+
+    #[test]
+    fn test_record_report() {
+        let metrics = Metrics::default();
+        let now = Utc.with_ymd_and_hms(2025, 3, 1, 9, 0, 0).unwrap();
+        let report = SyncReport {
+            cards: vec![
+                CardReport {
+                    card: "Amex".to_string(),
+                    fetched: 10,
+                    added: 3,
+                    updated: 1,
+                    matched: 2,
+                    unmatched_count: 2,
+                    unmatched_total: dec!(-45.50),
+                    ..Default::default()
+                },
+                CardReport {
+                    card: "Visa".to_string(),
+                    error: Some("Failed to get card transactions".to_string()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // As recorded by MetricsLayer while each card was synced
+        for card in ["Amex", "Visa"] {
+            metrics.set(
+                "card_sync_duration_seconds",
+                vec![("card", card.to_string())],
+                1.5,
+            );
+        }
+        metrics.record_report(&report, now);
+        let output = metrics.render();
+        assert!(output.contains("cct_sync_runs_total{result=\"failure\"} 1\n"));
+        assert!(output.contains("cct_sync_success 0\n"));
+        assert!(output.contains("cct_card_sync_duration_seconds{card=\"Visa\"} 1.5\n"));
+        assert!(output.contains("cct_sync_last_run_timestamp_seconds 1740819600\n"));
+        assert!(output.contains("cct_card_sync_failed{card=\"Visa\"} 1\n"));
+        assert!(output.contains("cct_card_transactions_fetched{card=\"Amex\"} 10\n"));
+        assert!(output.contains("cct_card_unmatched_debits_amount{card=\"Amex\"} -45.5\n"));
+        assert!(!output.contains("cct_card_transactions_fetched{card=\"Visa\"}"));
+
+        let report = SyncReport {
+            cards: vec![report.cards[0].clone()],
+            ..Default::default()
+        };
+        metrics.record_report(&report, now);
+        let output = metrics.render();
+        assert!(output.contains("cct_sync_runs_total{result=\"failure\"} 1\n"));
+        assert!(output.contains("cct_sync_runs_total{result=\"success\"} 1\n"));
+        assert!(
+            !output.contains("card=\"Visa\""),
+            "cards that weren't in the last sync shouldn't be reported"
+        );
+        assert!(output.contains("cct_card_sync_duration_seconds{card=\"Amex\"} 1.5\n"));
+    }
+}
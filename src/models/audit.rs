@@ -0,0 +1,226 @@
+use super::Transaction;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Headers of the History tab, in the order of `AuditEntry::to_row`.
+pub const HISTORY_HEADERS: &[&str] = &[
+    "Timestamp",
+    "Run ID",
+    "Card",
+    "Transaction ID",
+    "Field",
+    "Old Value",
+    "New Value",
+    "Reason",
+    "Rule",
+];
+
+/// Why a sync or import changed a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeReason {
+    /// New from the provider or an imported file
+    Added,
+    /// Details changed by the provider or an imported file
+    Updated,
+    /// Matched by reconcile
+    AutoMatch,
+    /// Set by a categorisation, comment or pair rule
+    Rule,
+    /// Moved to an archive tab
+    Archived,
+}
+
+impl ChangeReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeReason::Added => "added",
+            ChangeReason::Updated => "updated",
+            ChangeReason::AutoMatch => "auto_match",
+            ChangeReason::Rule => "rule",
+            ChangeReason::Archived => "archived",
+        }
+    }
+}
+
+/// A change to one column of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    pub transaction_id: String,
+    /// Header of the column that changed
+    pub field: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_value: Option<String>,
+    pub reason: ChangeReason,
+    /// Name of the rule that made the change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+}
+
+impl Change {
+    /// A transaction that wasn't in the sheet before.
+    pub fn added(transaction: &Transaction) -> Self {
+        Self {
+            transaction_id: transaction.id.clone(),
+            field: "Transaction".to_string(),
+            old_value: None,
+            new_value: Some(format!(
+                "{} {} {} {}",
+                transaction.timestamp.format("%Y-%m-%d"),
+                transaction.description,
+                transaction.amount,
+                transaction.currency
+            )),
+            reason: ChangeReason::Added,
+            rule: None,
+        }
+    }
+
+    /// Changes to the columns in `fields` between two versions of a transaction.
+    pub fn diff(
+        old: &Transaction,
+        new: &Transaction,
+        fields: &[&str],
+        reason: ChangeReason,
+        rule: Option<&str>,
+    ) -> Vec<Self> {
+        let (Ok(Value::Object(old_values)), Ok(Value::Object(new_values))) =
+            (serde_json::to_value(old), serde_json::to_value(new))
+        else {
+            return Vec::new();
+        };
+
+        fields
+            .iter()
+            .filter_map(|field| {
+                let old_value = old_values.get(*field).and_then(value_string);
+                let new_value = new_values.get(*field).and_then(value_string);
+                (old_value != new_value).then(|| Self {
+                    transaction_id: new.id.clone(),
+                    field: field.to_string(),
+                    old_value,
+                    new_value,
+                    reason,
+                    rule: rule.map(str::to_string),
+                })
+            })
+            .collect()
+    }
+}
+
+fn value_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// A change recorded in the audit log, with the sync or import that made it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub run_id: String,
+    /// Sheet tab of the transaction
+    pub card: String,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+impl AuditEntry {
+    /// The entry as a row of the History tab.
+    pub fn to_row(&self) -> Vec<Value> {
+        let optional = |value: &Option<String>| Value::String(value.clone().unwrap_or_default());
+        vec![
+            Value::String(self.timestamp.to_rfc3339()),
+            Value::String(self.run_id.clone()),
+            Value::String(self.card.clone()),
+            Value::String(self.change.transaction_id.clone()),
+            Value::String(self.change.field.clone()),
+            optional(&self.change.old_value),
+            optional(&self.change.new_value),
+            Value::String(self.change.reason.as_str().to_string()),
+            optional(&self.change.rule),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use chrono::TimeZone;
+    use rust_decimal::prelude::dec;
+
+    #[test]
+    fn test_diff() {
+        let old = mock_transaction(
+            "tx_1",
+            dec!(-10.00),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 1),
+        );
+        let new = Transaction {
+            description: "Updated description".to_string(),
+            amount: dec!(-12.50),
+            category: Some("Groceries".to_string()),
+            ..old.clone()
+        };
+
+        let changes = Change::diff(
+            &old,
+            &new,
+            &["Description", "Amount", "Currency", "Category"],
+            ChangeReason::Updated,
+            None,
+        );
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| (
+                    change.field.as_str(),
+                    change.old_value.as_deref(),
+                    change.new_value.as_deref()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "Description",
+                    Some(old.description.as_str()),
+                    Some("Updated description")
+                ),
+                ("Amount", Some("-10.00"), Some("-12.50")),
+                ("Category", None, Some("Groceries")),
+            ]
+        );
+        assert!(changes.iter().all(|change| change.transaction_id == "tx_1"));
+    }
+
+    #[test]
+    fn test_audit_entry() {
+        let entry = AuditEntry {
+            timestamp: Utc.with_ymd_and_hms(2025, 3, 1, 9, 0, 0).unwrap(),
+            run_id: "20250301T090000Z".to_string(),
+            card: "Amex".to_string(),
+            change: Change {
+                transaction_id: "tx_1".to_string(),
+                field: "Matched ID".to_string(),
+                old_value: None,
+                new_value: Some("tx_2".to_string()),
+                reason: ChangeReason::AutoMatch,
+                rule: None,
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"timestamp":"2025-03-01T09:00:00Z","run_id":"20250301T090000Z","card":"Amex","transaction_id":"tx_1","field":"Matched ID","new_value":"tx_2","reason":"auto_match"}"#
+        );
+        assert_eq!(entry.to_row().len(), HISTORY_HEADERS.len());
+        assert_eq!(entry.to_row()[7], Value::String("auto_match".to_string()));
+    }
+}
@@ -1,4 +1,6 @@
-use crate::truelayer::types::{TrueLayerCard, TrueLayerProvider};
+use crate::truelayer::types::{TrueLayerCard, TrueLayerCardBalance, TrueLayerProvider};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -33,6 +35,28 @@ impl From<TrueLayerProvider> for Provider {
     }
 }
 
+/// A card's balance and its latest statement, if the provider gives them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardBalance {
+    pub current: Decimal,
+    pub currency: String,
+    pub last_statement_date: Option<NaiveDate>,
+    pub last_statement_balance: Option<Decimal>,
+}
+
+impl From<TrueLayerCardBalance> for CardBalance {
+    fn from(tl: TrueLayerCardBalance) -> Self {
+        CardBalance {
+            current: tl.current,
+            currency: tl.currency,
+            last_statement_date: tl
+                .last_statement_date
+                .and_then(|date| NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()),
+            last_statement_balance: tl.last_statement_balance,
+        }
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod test_helpers {
     use super::*;
@@ -61,4 +85,29 @@ mod tests {
 
         assert_eq!(card, deserialized);
     }
+
+    #[test]
+    fn test_card_balance_from_truelayer() {
+        let tl: TrueLayerCardBalance = serde_json::from_str(
+            r#"{
+                "available": 3279.0,
+                "currency": "GBP",
+                "current": 20.0,
+                "credit_limit": 3300.0,
+                "last_statement_balance": 420.0,
+                "last_statement_date": "2017-01-28T00:00:00",
+                "payment_due": 5.0,
+                "payment_due_date": "2017-01-31T00:00:00",
+                "update_timestamp": "2017-02-07T17:33:30.001222Z"
+            }"#,
+        )
+        .unwrap();
+
+        let balance = CardBalance::from(tl);
+        assert_eq!(
+            balance.last_statement_date,
+            NaiveDate::from_ymd_opt(2017, 1, 28)
+        );
+        assert_eq!(balance.current, Decimal::from(20));
+    }
 }
@@ -1,5 +1,9 @@
+pub mod audit;
 pub mod card;
+pub mod sheet;
+pub mod statement;
 pub mod transaction;
 
-pub use card::Card;
-pub use transaction::{FromSheetRows, ToSheetRows, Transaction};
+pub use card::{Card, CardBalance};
+pub use sheet::SheetData;
+pub use transaction::{ToSheetRows, Transaction};
@@ -0,0 +1,326 @@
+use crate::models::transaction::{FromSheetRows, ToSheetRows, Transaction};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The contents of a card's sheet: the managed transactions and any columns added by the user.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SheetData {
+    pub transactions: Vec<Transaction>,
+    pub extra_columns: ExtraColumns,
+}
+
+/// Columns in the sheet that aren't part of the `Transaction` schema.
+///
+/// Values are keyed by transaction ID so that they follow their row when transactions are
+/// updated or re-sorted. Each column is written back at the position it was read from, and
+/// columns without a position are written after the others.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtraColumns {
+    pub headers: Vec<String>,
+    /// Index of each column in the header row it was read from
+    pub positions: Vec<Option<usize>>,
+    pub values: HashMap<String, Vec<Value>>,
+}
+
+impl ExtraColumns {
+    fn position(&self, column: usize) -> Option<usize> {
+        self.positions.get(column).copied().flatten()
+    }
+
+    /// Insert the extra columns' values into a row of managed columns, each at its position.
+    fn insert_into(&self, row: &mut Vec<Value>, values: Vec<Value>) {
+        let mut columns: Vec<(usize, Value)> = values
+            .into_iter()
+            .enumerate()
+            .map(|(column, value)| (self.position(column).unwrap_or(usize::MAX), value))
+            .collect();
+        // Stable, so that columns without a position stay in the order they were added
+        columns.sort_by_key(|(position, _)| *position);
+        for (position, value) in columns {
+            row.insert(position.min(row.len()), value);
+        }
+    }
+
+    /// Get the values for a transaction, padded with nulls if the transaction has none.
+    fn row(&self, id: &str) -> Vec<Value> {
+        let mut row = self.values.get(id).cloned().unwrap_or_default();
+        row.resize(self.headers.len(), Value::Null);
+        row
+    }
+
+    /// Copy a transaction's values from another sheet's columns, matching them by header and
+    /// adding any headers that this sheet doesn't have.
+    pub fn copy_row(&mut self, from: &ExtraColumns, id: &str) {
+        let Some(values) = from.values.get(id) else {
+            return;
+        };
+
+        let mut row = self.row(id);
+        for (header, value) in from.headers.iter().zip(values) {
+            match self.headers.iter().position(|h| h == header) {
+                Some(idx) => row[idx] = value.clone(),
+                None => {
+                    self.positions.resize(self.headers.len(), None);
+                    self.positions.push(
+                        from.headers
+                            .iter()
+                            .position(|h| h == header)
+                            .and_then(|column| from.position(column)),
+                    );
+                    self.headers.push(header.clone());
+                    row.push(value.clone());
+                }
+            }
+        }
+        self.values.insert(id.to_string(), row);
+    }
+}
+
+impl SheetData {
+    /// Convert a vector of rows (first row as headers) to transactions and extra columns.
+    pub fn from_sheet_rows(rows: &[Vec<Value>]) -> crate::error::Result<Self> {
+        let transactions = Transaction::from_sheet_rows(rows)?;
+
+        let Some(header_row) = rows.first() else {
+            return Ok(Self::default());
+        };
+
+        let field_names = Transaction::get_field_names();
+        let headers: Vec<String> = header_row
+            .iter()
+            .map(Transaction::value_to_string)
+            .collect();
+        let id_idx = headers.iter().position(|h| h == "ID");
+        let extra_indexes: Vec<usize> = headers
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| !field_names.contains(h))
+            .map(|(idx, _)| idx)
+            .collect();
+
+        let mut extra_columns = ExtraColumns {
+            headers: extra_indexes
+                .iter()
+                .map(|&idx| headers[idx].clone())
+                .collect(),
+            positions: extra_indexes.iter().map(|&idx| Some(idx)).collect(),
+            values: HashMap::new(),
+        };
+
+        if let Some(id_idx) = id_idx {
+            for row in rows.iter().skip(1) {
+                let Some(id) = row.get(id_idx).map(Transaction::value_to_string) else {
+                    continue;
+                };
+
+                let values: Vec<Value> = extra_indexes
+                    .iter()
+                    .map(|&idx| row.get(idx).cloned().unwrap_or(Value::Null))
+                    .collect();
+
+                if values
+                    .iter()
+                    .any(|v| !Transaction::normalize_sheet_value(v).is_null())
+                {
+                    extra_columns.values.insert(id, values);
+                }
+            }
+        }
+
+        Ok(Self {
+            transactions,
+            extra_columns,
+        })
+    }
+}
+
+impl ToSheetRows for SheetData {
+    fn to_sheet_rows(&self) -> crate::error::Result<Vec<Vec<Value>>> {
+        let mut rows = self.transactions.to_sheet_rows()?;
+
+        let extra_headers = self
+            .extra_columns
+            .headers
+            .iter()
+            .cloned()
+            .map(Value::String)
+            .collect();
+        self.extra_columns.insert_into(&mut rows[0], extra_headers);
+
+        for (row, transaction) in rows.iter_mut().skip(1).zip(&self.transactions) {
+            self.extra_columns
+                .insert_into(row, self.extra_columns.row(&transaction.id));
+        }
+
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+    use serde_json::json;
+
+    fn header_row(extra: &[&str]) -> Vec<Value> {
+        let mut headers: Vec<Value> = Transaction::get_field_names()
+            .into_iter()
+            .map(Value::String)
+            .collect();
+        headers.extend(extra.iter().map(|h| json!(h)));
+        headers
+    }
+
+    #[test]
+    fn test_from_sheet_rows_captures_extra_columns() {
+        let mut row_1 = vec![
+            json!("2024-11-23T10:00:00Z"),
+            json!("mock transaction: tx_1"),
+            json!(""),
+            json!("-12.34"),
+            json!("GBP"),
+            json!(""),
+            json!("Debit"),
+            json!("tx_1"),
+            json!(""),
+            json!(""),
+            json!(""),
+            json!(""),
+            json!(""),
+            json!(""),
+            json!(""),
+            json!(""),
+            json!(""),
+        ];
+        let row_2 = {
+            let mut row = row_1.clone();
+            row[7] = json!("tx_2");
+            row
+        };
+        row_1.extend([json!("Dinner"), json!("Yes")]);
+
+        let rows = vec![header_row(&["Notes", "Reimbursable"]), row_1, row_2];
+        let data = SheetData::from_sheet_rows(&rows).unwrap();
+
+        assert_eq!(data.transactions.len(), 2);
+        let managed = Transaction::get_field_names().len();
+        assert_eq!(
+            data.extra_columns,
+            ExtraColumns {
+                headers: vec!["Notes".to_string(), "Reimbursable".to_string()],
+                positions: vec![Some(managed), Some(managed + 1)],
+                values: HashMap::from([("tx_1".to_string(), vec![json!("Dinner"), json!("Yes")])]),
+            },
+            "rows without any extra values should not be stored"
+        );
+    }
+
+    #[test]
+    fn test_to_sheet_rows_writes_extra_columns_by_id() {
+        let tx_1 = mock_transaction(
+            "tx_1",
+            dec!(-12.34),
+            TransactionType::Debit,
+            mock_datetime(2024, 11, 23),
+        );
+        let tx_2 = mock_transaction(
+            "tx_2",
+            dec!(12.34),
+            TransactionType::Credit,
+            mock_datetime(2024, 11, 24),
+        );
+
+        let data = SheetData {
+            transactions: vec![tx_1, tx_2],
+            extra_columns: ExtraColumns {
+                headers: vec!["Who".to_string()],
+                values: HashMap::from([("tx_2".to_string(), vec![json!("Sam")])]),
+                ..Default::default()
+            },
+        };
+
+        let rows = data.to_sheet_rows().unwrap();
+        assert_eq!(rows[0], header_row(&["Who"]));
+        assert_eq!(rows[1].last(), Some(&Value::Null));
+        assert_eq!(rows[2].last(), Some(&json!("Sam")));
+    }
+
+    #[test]
+    fn test_copy_row_matches_headers() {
+        let from = ExtraColumns {
+            headers: vec!["Who".to_string(), "Notes".to_string()],
+            positions: vec![Some(4), Some(20)],
+            values: HashMap::from([("tx_1".to_string(), vec![json!("Sam"), json!("Dinner")])]),
+        };
+        let mut to = ExtraColumns {
+            headers: vec!["Notes".to_string()],
+            positions: vec![Some(18)],
+            values: HashMap::from([("tx_2".to_string(), vec![json!("Lunch")])]),
+        };
+
+        to.copy_row(&from, "tx_1");
+        to.copy_row(&from, "tx_3");
+
+        assert_eq!(
+            to,
+            ExtraColumns {
+                headers: vec!["Notes".to_string(), "Who".to_string()],
+                positions: vec![Some(18), Some(4)],
+                values: HashMap::from([
+                    ("tx_1".to_string(), vec![json!("Dinner"), json!("Sam")]),
+                    ("tx_2".to_string(), vec![json!("Lunch")]),
+                ]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_extra_columns_keep_their_position() {
+        let data = SheetData {
+            transactions: vec![mock_transaction(
+                "tx_1",
+                dec!(-12.34),
+                TransactionType::Debit,
+                mock_datetime(2024, 11, 23),
+            )],
+            extra_columns: ExtraColumns {
+                headers: vec!["Who".to_string()],
+                values: HashMap::from([("tx_1".to_string(), vec![json!("Sam")])]),
+                ..Default::default()
+            },
+        };
+        // Move the column from the end to between Amount and Currency, as a user would
+        let mut rows = data.to_sheet_rows().unwrap();
+        let amount = Transaction::get_column_index("Amount").unwrap();
+        for row in &mut rows {
+            let value = row.pop().unwrap();
+            row.insert(amount + 1, value);
+        }
+
+        let read = SheetData::from_sheet_rows(&rows).unwrap();
+        assert_eq!(read.extra_columns.positions, vec![Some(amount + 1)]);
+        let written = read.to_sheet_rows().unwrap();
+        assert_eq!(written[0][amount + 1], json!("Who"));
+        assert_eq!(written[1][amount + 1], json!("Sam"));
+        assert_eq!(written[0].len(), rows[0].len());
+    }
+
+    #[test]
+    fn test_round_trip_without_extra_columns() {
+        let data = SheetData {
+            transactions: vec![mock_transaction(
+                "tx_1",
+                dec!(-12.34),
+                TransactionType::Debit,
+                mock_datetime(2024, 11, 23),
+            )],
+            extra_columns: ExtraColumns::default(),
+        };
+
+        let rows = data.to_sheet_rows().unwrap();
+        assert_eq!(rows[0], header_row(&[]));
+        assert_eq!(SheetData::from_sheet_rows(&rows).unwrap(), data);
+    }
+}
@@ -0,0 +1,411 @@
+use crate::error::Result;
+use crate::models::Transaction;
+use crate::models::transaction::TransactionType;
+use chrono::{Datelike, Months, NaiveDate};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// A card's monthly statement cycle, closing on the same day each month, or the last day of
+/// shorter months.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatementCycle {
+    pub day: u32,
+}
+
+impl StatementCycle {
+    pub fn new(day: u32) -> Self {
+        Self { day }
+    }
+
+    /// Closing date of the statement that includes a date.
+    pub fn closing_date(&self, date: NaiveDate) -> NaiveDate {
+        let closing = self.closing_in_month(date);
+        match date <= closing {
+            true => closing,
+            false => self.closing_in_month(date + Months::new(1)),
+        }
+    }
+
+    fn closing_in_month(&self, date: NaiveDate) -> NaiveDate {
+        let first = date.with_day(1).expect("every month has a first day");
+        let last = (first + Months::new(1))
+            .pred_opt()
+            .expect("dates are in range");
+        first
+            .with_day(self.day.clamp(1, last.day()))
+            .expect("day is within the month")
+    }
+
+    /// Set each transaction's statement and put the statement's net amount on the last
+    /// transaction of each statement, as a subtotal. Transactions must be sorted by timestamp.
+    ///
+    /// Transactions already `archived` from the card count towards the totals of the statements
+    /// they're in, but aren't changed. Transactions that can't be totalled in `currency` are
+    /// left out of the totals.
+    pub fn assign(
+        &self,
+        transactions: &mut [Transaction],
+        archived: &[Transaction],
+        currency: StatementCurrency,
+    ) {
+        let mut totals: BTreeMap<NaiveDate, (Decimal, usize)> = BTreeMap::new();
+        for (idx, t) in transactions.iter_mut().enumerate() {
+            let closing = self.closing_date(t.timestamp.date_naive());
+            t.statement = Some(closing);
+            t.statement_total = None;
+
+            let total = totals.entry(closing).or_insert((Decimal::ZERO, idx));
+            total.0 += currency.amount(t).unwrap_or_default();
+            total.1 = idx;
+        }
+        for t in archived {
+            if let Some(total) = totals.get_mut(&self.closing_date(t.timestamp.date_naive())) {
+                total.0 += currency.amount(t).unwrap_or_default();
+            }
+        }
+
+        for (total, idx) in totals.into_values() {
+            transactions[idx].statement_total = Some(total);
+        }
+    }
+}
+
+/// The currency that statements are totalled in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementCurrency<'a> {
+    /// The base currency, totalling the converted amounts
+    Base,
+    /// The card's own currency, leaving out transactions in any other
+    Card(&'a str),
+}
+
+impl<'a> StatementCurrency<'a> {
+    /// Total in the base currency if one is configured, or else in the card's currency.
+    pub fn new(base: Option<&str>, card: &'a str) -> Self {
+        match base {
+            Some(_) => Self::Base,
+            None => Self::Card(card),
+        }
+    }
+
+    /// The amount that a transaction adds to its statement, if it can be totalled in this
+    /// currency.
+    fn amount(&self, transaction: &Transaction) -> Option<Decimal> {
+        match self {
+            Self::Base => transaction.converted_amount,
+            Self::Card(currency) => {
+                (transaction.currency == *currency).then_some(transaction.amount)
+            }
+        }
+    }
+}
+
+/// The currency that most of a card's transactions are in, taken to be the card's own.
+pub fn main_currency(transactions: &[Transaction]) -> Option<&str> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for t in transactions {
+        *counts.entry(t.currency.as_str()).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(currency, _)| currency)
+}
+
+/// Clear the statement columns, for cards whose cycle isn't known.
+pub fn clear_statements(transactions: &mut [Transaction]) {
+    for t in transactions {
+        t.statement = None;
+        t.statement_total = None;
+    }
+}
+
+/// Totals for one statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementSummary {
+    pub closing_date: NaiveDate,
+    /// Net amount of every transaction in the statement
+    pub total: Decimal,
+    /// Total of the debits that haven't been matched with a credit, so are still to be paid
+    pub unpaid: Decimal,
+    pub unpaid_count: usize,
+    /// Transactions left out of the totals, as they're in another currency or haven't been
+    /// converted
+    pub skipped: usize,
+}
+
+/// Summarise the statements that have closed on or before `today`, oldest first.
+pub fn summarise_statements(
+    cycle: StatementCycle,
+    transactions: &[Transaction],
+    currency: StatementCurrency,
+    today: NaiveDate,
+) -> Vec<StatementSummary> {
+    let mut summaries: BTreeMap<NaiveDate, StatementSummary> = BTreeMap::new();
+    for t in transactions {
+        let closing_date = cycle.closing_date(t.timestamp.date_naive());
+        if closing_date > today {
+            continue;
+        }
+
+        let summary = summaries
+            .entry(closing_date)
+            .or_insert_with(|| StatementSummary {
+                closing_date,
+                total: Decimal::ZERO,
+                unpaid: Decimal::ZERO,
+                unpaid_count: 0,
+                skipped: 0,
+            });
+        let Some(amount) = currency.amount(t) else {
+            summary.skipped += 1;
+            continue;
+        };
+        summary.total += amount;
+        if t.type_ == TransactionType::Debit && t.matched_id.is_none() {
+            summary.unpaid += amount;
+            summary.unpaid_count += 1;
+        }
+    }
+
+    summaries.into_values().collect()
+}
+
+/// Statement days learned from each card's balance, keyed by sheet tab, so that commands
+/// which don't fetch the balance can use them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatementDays {
+    days: BTreeMap<String, u32>,
+}
+
+impl StatementDays {
+    /// Load the days from a file, which is empty if the file doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, tab_name: &str) -> Option<u32> {
+        self.days.get(tab_name).copied()
+    }
+
+    pub fn set(&mut self, tab_name: &str, day: u32) {
+        self.days.insert(tab_name.to_string(), day);
+    }
+
+    /// Learn a card's closing day from the date its last statement closed.
+    ///
+    /// A statement that closed on the last day of a month may be from a card that closes at the
+    /// end of every month, so it doesn't replace a later day learned before, and is learned as
+    /// 31 if none was. Otherwise a card closing at month end would learn 28 after February and
+    /// close on the 28th from then on.
+    pub fn learn(&mut self, tab_name: &str, closing_date: NaiveDate) {
+        let day = closing_date.day();
+        let is_month_end = closing_date.succ_opt().is_none_or(|next| next.day() == 1);
+        let day = match (is_month_end, self.get(tab_name)) {
+            (true, Some(previous)) => previous.max(day),
+            (true, None) => 31,
+            (false, _) => day,
+        };
+        self.set(tab_name, day);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_closing_date() {
+        let cycle = StatementCycle::new(15);
+        assert_eq!(cycle.closing_date(date(2025, 1, 3)), date(2025, 1, 15));
+        assert_eq!(cycle.closing_date(date(2025, 1, 15)), date(2025, 1, 15));
+        assert_eq!(cycle.closing_date(date(2025, 1, 16)), date(2025, 2, 15));
+        assert_eq!(cycle.closing_date(date(2025, 12, 20)), date(2026, 1, 15));
+
+        let cycle = StatementCycle::new(31);
+        assert_eq!(cycle.closing_date(date(2025, 2, 10)), date(2025, 2, 28));
+        assert_eq!(cycle.closing_date(date(2025, 3, 1)), date(2025, 3, 31));
+    }
+
+    fn mock_transactions() -> Vec<Transaction> {
+        let debit = |id, amount, day| {
+            mock_transaction(
+                id,
+                amount,
+                TransactionType::Debit,
+                mock_datetime(2025, 1, day),
+            )
+        };
+        vec![
+            Transaction {
+                matched_id: Some("tx_payment".to_string()),
+                ..debit("tx_1", dec!(-10.00), 2)
+            },
+            debit("tx_2", dec!(-5.00), 10),
+            debit("tx_3", dec!(-20.00), 20),
+            Transaction {
+                matched_id: Some("tx_1".to_string()),
+                ..mock_transaction(
+                    "tx_payment",
+                    dec!(10.00),
+                    TransactionType::Credit,
+                    mock_datetime(2025, 1, 25),
+                )
+            },
+        ]
+    }
+
+    #[test]
+    fn test_learn_statement_days() {
+        let mut days = StatementDays::default();
+        days.learn("Amex", date(2025, 1, 31));
+        days.learn("Amex", date(2025, 2, 28));
+        assert_eq!(
+            days.get("Amex"),
+            Some(31),
+            "month end shouldn't become the 28th"
+        );
+        assert_eq!(
+            StatementCycle::new(31).closing_date(date(2025, 3, 2)),
+            date(2025, 3, 31)
+        );
+
+        days.learn("Visa", date(2025, 1, 30));
+        days.learn("Visa", date(2025, 4, 30));
+        assert_eq!(days.get("Visa"), Some(30));
+        days.learn("Visa", date(2025, 5, 12));
+        assert_eq!(
+            days.get("Visa"),
+            Some(12),
+            "a changed cycle should be learned"
+        );
+
+        days.learn("Mastercard", date(2025, 2, 28));
+        assert_eq!(days.get("Mastercard"), Some(31));
+        days.learn("Mastercard", date(2025, 3, 28));
+        assert_eq!(days.get("Mastercard"), Some(28));
+    }
+
+    #[test]
+    fn test_assign() {
+        let mut transactions = mock_transactions();
+        StatementCycle::new(15).assign(&mut transactions, &[], StatementCurrency::Card("GBP"));
+
+        let statements: Vec<_> = transactions.iter().map(|t| t.statement).collect();
+        assert_eq!(
+            statements,
+            vec![
+                Some(date(2025, 1, 15)),
+                Some(date(2025, 1, 15)),
+                Some(date(2025, 2, 15)),
+                Some(date(2025, 2, 15)),
+            ]
+        );
+        let totals: Vec<_> = transactions.iter().map(|t| t.statement_total).collect();
+        assert_eq!(
+            totals,
+            vec![None, Some(dec!(-15.00)), None, Some(dec!(-10.00))]
+        );
+    }
+
+    #[test]
+    fn test_assign_includes_archived_transactions() {
+        let mut transactions = mock_transactions();
+        let archived = transactions.remove(0);
+        StatementCycle::new(15).assign(
+            &mut transactions,
+            &[archived],
+            StatementCurrency::Card("GBP"),
+        );
+
+        let totals: Vec<_> = transactions.iter().map(|t| t.statement_total).collect();
+        assert_eq!(
+            totals,
+            vec![Some(dec!(-15.00)), None, Some(dec!(-10.00))],
+            "the statement's total should include the archived transaction"
+        );
+    }
+
+    #[test]
+    fn test_summarise_statements() {
+        let summaries = summarise_statements(
+            StatementCycle::new(15),
+            &mock_transactions(),
+            StatementCurrency::Card("GBP"),
+            date(2025, 2, 1),
+        );
+
+        assert_eq!(
+            summaries,
+            vec![StatementSummary {
+                closing_date: date(2025, 1, 15),
+                total: dec!(-15.00),
+                unpaid: dec!(-5.00),
+                unpaid_count: 1,
+                skipped: 0,
+            }],
+            "only closed statements should be summarised"
+        );
+    }
+
+    #[test]
+    fn test_statements_in_other_currencies() {
+        let mut transactions = mock_transactions();
+        transactions[0].converted_amount = Some(dec!(-10.00));
+        transactions[1] = Transaction {
+            currency: "EUR".to_string(),
+            amount: dec!(-6.00),
+            converted_amount: Some(dec!(-5.00)),
+            ..transactions[1].clone()
+        };
+        let cycle = StatementCycle::new(15);
+        let today = date(2025, 2, 1);
+
+        assert_eq!(main_currency(&transactions), Some("GBP"));
+        let summaries =
+            summarise_statements(cycle, &transactions, StatementCurrency::Card("GBP"), today);
+        assert_eq!(
+            (
+                summaries[0].total,
+                summaries[0].unpaid_count,
+                summaries[0].skipped
+            ),
+            (dec!(-10.00), 0, 1),
+            "transactions in another currency should be left out without a base currency"
+        );
+        let summaries = summarise_statements(cycle, &transactions, StatementCurrency::Base, today);
+        assert_eq!(
+            (
+                summaries[0].total,
+                summaries[0].unpaid,
+                summaries[0].skipped
+            ),
+            (dec!(-15.00), dec!(-5.00), 0),
+            "converted amounts should be totalled in the base currency"
+        );
+
+        cycle.assign(&mut transactions, &[], StatementCurrency::Card("GBP"));
+        assert_eq!(transactions[1].statement_total, Some(dec!(-10.00)));
+        cycle.assign(&mut transactions, &[], StatementCurrency::Base);
+        assert_eq!(transactions[1].statement_total, Some(dec!(-15.00)));
+    }
+}
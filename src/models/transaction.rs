@@ -2,7 +2,7 @@ use crate::{
     error::AppError,
     truelayer::types::{TrueLayerTransaction, TrueLayerTransactionType},
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
 use rust_decimal::prelude::dec;
 use serde::{Deserialize, Serialize};
@@ -13,15 +13,42 @@ use serde_json::{Map, Value};
 pub struct Transaction {
     pub timestamp: DateTime<Utc>,
     pub description: String,
+    /// Cleaned up merchant name, derived from the description
+    #[serde(default)]
+    pub merchant: Option<String>,
     pub amount: Decimal,
     pub currency: String,
+    /// Amount in the base currency, at the exchange rate on the transaction date
+    #[serde(rename = "Converted Amount", default)]
+    pub converted_amount: Option<Decimal>,
     pub type_: TransactionType,
     #[serde(rename = "ID")]
     pub id: String,
     #[serde(rename = "Matched ID", default)]
     pub matched_id: Option<String>,
+    /// How closely the matched transaction's amount agrees, from 0 to 1, set by reconcile
+    #[serde(rename = "Match Confidence", default)]
+    pub match_confidence: Option<Decimal>,
+    /// Days since an unmatched transaction, as of the last sync, blank once it's matched
+    #[serde(rename = "Age (days)", default)]
+    pub age_days: Option<i64>,
     #[serde(default)]
     pub comments: Option<String>,
+    /// Set by the first matching categorisation rule, or typed by hand
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Comma-separated tags, set along with the category
+    #[serde(default)]
+    pub tags: Option<String>,
+    /// Name of the rule that set the category and tags, blank if they were typed by hand
+    #[serde(default)]
+    pub rule: Option<String>,
+    /// Closing date of the statement that the transaction is on
+    #[serde(default)]
+    pub statement: Option<NaiveDate>,
+    /// Net amount of the statement, on its last transaction
+    #[serde(rename = "Statement Total", default)]
+    pub statement_total: Option<Decimal>,
 }
 
 impl From<TrueLayerTransaction> for Transaction {
@@ -29,12 +56,21 @@ impl From<TrueLayerTransaction> for Transaction {
         Transaction {
             timestamp: tl.timestamp,
             description: tl.description,
+            merchant: None,
             amount: tl.amount,
             currency: tl.currency,
+            converted_amount: None,
             type_: tl.transaction_type.into(),
             id: tl.normalised_provider_transaction_id,
             matched_id: None,
+            match_confidence: None,
+            age_days: None,
             comments: None,
+            category: None,
+            tags: None,
+            rule: None,
+            statement: None,
+            statement_total: None,
         }
     }
 }
@@ -42,7 +78,7 @@ impl From<TrueLayerTransaction> for Transaction {
 impl Transaction {
     /// Derive the CSV headers from the struct definition by serializing a dummy instance.
     /// https://github.com/BurntSushi/rust-csv/issues/161
-    fn get_field_names() -> Vec<String> {
+    pub(crate) fn get_field_names() -> Vec<String> {
         let mut writer = csv::WriterBuilder::new()
             .has_headers(true)
             .from_writer(Vec::new());
@@ -50,12 +86,21 @@ impl Transaction {
         let dummy = Transaction {
             timestamp: Utc::now(),
             description: String::new(),
+            merchant: None,
             amount: dec!(0),
             currency: String::new(),
+            converted_amount: None,
             type_: TransactionType::Debit,
             id: String::new(),
             matched_id: None,
+            match_confidence: None,
+            age_days: None,
             comments: None,
+            category: None,
+            tags: None,
+            rule: None,
+            statement: None,
+            statement_total: None,
         };
 
         // Serialize to write headers
@@ -73,19 +118,36 @@ impl Transaction {
             .unwrap_or_default()
     }
 
-    /// Get the column index (0-based) for a specific field name.
+    /// Columns with the details that come from the provider, compared by `same_details`.
+    pub const DETAIL_FIELDS: &[&str] = &["Timestamp", "Description", "Amount", "Currency", "Type"];
+
+    /// Whether the details that come from the provider are the same, ignoring the columns
+    /// that are filled in by sync or by hand.
+    pub fn same_details(&self, other: &Self) -> bool {
+        self.timestamp == other.timestamp
+            && self.description == other.description
+            && self.amount == other.amount
+            && self.currency == other.currency
+            && self.type_ == other.type_
+    }
+
+    /// Get the column index (0-based) for a specific field name, in a sheet without any of the
+    /// user's own columns. Written sheets are formatted from their actual header row instead.
+    #[cfg(test)]
     pub fn get_column_index(field_name: &str) -> Option<usize> {
         Self::get_field_names()
             .iter()
             .position(|name| name == field_name)
     }
 
-    /// Get the column letter (A-based) for a specific field name.
+    /// Get the column letter (A-based) for a specific field name, see `get_column_index`.
+    #[cfg(test)]
     pub fn get_column_letter(field_name: &str) -> Option<String> {
         Self::get_column_index(field_name).map(Self::index_to_column_letter)
     }
 
-    fn index_to_column_letter(col_idx: usize) -> String {
+    /// Get the column letter (A-based) for a column index.
+    pub(crate) fn index_to_column_letter(col_idx: usize) -> String {
         let remainder = col_idx % 26;
         let char = (b'A' + remainder as u8) as char;
 
@@ -97,14 +159,14 @@ impl Transaction {
         format!("{}{}", Self::index_to_column_letter(parent), char)
     }
 
-    fn value_to_string(v: &Value) -> String {
+    pub(crate) fn value_to_string(v: &Value) -> String {
         match v {
             Value::String(s) => s.clone(),
             _ => v.to_string(),
         }
     }
 
-    fn normalize_sheet_value(v: &Value) -> Value {
+    pub(crate) fn normalize_sheet_value(v: &Value) -> Value {
         match v {
             Value::String(s) if s.is_empty() => Value::Null,
             _ => v.clone(),
@@ -211,12 +273,21 @@ pub(crate) mod test_helpers {
         Transaction {
             timestamp,
             description: format!("mock transaction: {id}"),
+            merchant: None,
             currency: "GBP".to_string(),
+            converted_amount: None,
             amount,
             type_,
             id: id.to_string(),
             matched_id: None,
+            match_confidence: None,
+            age_days: None,
             comments: None,
+            category: None,
+            tags: None,
+            rule: None,
+            statement: None,
+            statement_total: None,
         }
     }
 }
@@ -241,22 +312,40 @@ mod tests {
             vec![
                 json!("Timestamp"),
                 json!("Description"),
+                json!("Merchant"),
                 json!("Amount"),
                 json!("Currency"),
+                json!("Converted Amount"),
                 json!("Type"),
                 json!("ID"),
                 json!("Matched ID"),
+                json!("Match Confidence"),
+                json!("Age (days)"),
                 json!("Comments"),
+                json!("Category"),
+                json!("Tags"),
+                json!("Rule"),
+                json!("Statement"),
+                json!("Statement Total"),
             ],
             vec![
                 json!("2024-11-23T10:00:00Z"),
                 json!("mock transaction: tx_123"),
+                Value::Null,
                 json!("-12.34"), // rust_decimal serializes to string by default
                 json!("GBP"),
+                Value::Null,
                 json!("Debit"),
                 json!("tx_123"),
                 Value::Null, // Option::None serializes to null
                 Value::Null, // Option::None serializes to null
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
             ],
         ];
         assert_eq!(rows, expected);
@@ -269,12 +358,21 @@ mod tests {
         let expected = vec![vec![
             json!("Timestamp"),
             json!("Description"),
+            json!("Merchant"),
             json!("Amount"),
             json!("Currency"),
+            json!("Converted Amount"),
             json!("Type"),
             json!("ID"),
             json!("Matched ID"),
+            json!("Match Confidence"),
+            json!("Age (days)"),
             json!("Comments"),
+            json!("Category"),
+            json!("Tags"),
+            json!("Rule"),
+            json!("Statement"),
+            json!("Statement Total"),
         ]];
         assert_eq!(rows, expected);
     }
@@ -285,16 +383,21 @@ mod tests {
             vec![
                 json!("Timestamp"),
                 json!("Description"),
+                json!("Merchant"),
                 json!("Amount"),
                 json!("Currency"),
                 json!("Type"),
                 json!("ID"),
                 json!("Matched ID"),
                 json!("Comments"),
+                json!("Category"),
+                json!("Tags"),
+                json!("Rule"),
             ],
             vec![
                 json!("2024-11-23T10:00:00Z"),
                 json!("mock transaction: tx_123"),
+                json!(""),
                 json!("-12.34"),
                 json!("GBP"),
                 json!("Debit"),
@@ -308,12 +411,21 @@ mod tests {
         let expected = vec![Transaction {
             timestamp: test_helpers::mock_datetime(2024, 11, 23),
             description: "mock transaction: tx_123".to_string(),
+            merchant: None,
             currency: "GBP".to_string(),
+            converted_amount: None,
             amount: dec!(-12.34),
             type_: TransactionType::Debit,
             id: "tx_123".to_string(),
             matched_id: None,
+            match_confidence: None,
+            age_days: None,
             comments: None,
+            category: None,
+            tags: None,
+            rule: None,
+            statement: None,
+            statement_total: None,
         }];
         assert_eq!(transactions, expected);
     }
@@ -332,16 +444,21 @@ mod tests {
             vec![
                 json!("Timestamp"),
                 json!("Description"),
+                json!("Merchant"),
                 json!("Amount"),
                 json!("Currency"),
                 json!("Type"),
                 json!("ID"),
                 json!("Matched ID"),
                 json!("Comments"),
+                json!("Category"),
+                json!("Tags"),
+                json!("Rule"),
             ],
             vec![
                 json!("2024-11-23T10:00:00Z"),
                 json!("Test transaction"),
+                json!(""),
                 json!("100.00"),
                 json!("GBP"),
                 json!("Credit"),
@@ -355,12 +472,21 @@ mod tests {
         let expected = vec![Transaction {
             timestamp: test_helpers::mock_datetime(2024, 11, 23),
             description: "Test transaction".to_string(),
+            merchant: None,
             currency: "GBP".to_string(),
+            converted_amount: None,
             amount: dec!(100.00),
             type_: TransactionType::Credit,
             id: "tx_123".to_string(),
             matched_id: Some("tx_456".to_string()),
+            match_confidence: None,
+            age_days: None,
             comments: Some("Manually added comment".to_string()),
+            category: None,
+            tags: None,
+            rule: None,
+            statement: None,
+            statement_total: None,
         }];
         assert_eq!(transactions, expected);
     }
@@ -370,12 +496,21 @@ mod tests {
         let rows = vec![vec![
             json!("Timestamp"),
             json!("Description"),
+            json!("Merchant"),
             json!("Amount"),
             json!("Currency"),
+            json!("Converted Amount"),
             json!("Type"),
             json!("ID"),
             json!("Matched ID"),
+            json!("Match Confidence"),
+            json!("Age (days)"),
             json!("Comments"),
+            json!("Category"),
+            json!("Tags"),
+            json!("Rule"),
+            json!("Statement"),
+            json!("Statement Total"),
         ]];
 
         let transactions = Transaction::from_sheet_rows(&rows).unwrap();
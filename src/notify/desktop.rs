@@ -0,0 +1,89 @@
+use super::{Notification, Notifier};
+use crate::config::{DesktopConfig, NotifyEvent};
+use crate::error::{AppError, Result};
+use crate::sync::report::SyncReport;
+use async_trait::async_trait;
+use std::process::Command;
+
+/// Shows a notification with `osascript` on macOS, or `notify-send` elsewhere.
+pub(super) struct DesktopNotifier {
+    config: DesktopConfig,
+}
+
+impl DesktopNotifier {
+    pub(super) fn new(config: DesktopConfig) -> Self {
+        Self { config }
+    }
+}
+
+/// Quote a string for AppleScript.
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn command(notification: &Notification) -> Command {
+    let mut command = match cfg!(target_os = "macos") {
+        true => {
+            let mut command = Command::new("osascript");
+            command.arg("-e").arg(format!(
+                "display notification {} with title {}",
+                applescript_string(&notification.text()),
+                applescript_string(&notification.title)
+            ));
+            command
+        }
+        false => {
+            let mut command = Command::new("notify-send");
+            command
+                .arg("--app-name=credit-card-tracker")
+                .arg(&notification.title)
+                .arg(notification.text());
+            command
+        }
+    };
+    command.stdin(std::process::Stdio::null());
+    command
+}
+
+#[async_trait]
+impl Notifier for DesktopNotifier {
+    fn name(&self) -> &'static str {
+        "desktop"
+    }
+
+    fn events(&self) -> &[NotifyEvent] {
+        &self.config.events
+    }
+
+    async fn send(&self, notification: &Notification, _report: &SyncReport) -> Result<()> {
+        let mut command = command(notification);
+        let program = command.get_program().to_string_lossy().to_string();
+        let output = command
+            .output()
+            .map_err(|e| AppError::Other(anyhow::anyhow!("Failed to run {}: {}", program, e)))?;
+
+        if !output.status.success() {
+            return Err(AppError::Other(anyhow::anyhow!(
+                "{} failed ({}): {}",
+                program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_applescript_string() {
+        assert_eq!(
+            applescript_string(r#"Run "auth" \ now"#),
+            r#""Run \"auth\" \\ now""#
+        );
+    }
+}
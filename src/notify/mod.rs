@@ -0,0 +1,261 @@
+mod desktop;
+mod smtp;
+mod webhook;
+
+use crate::config::{NotifyConfig, NotifyEvent};
+use crate::error::Result;
+use crate::sync::report::SyncReport;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+/// Command to renew the TrueLayer consent.
+const RENEW_COMMAND: &str = "credit-card-tracker auth truelayer --reset";
+
+/// One line of a notification, about a single event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NotificationLine {
+    pub event: NotifyEvent,
+    pub text: String,
+}
+
+/// Summary of a sync to send to each output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    pub title: String,
+    pub lines: Vec<NotificationLine>,
+}
+
+impl Notification {
+    /// Describe each event in a sync report, as of `now`.
+    pub fn from_report(report: &SyncReport, consent_warning_days: u32, now: DateTime<Utc>) -> Self {
+        let mut lines = Vec::new();
+        let mut line = |event, text: String| lines.push(NotificationLine { event, text });
+
+        for error in &report.errors {
+            line(NotifyEvent::Errors, format!("Sync failed: {}", error));
+        }
+
+        if let Some(expires_at) = report.consent_expires_at
+            && expires_at - now <= Duration::days(i64::from(consent_warning_days))
+        {
+            let verb = match expires_at <= now {
+                true => "expired",
+                false => "expires",
+            };
+            line(
+                NotifyEvent::ConsentExpiry,
+                format!(
+                    "TrueLayer consent {} on {}, run `{}` to renew it",
+                    verb,
+                    expires_at.format("%Y-%m-%d"),
+                    RENEW_COMMAND
+                ),
+            );
+        }
+
+        for card in &report.cards {
+            if let Some(error) = &card.error {
+                line(
+                    NotifyEvent::Errors,
+                    format!("{}: sync failed: {}", card.card, error),
+                );
+            }
+            if card.added > 0 {
+                line(
+                    NotifyEvent::NewTransactions,
+                    format!("{}: {} new transactions", card.card, card.added),
+                );
+            }
+            if card.matched > 0 {
+                line(
+                    NotifyEvent::NewMatches,
+                    format!("{}: {} pairs matched", card.card, card.matched),
+                );
+            }
+            // Debits left unmatched by earlier syncs have already been notified
+            if card.new_unmatched_count > 0 {
+                line(
+                    NotifyEvent::Unmatched,
+                    format!(
+                        "{}: {} new unmatched debits totalling {}",
+                        card.card, card.new_unmatched_count, -card.new_unmatched_total
+                    ),
+                );
+            }
+            if let Some(alert) = &card.overdue {
+                line(
+                    NotifyEvent::Overdue,
+                    format!(
+                        "{}: {} unmatched transactions older than {} days, the oldest is {} days",
+                        card.card, alert.count, alert.threshold_days, alert.oldest_days
+                    ),
+                );
+            }
+        }
+
+        let title = match report.is_success() {
+            true => "Credit card sync completed",
+            false => "Credit card sync failed",
+        };
+        Self {
+            title: title.to_string(),
+            lines,
+        }
+    }
+
+    /// Keep only the lines about `events`, or `None` if there are none left to send.
+    pub fn filter(&self, events: &[NotifyEvent]) -> Option<Self> {
+        let lines: Vec<NotificationLine> = self
+            .lines
+            .iter()
+            .filter(|line| events.contains(&line.event))
+            .cloned()
+            .collect();
+
+        match lines.is_empty() {
+            true => None,
+            false => Some(Self {
+                title: self.title.clone(),
+                lines,
+            }),
+        }
+    }
+
+    /// The lines as plain text, one per line.
+    pub fn text(&self) -> String {
+        let lines: Vec<&str> = self.lines.iter().map(|line| line.text.as_str()).collect();
+        lines.join("\n")
+    }
+}
+
+/// An output that notifications can be sent to.
+#[async_trait]
+trait Notifier {
+    fn name(&self) -> &'static str;
+
+    /// Events that this output is sent.
+    fn events(&self) -> &[NotifyEvent];
+
+    async fn send(&self, notification: &Notification, report: &SyncReport) -> Result<()>;
+}
+
+fn notifiers(config: &NotifyConfig) -> Vec<Box<dyn Notifier + Send + Sync>> {
+    let mut notifiers: Vec<Box<dyn Notifier + Send + Sync>> = Vec::new();
+    if let Some(webhook) = &config.webhook {
+        notifiers.push(Box::new(webhook::WebhookNotifier::new(webhook.clone())));
+    }
+    if let Some(smtp) = &config.smtp {
+        notifiers.push(Box::new(smtp::SmtpNotifier::new(smtp.clone())));
+    }
+    if let Some(desktop) = &config.desktop {
+        notifiers.push(Box::new(desktop::DesktopNotifier::new(desktop.clone())));
+    }
+    notifiers
+}
+
+/// Send a report of a sync to every configured output that wants its events. Failures are
+/// logged rather than returned, so that they don't fail the sync.
+pub async fn notify(config: &NotifyConfig, report: &SyncReport) {
+    let notification = Notification::from_report(report, config.consent_warning_days, Utc::now());
+
+    for notifier in notifiers(config) {
+        let Some(notification) = notification.filter(notifier.events()) else {
+            debug!(output = notifier.name(), "Nothing to notify");
+            continue;
+        };
+
+        match notifier.send(&notification, report).await {
+            Ok(()) => info!(output = notifier.name(), "Notification sent"),
+            Err(e) => warn!(output = notifier.name(), error = %e, "Failed to send notification"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::aging::AgeAlert;
+    use crate::sync::report::CardReport;
+    use chrono::TimeZone;
+    use rust_decimal::prelude::dec;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 1, 9, 0, 0).unwrap()
+    }
+
+    fn mock_report() -> SyncReport {
+        SyncReport {
+            cards: vec![
+                CardReport {
+                    card: "Amex".to_string(),
+                    added: 3,
+                    matched: 1,
+                    unmatched_count: 5,
+                    unmatched_total: dec!(-120.00),
+                    new_unmatched_count: 2,
+                    new_unmatched_total: dec!(-45.50),
+                    overdue: Some(AgeAlert {
+                        tab_name: "Amex".to_string(),
+                        threshold_days: 30,
+                        count: 1,
+                        oldest_days: 41,
+                    }),
+                    ..Default::default()
+                },
+                CardReport {
+                    card: "Visa".to_string(),
+                    error: Some("Failed to get card transactions".to_string()),
+                    ..Default::default()
+                },
+            ],
+            errors: vec!["No cards found".to_string()],
+            consent_expires_at: Some(now() + Duration::days(5)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_from_report() {
+        let notification = Notification::from_report(&mock_report(), 7, now());
+
+        assert_eq!(notification.title, "Credit card sync failed");
+        assert_eq!(
+            notification.text(),
+            "Sync failed: No cards found\n\
+             TrueLayer consent expires on 2025-03-06, run `credit-card-tracker auth truelayer --reset` to renew it\n\
+             Amex: 3 new transactions\n\
+             Amex: 1 pairs matched\n\
+             Amex: 2 new unmatched debits totalling 45.50\n\
+             Amex: 1 unmatched transactions older than 30 days, the oldest is 41 days\n\
+             Visa: sync failed: Failed to get card transactions"
+        );
+
+        let notification = Notification::from_report(&mock_report(), 3, now());
+        assert!(
+            !notification
+                .lines
+                .iter()
+                .any(|line| line.event == NotifyEvent::ConsentExpiry),
+            "consent expiring after the warning days shouldn't be notified"
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let notification = Notification::from_report(&mock_report(), 7, now());
+
+        let filtered = notification
+            .filter(&[NotifyEvent::NewTransactions, NotifyEvent::Overdue])
+            .unwrap();
+        assert_eq!(filtered.lines.len(), 2);
+        assert_eq!(filtered.lines[0].text, "Amex: 3 new transactions");
+
+        let mut report = mock_report();
+        report.errors.clear();
+        report.cards.truncate(1);
+        let notification = Notification::from_report(&report, 7, now());
+        assert_eq!(notification.filter(&[NotifyEvent::Errors]), None);
+    }
+}
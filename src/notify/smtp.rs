@@ -0,0 +1,137 @@
+use super::{Notification, Notifier};
+use crate::config::{NotifyEvent, SmtpConfig};
+use crate::error::{AppError, Result};
+use crate::sync::report::SyncReport;
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+/// Port for SMTP over TLS, rather than STARTTLS.
+const TLS_PORT: u16 = 465;
+
+/// Emails the notification as plain text.
+pub(super) struct SmtpNotifier {
+    config: SmtpConfig,
+}
+
+impl SmtpNotifier {
+    pub(super) fn new(config: SmtpConfig) -> Self {
+        Self { config }
+    }
+}
+
+fn smtp_error(message: &str, e: impl std::fmt::Display) -> AppError {
+    AppError::Other(anyhow::anyhow!("{}: {}", message, e))
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox> {
+    address
+        .parse()
+        .map_err(|e| smtp_error(&format!("Invalid email address {:?}", address), e))
+}
+
+fn message(config: &SmtpConfig, notification: &Notification) -> Result<Message> {
+    let mut builder = Message::builder()
+        .from(parse_mailbox(&config.from)?)
+        .subject(&notification.title)
+        .header(ContentType::TEXT_PLAIN);
+    for to in &config.to {
+        builder = builder.to(parse_mailbox(to)?);
+    }
+
+    builder
+        .body(notification.text())
+        .map_err(|e| smtp_error("Failed to build email", e))
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    fn events(&self) -> &[NotifyEvent] {
+        &self.config.events
+    }
+
+    async fn send(&self, notification: &Notification, _report: &SyncReport) -> Result<()> {
+        let message = message(&self.config, notification)?;
+
+        let host = &self.config.host;
+        let mut transport = match self.config.port {
+            TLS_PORT => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+        }
+        .map_err(|e| smtp_error("Invalid SMTP host", e))?
+        .port(self.config.port);
+        if let Some(username) = &self.config.username {
+            transport = transport.credentials(Credentials::new(
+                username.clone(),
+                self.config.password.clone(),
+            ));
+        }
+
+        transport
+            .build()
+            .send(message)
+            .await
+            .map_err(|e| smtp_error("Failed to send email", e))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::NotificationLine;
+
+    fn mock_config() -> SmtpConfig {
+        SmtpConfig {
+            host: "smtp.example.com".to_string(),
+            port: 587,
+            username: None,
+            password: String::new(),
+            password_command: None,
+            from: "Tracker <tracker@example.com>".to_string(),
+            to: vec![
+                "sam@example.com".to_string(),
+                "alex@example.com".to_string(),
+            ],
+            events: NotifyEvent::ALL.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_message() {
+        let notification = Notification {
+            title: "Credit card sync completed".to_string(),
+            lines: vec![NotificationLine {
+                event: NotifyEvent::NewTransactions,
+                text: "Amex: 3 new transactions".to_string(),
+            }],
+        };
+
+        let email = message(&mock_config(), &notification).unwrap();
+        let email = String::from_utf8(email.formatted()).unwrap();
+        assert!(
+            email.contains("Subject: Credit card sync completed"),
+            "{}",
+            email
+        );
+        assert!(
+            email.contains("To: sam@example.com, alex@example.com"),
+            "{}",
+            email
+        );
+        assert!(email.contains("Amex: 3 new transactions"), "{}", email);
+
+        let config = SmtpConfig {
+            to: vec!["not an address".to_string()],
+            ..mock_config()
+        };
+        assert!(message(&config, &notification).is_err());
+    }
+}
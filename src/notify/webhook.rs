@@ -0,0 +1,114 @@
+use super::{Notification, Notifier};
+use crate::config::{NotifyEvent, WebhookConfig};
+use crate::error::{AppError, Result};
+use crate::sync::report::SyncReport;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::time::Duration;
+
+/// How long to wait for the webhook, so that a server that doesn't respond can't hold up the
+/// sync.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// POSTs a JSON body with the notification and the full report.
+pub(super) struct WebhookNotifier {
+    config: WebhookConfig,
+}
+
+#[derive(Serialize)]
+struct WebhookBody<'a> {
+    title: &'a str,
+    text: String,
+    events: Vec<NotifyEvent>,
+    report: &'a SyncReport,
+}
+
+impl WebhookNotifier {
+    pub(super) fn new(config: WebhookConfig) -> Self {
+        Self { config }
+    }
+}
+
+fn body<'a>(notification: &'a Notification, report: &'a SyncReport) -> WebhookBody<'a> {
+    let mut events: Vec<NotifyEvent> = Vec::new();
+    for line in &notification.lines {
+        if !events.contains(&line.event) {
+            events.push(line.event);
+        }
+    }
+
+    WebhookBody {
+        title: &notification.title,
+        text: notification.text(),
+        events,
+        report,
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn events(&self) -> &[NotifyEvent] {
+        &self.config.events
+    }
+
+    async fn send(&self, notification: &Notification, report: &SyncReport) -> Result<()> {
+        let response = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .build()?
+            .post(&self.config.url)
+            .json(&body(notification, report))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::Other(anyhow::anyhow!(
+                "Webhook returned {} - {}",
+                status,
+                body
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::NotificationLine;
+    use serde_json::json;
+
+    #[test]
+    fn test_body() {
+        let notification = Notification {
+            title: "Credit card sync completed".to_string(),
+            lines: vec![
+                NotificationLine {
+                    event: NotifyEvent::NewTransactions,
+                    text: "Amex: 3 new transactions".to_string(),
+                },
+                NotificationLine {
+                    event: NotifyEvent::NewTransactions,
+                    text: "Visa: 1 new transactions".to_string(),
+                },
+            ],
+        };
+        let report = SyncReport::default();
+
+        assert_eq!(
+            serde_json::to_value(body(&notification, &report)).unwrap(),
+            json!({
+                "title": "Credit card sync completed",
+                "text": "Amex: 3 new transactions\nVisa: 1 new transactions",
+                "events": ["new_transactions"],
+                "report": {"cards": [], "errors": []},
+            })
+        );
+    }
+}
@@ -0,0 +1,381 @@
+mod output;
+
+use crate::export::ExportRecord;
+use crate::models::Transaction;
+use crate::models::transaction::TransactionType;
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+pub use output::write_report;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Tables for reading in a terminal
+    Table,
+    /// A JSON object with a list of rows for each section
+    Json,
+    /// Comma-separated values, with a row per line of every section
+    Csv,
+}
+
+/// Label for transactions without a category.
+const UNCATEGORISED: &str = "Uncategorised";
+
+/// Label for the trend across every card.
+const ALL_CARDS: &str = "All cards";
+
+/// Ages of outstanding debits, as (label, oldest age in days).
+const AGE_BUCKETS: &[(&str, i64)] = &[
+    ("0-30 days", 30),
+    ("31-60 days", 60),
+    ("61-90 days", 90),
+    ("90+ days", i64::MAX),
+];
+
+/// What to include in a report.
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    /// Date that the report is made on
+    pub today: NaiveDate,
+    /// Number of months of spending, including the current month
+    pub months: u32,
+    /// Length of the periods compared by the trend
+    pub trend_days: i64,
+    /// Number of merchants to list
+    pub top_merchants: usize,
+    /// Base currency, so that converted amounts are totalled instead of amounts
+    pub base_currency: Option<String>,
+}
+
+/// One line of a report section. Spending is positive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReportRow {
+    /// Card, category or merchant
+    pub name: String,
+    /// Month or age of the transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<String>,
+    pub count: usize,
+    pub amount: Decimal,
+    /// Amount in the previous period, for trends
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Decimal>,
+}
+
+/// Spending and outstanding balance analytics for the stored transactions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+    /// Currency of the amounts, if they have all been converted into the base currency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// Debits that haven't been matched, by card and age
+    pub outstanding: Vec<ReportRow>,
+    /// Spending by card and month
+    pub monthly_by_card: Vec<ReportRow>,
+    /// Spending by category and month
+    pub monthly_by_category: Vec<ReportRow>,
+    /// Merchants with the most spending
+    pub merchants: Vec<ReportRow>,
+    /// Spending in the latest period compared with the one before, by card
+    pub trend: Vec<ReportRow>,
+    /// Transactions left out because they have no converted amount
+    #[serde(skip_serializing_if = "is_zero")]
+    pub unconverted: usize,
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+/// Totals keyed by name and period, kept in order.
+type Totals = BTreeMap<(String, String), (usize, Decimal)>;
+
+fn add(totals: &mut Totals, name: &str, period: &str, amount: Decimal) {
+    let total = totals
+        .entry((name.to_string(), period.to_string()))
+        .or_default();
+    total.0 += 1;
+    total.1 += amount;
+}
+
+fn rows(totals: Totals) -> Vec<ReportRow> {
+    totals
+        .into_iter()
+        .map(|((name, period), (count, amount))| ReportRow {
+            name,
+            period: Some(period),
+            count,
+            amount,
+            previous: None,
+        })
+        .collect()
+}
+
+impl Report {
+    pub fn build(records: &[ExportRecord], options: &ReportOptions) -> Self {
+        let value = |t: &Transaction| match options.base_currency {
+            Some(_) => t.converted_amount,
+            None => Some(t.amount),
+        };
+
+        let first_month = options
+            .today
+            .with_day(1)
+            .and_then(|d| d.checked_sub_months(Months::new(options.months.saturating_sub(1))))
+            .unwrap_or(options.today);
+        let trend_start = options.today - Duration::days(options.trend_days);
+        let previous_start = trend_start - Duration::days(options.trend_days);
+
+        let mut outstanding = Totals::new();
+        let mut by_card = Totals::new();
+        let mut by_category = Totals::new();
+        let mut merchants: HashMap<String, (usize, Decimal)> = HashMap::new();
+        let mut trend: BTreeMap<String, (usize, Decimal, Decimal)> = BTreeMap::new();
+        let mut unconverted = 0;
+
+        for record in records {
+            let t = &record.transaction;
+            if t.type_ != TransactionType::Debit {
+                continue;
+            }
+            let Some(amount) = value(t) else {
+                unconverted += 1;
+                continue;
+            };
+            let spend = -amount;
+            let date = t.timestamp.date_naive();
+
+            if t.matched_id.is_none() {
+                let age = (options.today - date).num_days();
+                let (bucket, _) = AGE_BUCKETS
+                    .iter()
+                    .find(|(_, oldest)| age <= *oldest)
+                    .expect("the last bucket has no limit");
+                add(&mut outstanding, &record.card, bucket, spend);
+            }
+
+            if date >= first_month && date <= options.today {
+                let month = date.format("%Y-%m").to_string();
+                add(&mut by_card, &record.card, &month, spend);
+                let category = t.category.as_deref().unwrap_or(UNCATEGORISED);
+                add(&mut by_category, category, &month, spend);
+
+                let merchant = t.merchant.as_deref().unwrap_or(&t.description);
+                let total = merchants.entry(merchant.to_string()).or_default();
+                total.0 += 1;
+                total.1 += spend;
+            }
+
+            for card in [record.card.as_str(), ALL_CARDS] {
+                if date > trend_start && date <= options.today {
+                    let total = trend.entry(card.to_string()).or_default();
+                    total.0 += 1;
+                    total.1 += spend;
+                } else if date > previous_start && date <= trend_start {
+                    trend.entry(card.to_string()).or_default().2 += spend;
+                }
+            }
+        }
+
+        let mut merchants: Vec<ReportRow> = merchants
+            .into_iter()
+            .map(|(name, (count, amount))| ReportRow {
+                name,
+                period: None,
+                count,
+                amount,
+                previous: None,
+            })
+            .collect();
+        merchants.sort_by(|a, b| b.amount.cmp(&a.amount).then_with(|| a.name.cmp(&b.name)));
+        merchants.truncate(options.top_merchants);
+
+        // Keep the total last, after the cards
+        let mut trend: Vec<ReportRow> = trend
+            .into_iter()
+            .map(|(name, (count, amount, previous))| ReportRow {
+                name,
+                period: None,
+                count,
+                amount,
+                previous: Some(previous),
+            })
+            .collect();
+        trend.sort_by_key(|row| row.name == ALL_CARDS);
+
+        // Sort outstanding debits by the order of the buckets, rather than their labels
+        let mut outstanding = rows(outstanding);
+        outstanding.sort_by_key(|row| {
+            let bucket = AGE_BUCKETS
+                .iter()
+                .position(|(label, _)| row.period.as_deref() == Some(label));
+            (row.name.clone(), bucket)
+        });
+
+        Self {
+            currency: options.base_currency.clone(),
+            outstanding,
+            monthly_by_card: rows(by_card),
+            monthly_by_category: rows(by_category),
+            merchants,
+            trend,
+            unconverted,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use super::*;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    pub(crate) fn mock_options() -> ReportOptions {
+        ReportOptions {
+            today: NaiveDate::from_ymd_opt(2025, 3, 31).unwrap(),
+            months: 2,
+            trend_days: 30,
+            top_merchants: 2,
+            base_currency: None,
+        }
+    }
+
+    pub(crate) fn mock_records() -> Vec<ExportRecord> {
+        let record = |card: &str, transaction| ExportRecord {
+            card: card.to_string(),
+            account: String::new(),
+            transaction,
+        };
+        let debit = |id, amount, month, day, merchant: &str, category: Option<&str>| Transaction {
+            merchant: Some(merchant.to_string()),
+            category: category.map(str::to_string),
+            ..mock_transaction(
+                id,
+                amount,
+                TransactionType::Debit,
+                mock_datetime(2025, month, day),
+            )
+        };
+
+        vec![
+            record(
+                "Amex",
+                Transaction {
+                    matched_id: Some("tx_payment".to_string()),
+                    ..debit("tx_1", dec!(-100.00), 1, 10, "Tesco", Some("Groceries"))
+                },
+            ),
+            record(
+                "Amex",
+                debit("tx_2", dec!(-40.00), 2, 20, "Tesco", Some("Groceries")),
+            ),
+            record("Amex", debit("tx_3", dec!(-25.00), 3, 20, "Amazon", None)),
+            record(
+                "Visa",
+                debit("tx_4", dec!(-10.00), 3, 25, "Pret", Some("Eating out")),
+            ),
+            record(
+                "Amex",
+                Transaction {
+                    matched_id: Some("tx_1".to_string()),
+                    ..mock_transaction(
+                        "tx_payment",
+                        dec!(100.00),
+                        TransactionType::Credit,
+                        mock_datetime(2025, 1, 30),
+                    )
+                },
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_helpers::{mock_options, mock_records};
+    use super::*;
+    use rust_decimal::prelude::dec;
+
+    fn row(name: &str, period: Option<&str>, count: usize, amount: Decimal) -> ReportRow {
+        ReportRow {
+            name: name.to_string(),
+            period: period.map(str::to_string),
+            count,
+            amount,
+            previous: None,
+        }
+    }
+
+    #[test]
+    fn test_build() {
+        let report = Report::build(&mock_records(), &mock_options());
+
+        assert_eq!(
+            report.outstanding,
+            vec![
+                row("Amex", Some("0-30 days"), 1, dec!(25.00)),
+                row("Amex", Some("31-60 days"), 1, dec!(40.00)),
+                row("Visa", Some("0-30 days"), 1, dec!(10.00)),
+            ]
+        );
+        assert_eq!(
+            report.monthly_by_card,
+            vec![
+                row("Amex", Some("2025-02"), 1, dec!(40.00)),
+                row("Amex", Some("2025-03"), 1, dec!(25.00)),
+                row("Visa", Some("2025-03"), 1, dec!(10.00)),
+            ],
+            "months before the report period should be left out"
+        );
+        assert_eq!(
+            report.monthly_by_category,
+            vec![
+                row("Eating out", Some("2025-03"), 1, dec!(10.00)),
+                row("Groceries", Some("2025-02"), 1, dec!(40.00)),
+                row("Uncategorised", Some("2025-03"), 1, dec!(25.00)),
+            ]
+        );
+        assert_eq!(
+            report.merchants,
+            vec![
+                row("Tesco", None, 1, dec!(40.00)),
+                row("Amazon", None, 1, dec!(25.00)),
+            ]
+        );
+
+        let trend: Vec<_> = report
+            .trend
+            .iter()
+            .map(|r| (r.name.as_str(), r.amount, r.previous))
+            .collect();
+        assert_eq!(
+            trend,
+            vec![
+                ("Amex", dec!(25.00), Some(dec!(40.00))),
+                ("Visa", dec!(10.00), Some(dec!(0))),
+                ("All cards", dec!(35.00), Some(dec!(40.00))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_in_base_currency() {
+        let mut records = mock_records();
+        records[2].transaction.converted_amount = Some(dec!(-20.00));
+        let options = ReportOptions {
+            base_currency: Some("GBP".to_string()),
+            ..mock_options()
+        };
+
+        let report = Report::build(&records, &options);
+        assert_eq!(report.currency.as_deref(), Some("GBP"));
+        assert_eq!(report.unconverted, 3);
+        assert_eq!(
+            report.monthly_by_card,
+            vec![row("Amex", Some("2025-03"), 1, dec!(20.00))]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use super::{Report, ReportFormat, ReportRow};
+use crate::error::{AppError, Result};
+use rust_decimal::Decimal;
+use std::io::Write;
+
+/// A titled list of rows, with the headers to show them under in a table.
+struct Section<'a> {
+    key: &'static str,
+    title: &'static str,
+    name_header: &'static str,
+    period_header: Option<&'static str>,
+    rows: &'a [ReportRow],
+}
+
+fn sections(report: &Report) -> Vec<Section<'_>> {
+    vec![
+        Section {
+            key: "outstanding",
+            title: "Outstanding debits",
+            name_header: "Card",
+            period_header: Some("Age"),
+            rows: &report.outstanding,
+        },
+        Section {
+            key: "monthly_by_card",
+            title: "Monthly spend by card",
+            name_header: "Card",
+            period_header: Some("Month"),
+            rows: &report.monthly_by_card,
+        },
+        Section {
+            key: "monthly_by_category",
+            title: "Monthly spend by category",
+            name_header: "Category",
+            period_header: Some("Month"),
+            rows: &report.monthly_by_category,
+        },
+        Section {
+            key: "merchants",
+            title: "Largest merchants",
+            name_header: "Merchant",
+            period_header: None,
+            rows: &report.merchants,
+        },
+        Section {
+            key: "trend",
+            title: "Trend against the previous period",
+            name_header: "Card",
+            period_header: None,
+            rows: &report.trend,
+        },
+    ]
+}
+
+/// Write a report in the given format.
+pub fn write_report(report: &Report, format: ReportFormat, writer: &mut impl Write) -> Result<()> {
+    match format {
+        ReportFormat::Table => write_table(report, writer),
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, report)?;
+            writeln!(writer)?;
+            Ok(())
+        }
+        ReportFormat::Csv => write_csv(report, writer),
+    }
+}
+
+/// Percentage change from the previous amount, if there was one.
+fn change(row: &ReportRow) -> Option<Decimal> {
+    let previous = row.previous.filter(|p| !p.is_zero())?;
+    Some(((row.amount - previous) / previous * Decimal::ONE_HUNDRED).round_dp(1))
+}
+
+fn write_table(report: &Report, writer: &mut impl Write) -> Result<()> {
+    let amount_header = match &report.currency {
+        Some(currency) => format!("Amount ({})", currency),
+        None => "Amount".to_string(),
+    };
+
+    for (idx, section) in sections(report).iter().enumerate() {
+        if idx > 0 {
+            writeln!(writer)?;
+        }
+        writeln!(writer, "{}", section.title)?;
+
+        let is_trend = section.rows.iter().any(|row| row.previous.is_some());
+        let mut headers = vec![section.name_header.to_string()];
+        headers.extend(section.period_header.map(str::to_string));
+        headers.extend(["Count".to_string(), amount_header.clone()]);
+        if is_trend {
+            headers.extend(["Previous".to_string(), "Change".to_string()]);
+        }
+
+        let rows: Vec<Vec<String>> = section
+            .rows
+            .iter()
+            .map(|row| {
+                let mut cells = vec![row.name.clone()];
+                if section.period_header.is_some() {
+                    cells.push(row.period.clone().unwrap_or_default());
+                }
+                cells.extend([row.count.to_string(), format!("{:.2}", row.amount)]);
+                if is_trend {
+                    cells.push(format!("{:.2}", row.previous.unwrap_or_default()));
+                    cells.push(change(row).map_or("-".to_string(), |c| format!("{:+}%", c)));
+                }
+                cells
+            })
+            .collect();
+
+        // Text columns are left aligned and numbers right aligned
+        let text_columns = 1 + usize::from(section.period_header.is_some());
+        let widths: Vec<usize> = (0..headers.len())
+            .map(|col| {
+                rows.iter()
+                    .map(|cells| cells[col].chars().count())
+                    .chain([headers[col].chars().count()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        for cells in std::iter::once(&headers).chain(&rows) {
+            let line: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(col, (cell, width))| match col < text_columns {
+                    true => format!("{:<width$}", cell),
+                    false => format!("{:>width$}", cell),
+                })
+                .collect();
+            writeln!(writer, "{}", line.join("  ").trim_end())?;
+        }
+        if rows.is_empty() {
+            writeln!(writer, "(none)")?;
+        }
+    }
+
+    if report.unconverted > 0 {
+        writeln!(
+            writer,
+            "\n{} transactions were left out because they have no converted amount",
+            report.unconverted
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_csv(report: &Report, writer: &mut impl Write) -> Result<()> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    let to_error = |e: csv::Error| AppError::Other(e.into());
+
+    csv_writer
+        .write_record(["Section", "Name", "Period", "Count", "Amount", "Previous"])
+        .map_err(to_error)?;
+    for section in sections(report) {
+        for row in section.rows {
+            csv_writer
+                .write_record([
+                    section.key.to_string(),
+                    row.name.clone(),
+                    row.period.clone().unwrap_or_default(),
+                    row.count.to_string(),
+                    row.amount.to_string(),
+                    row.previous.map(|p| p.to_string()).unwrap_or_default(),
+                ])
+                .map_err(to_error)?;
+        }
+    }
+
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::test_helpers::{mock_options, mock_records};
+
+    fn write(format: ReportFormat) -> String {
+        let report = Report::build(&mock_records(), &mock_options());
+        let mut output = Vec::new();
+        write_report(&report, format, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_write_table() {
+        let output = write(ReportFormat::Table);
+
+        assert!(
+            output.starts_with(
+                "Outstanding debits\n\
+                 Card  Age         Count  Amount\n\
+                 Amex  0-30 days       1   25.00\n\
+                 Amex  31-60 days      1   40.00\n\
+                 Visa  0-30 days       1   10.00\n"
+            ),
+            "{}",
+            output
+        );
+        assert!(
+            output.ends_with(
+                "Trend against the previous period\n\
+                 Card       Count  Amount  Previous  Change\n\
+                 Amex           1   25.00     40.00  -37.5%\n\
+                 Visa           1   10.00      0.00       -\n\
+                 All cards      2   35.00     40.00  -12.5%\n"
+            ),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let output = write(ReportFormat::Csv);
+        let mut lines = output.lines();
+
+        assert_eq!(
+            lines.next(),
+            Some("Section,Name,Period,Count,Amount,Previous")
+        );
+        assert_eq!(lines.next(), Some("outstanding,Amex,0-30 days,1,25.00,"));
+        assert_eq!(
+            output.lines().last(),
+            Some("trend,All cards,,2,35.00,40.00")
+        );
+    }
+
+    #[test]
+    fn test_write_json() {
+        let output = write(ReportFormat::Json);
+        let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(
+            json["merchants"][0],
+            serde_json::json!({"name": "Tesco", "count": 1, "amount": "40.00"})
+        );
+        assert!(json.get("currency").is_none());
+    }
+}
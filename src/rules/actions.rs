@@ -0,0 +1,284 @@
+use crate::error::Result;
+use crate::models::Transaction;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// A change made to a sheet by a rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    Comment {
+        transaction_id: String,
+        comment: String,
+    },
+    Match {
+        debit_id: String,
+        credit_id: String,
+    },
+}
+
+impl Action {
+    /// Undo the action, if the transactions haven't been changed since. Returns whether it was
+    /// undone.
+    pub fn revert(&self, transactions: &mut [Transaction]) -> bool {
+        let find =
+            |transactions: &[Transaction], id: &str| transactions.iter().position(|t| t.id == id);
+
+        match self {
+            Action::Comment {
+                transaction_id,
+                comment,
+            } => {
+                let Some(idx) = find(transactions, transaction_id) else {
+                    return false;
+                };
+                if transactions[idx].comments.as_ref() != Some(comment) {
+                    return false;
+                }
+                transactions[idx].comments = None;
+            }
+            Action::Match {
+                debit_id,
+                credit_id,
+            } => {
+                let (Some(debit), Some(credit)) =
+                    (find(transactions, debit_id), find(transactions, credit_id))
+                else {
+                    return false;
+                };
+                if transactions[debit].matched_id.as_ref() != Some(credit_id)
+                    || transactions[credit].matched_id.as_ref() != Some(debit_id)
+                {
+                    return false;
+                }
+                for idx in [debit, credit] {
+                    transactions[idx].matched_id = None;
+                    transactions[idx].match_confidence = None;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// An action along with when and why it was made.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionRecord {
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    /// Sheet tab of the transactions
+    pub sheet: String,
+    /// Name of the rule that made the change
+    pub rule: String,
+    #[serde(flatten)]
+    pub action: Action,
+    #[serde(default)]
+    pub reverted: bool,
+}
+
+/// Record of every change made by rules, one JSON object per line, so that they can be
+/// reverted.
+#[derive(Debug, Clone, Default)]
+pub struct ActionLog {
+    path: Option<PathBuf>,
+    records: Vec<ActionRecord>,
+}
+
+impl ActionLog {
+    /// Load the log from a file, which is empty if the file doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        let records = match path.exists() {
+            true => fs::read_to_string(path)?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<std::result::Result<_, _>>()?,
+            false => Vec::new(),
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            records,
+        })
+    }
+
+    /// Write the log back to the file it was loaded from.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut contents = String::new();
+        for record in &self.records {
+            contents.push_str(&serde_json::to_string(record)?);
+            contents.push('\n');
+        }
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+
+    pub fn records(&self) -> &[ActionRecord] {
+        &self.records
+    }
+
+    pub fn record(&mut self, sheet: &str, rule: &str, action: Action) {
+        let id = self.records.last().map_or(1, |r| r.id + 1);
+        self.records.push(ActionRecord {
+            id,
+            timestamp: Utc::now(),
+            sheet: sheet.to_string(),
+            rule: rule.to_string(),
+            action,
+            reverted: false,
+        });
+    }
+
+    /// Whether a rule's match between two transactions has been reverted, so that it shouldn't
+    /// be made again.
+    pub fn is_reverted_match(&self, sheet: &str, debit_id: &str, credit_id: &str) -> bool {
+        self.records.iter().any(|record| {
+            record.reverted
+                && record.sheet == sheet
+                && record.action
+                    == Action::Match {
+                        debit_id: debit_id.to_string(),
+                        credit_id: credit_id.to_string(),
+                    }
+        })
+    }
+
+    /// Revert the selected actions for a sheet, newest first, and mark them as reverted. Actions
+    /// whose transactions have been changed since are left alone, but still marked.
+    pub fn revert(
+        &mut self,
+        sheet: &str,
+        ids: &[u64],
+        transactions: &mut [Transaction],
+    ) -> Vec<u64> {
+        let mut reverted = Vec::new();
+
+        for record in self
+            .records
+            .iter_mut()
+            .rev()
+            .filter(|r| r.sheet == sheet && !r.reverted && ids.contains(&r.id))
+        {
+            match record.action.revert(transactions) {
+                true => reverted.push(record.id),
+                false => warn!(
+                    id = record.id,
+                    rule = record.rule,
+                    "Transactions have changed since the action, so it was left alone"
+                ),
+            }
+            record.reverted = true;
+        }
+
+        reverted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    fn mock_transactions() -> Vec<Transaction> {
+        let debit = Transaction {
+            matched_id: Some("tx_2".to_string()),
+            comments: Some("Split with Sam".to_string()),
+            ..mock_transaction(
+                "tx_1",
+                dec!(-10.00),
+                TransactionType::Debit,
+                mock_datetime(2025, 1, 1),
+            )
+        };
+        let credit = Transaction {
+            matched_id: Some("tx_1".to_string()),
+            ..mock_transaction(
+                "tx_2",
+                dec!(9.99),
+                TransactionType::Credit,
+                mock_datetime(2025, 1, 2),
+            )
+        };
+        vec![debit, credit]
+    }
+
+    fn mock_log() -> ActionLog {
+        let mut log = ActionLog::default();
+        log.record(
+            "Amex",
+            "sam",
+            Action::Comment {
+                transaction_id: "tx_1".to_string(),
+                comment: "Split with Sam".to_string(),
+            },
+        );
+        log.record(
+            "Amex",
+            "refunds",
+            Action::Match {
+                debit_id: "tx_1".to_string(),
+                credit_id: "tx_2".to_string(),
+            },
+        );
+        log
+    }
+
+    #[test]
+    fn test_revert() {
+        let mut log = mock_log();
+        let mut transactions = mock_transactions();
+        assert!(!log.is_reverted_match("Amex", "tx_1", "tx_2"));
+
+        let reverted = log.revert("Amex", &[1, 2], &mut transactions);
+        assert_eq!(reverted, vec![2, 1]);
+        assert_eq!(transactions[0].comments, None);
+        assert_eq!(transactions[0].matched_id, None);
+        assert_eq!(transactions[1].matched_id, None);
+        assert!(log.is_reverted_match("Amex", "tx_1", "tx_2"));
+        assert!(!log.is_reverted_match("Visa", "tx_1", "tx_2"));
+
+        let reverted = log.revert("Amex", &[1, 2], &mut transactions);
+        assert!(reverted.is_empty(), "actions should only be reverted once");
+    }
+
+    #[test]
+    fn test_revert_skips_changed_transactions() {
+        let mut log = mock_log();
+        let mut transactions = mock_transactions();
+        transactions[0].comments = Some("Edited".to_string());
+
+        let reverted = log.revert("Amex", &[1], &mut transactions);
+        assert!(reverted.is_empty());
+        assert_eq!(transactions[0].comments.as_deref(), Some("Edited"));
+        assert!(log.records()[0].reverted);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!(
+            "credit-card-tracker-actions-{}.jsonl",
+            std::process::id()
+        ));
+        let mut log = ActionLog::load(&path).unwrap();
+        assert!(log.records().is_empty());
+
+        log.records = mock_log().records;
+        log.save().unwrap();
+
+        let loaded = ActionLog::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.records(), log.records());
+        assert_eq!(loaded.records()[1].id, 2);
+    }
+}
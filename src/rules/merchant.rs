@@ -0,0 +1,160 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::sync::LazyLock;
+
+/// Payment processors that prefix the merchant name, such as `SQ *` for Square.
+static PROCESSOR_PREFIX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(SQ|SP|SUMUP|ZTL|IZ|IZETTLE|CRV|TST|PAYPAL|PP|CKO|GOOGLE|LS)\s*\*\s*")
+        .expect("processor prefix pattern should be valid")
+});
+
+/// Merchants whose descriptions vary too much to clean up, as (pattern, name).
+const BUILTIN_MERCHANTS: &[(&str, &str)] = &[
+    (r"(?i)^(AMZN|AMAZON)", "Amazon"),
+    (r"(?i)^UBER\s*\*?\s*EATS", "Uber Eats"),
+    (r"(?i)^UBER", "Uber"),
+    (r"(?i)^(TFL|TRANSPORT FOR LONDON)", "TfL"),
+    (r"(?i)^NETFLIX", "Netflix"),
+    (r"(?i)^SPOTIFY", "Spotify"),
+    (r"(?i)^APPLE\.COM", "Apple"),
+    (r"(?i)^DELIVEROO", "Deliveroo"),
+];
+
+static BUILTIN_MERCHANT_PATTERNS: LazyLock<Vec<MerchantRule>> = LazyLock::new(|| {
+    BUILTIN_MERCHANTS
+        .iter()
+        .map(|(pattern, name)| MerchantRule {
+            pattern: Regex::new(pattern).expect("built-in merchant pattern should be valid"),
+            name: name.to_string(),
+        })
+        .collect()
+});
+
+/// Country codes that card networks append to the location.
+const COUNTRY_CODES: &[&str] = &["GB", "GBR", "UK", "IE", "IRL", "US", "USA"];
+
+/// Names a merchant when its pattern matches the description.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MerchantRule {
+    /// Regular expression that must match somewhere in the description
+    #[serde(deserialize_with = "deserialize_pattern")]
+    pub pattern: Regex,
+    pub name: String,
+}
+
+/// Get the merchant name for a description, from the first matching rule or built-in pattern,
+/// or else by removing processor prefixes, reference codes and locations.
+pub fn normalise_merchant(rules: &[MerchantRule], description: &str) -> Option<String> {
+    let description = PROCESSOR_PREFIX.replace(description.trim(), "");
+
+    if let Some(rule) = rules
+        .iter()
+        .chain(BUILTIN_MERCHANT_PATTERNS.iter())
+        .find(|rule| rule.pattern.is_match(&description))
+    {
+        return Some(rule.name.clone());
+    }
+
+    // Card networks pad the name and location into columns, and references follow a `*`
+    let name = description
+        .split("  ")
+        .next()
+        .unwrap_or_default()
+        .split('*')
+        .next()
+        .unwrap_or_default();
+
+    let mut words: Vec<&str> = name
+        .split_whitespace()
+        .filter(|word| !word.chars().any(|c| c.is_ascii_digit()))
+        .collect();
+    while words
+        .last()
+        .is_some_and(|word| COUNTRY_CODES.contains(&word.to_ascii_uppercase().as_str()))
+    {
+        words.pop();
+    }
+
+    let name = words
+        .iter()
+        .map(|word| title_case(word))
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!name.is_empty()).then_some(name)
+}
+
+/// Capitalise the first letter of a word that is all upper or lower case, leaving mixed case
+/// words such as "McDonald's" alone.
+fn title_case(word: &str) -> String {
+    let letters = || word.chars().filter(|c| c.is_alphabetic());
+    let is_mixed_case = letters().any(char::is_uppercase) && letters().any(char::is_lowercase);
+    if is_mixed_case {
+        return word.to_string();
+    }
+
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| {
+            first
+                .to_uppercase()
+                .chain(chars.flat_map(char::to_lowercase))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn deserialize_pattern<'de, D>(deserializer: D) -> std::result::Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pattern: String = Deserialize::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalise_builtin() {
+        let cases = [
+            ("AMZNMKTPLACE*AB12CD34 AMAZON.CO.UK", "Amazon"),
+            ("UBER *EATS HELP.UBER.COM", "Uber Eats"),
+            ("SQ *UBER TRIP", "Uber"),
+            ("TESCO STORES 3297    LONDON      GB", "Tesco Stores"),
+            ("SQ *THE COFFEE HOUSE", "The Coffee House"),
+            ("PAYPAL *EBAY 4029357733", "Ebay"),
+            ("McDonald's 1234 UK", "McDonald's"),
+            ("ZARA.COM*R1234 GB", "Zara.com"),
+        ];
+
+        for (description, expected) in cases {
+            assert_eq!(
+                normalise_merchant(&[], description).as_deref(),
+                Some(expected),
+                "{}",
+                description
+            );
+        }
+    }
+
+    #[test]
+    fn test_normalise_user_rules_first() {
+        let rules = [MerchantRule {
+            pattern: Regex::new("(?i)amzn").unwrap(),
+            name: "Amazon Marketplace".to_string(),
+        }];
+        assert_eq!(
+            normalise_merchant(&rules, "AMZNMKTPLACE*AB12CD34").as_deref(),
+            Some("Amazon Marketplace")
+        );
+    }
+
+    #[test]
+    fn test_normalise_nothing_left() {
+        assert_eq!(normalise_merchant(&[], "123456 GB"), None);
+        assert_eq!(normalise_merchant(&[], ""), None);
+    }
+}
@@ -0,0 +1,431 @@
+mod actions;
+mod merchant;
+mod pair;
+
+use crate::error::{AppError, Result};
+use crate::models::{Card, Transaction};
+use regex::Regex;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+pub use actions::{Action, ActionLog};
+pub use merchant::MerchantRule;
+pub use pair::PairRule;
+
+/// Rules for categorising and commenting on transactions, where the first rule to match a
+/// transaction sets its category and tags, and for naming merchants and pairing transactions.
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+    merchants: Vec<MerchantRule>,
+    pairs: Vec<PairRule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default, rename = "rule")]
+    rules: Vec<Rule>,
+    #[serde(default, rename = "merchant")]
+    merchants: Vec<MerchantRule>,
+    #[serde(default, rename = "pair")]
+    pairs: Vec<PairRule>,
+}
+
+/// A rule that categorises or comments on transactions. Every matcher that is set must match
+/// for the rule to apply.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    /// Regular expression that must match somewhere in the description
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub description: Option<Regex>,
+    /// Regular expression that must match somewhere in the normalised merchant name
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub merchant: Option<Regex>,
+    /// Smallest amount, inclusive, where charges are negative
+    pub min_amount: Option<Decimal>,
+    /// Largest amount, inclusive, where charges are negative
+    pub max_amount: Option<Decimal>,
+    /// Provider ID or name, ignoring case
+    pub provider: Option<String>,
+    /// Card ID or name, ignoring case
+    pub card: Option<String>,
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Comment for new transactions that don't already have one
+    pub comment: Option<String>,
+}
+
+impl Rule {
+    fn matches(&self, card: &Card, transaction: &Transaction) -> bool {
+        let matches_name = |expected: &Option<String>, id: &str, name: &str| {
+            expected
+                .as_deref()
+                .is_none_or(|e| e.eq_ignore_ascii_case(id) || e.eq_ignore_ascii_case(name))
+        };
+
+        self.description
+            .as_ref()
+            .is_none_or(|re| re.is_match(&transaction.description))
+            && self
+                .merchant
+                .as_ref()
+                .is_none_or(|re| re.is_match(transaction.merchant.as_deref().unwrap_or_default()))
+            && self.min_amount.is_none_or(|min| transaction.amount >= min)
+            && self.max_amount.is_none_or(|max| transaction.amount <= max)
+            && matches_name(&self.provider, &card.provider.id, &card.provider.name)
+            && matches_name(&self.card, &card.id, &card.name)
+    }
+
+    /// Tags as written to the sheet.
+    fn tags_value(&self) -> Option<String> {
+        (!self.tags.is_empty()).then(|| self.tags.join(", "))
+    }
+}
+
+impl Rules {
+    /// Load rules from a TOML file of `[[rule]]` tables. There are no rules if the file
+    /// doesn't exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents)
+            .map_err(|e| AppError::Config(format!("Invalid rules in {:?}: {}", path, e)))
+    }
+
+    pub(crate) fn parse(contents: &str) -> std::result::Result<Self, String> {
+        let file: RulesFile = toml::from_str(contents).map_err(|e| e.to_string())?;
+
+        let mut names = HashSet::new();
+        let all_names = file
+            .rules
+            .iter()
+            .map(|rule| &rule.name)
+            .chain(file.pairs.iter().map(|pair| &pair.name));
+        for name in all_names {
+            if name.is_empty() {
+                return Err("rule names must not be empty".to_string());
+            }
+            if !names.insert(name.as_str()) {
+                return Err(format!("rule {:?} is defined more than once", name));
+            }
+        }
+
+        for rule in &file.rules {
+            if rule.category.is_none() && rule.comment.is_none() {
+                return Err(format!(
+                    "rule {:?} must set a category or a comment",
+                    rule.name
+                ));
+            }
+            if rule.category.is_none() && !rule.tags.is_empty() {
+                return Err(format!("rule {:?} has tags but no category", rule.name));
+            }
+            if let (Some(min), Some(max)) = (rule.min_amount, rule.max_amount)
+                && min > max
+            {
+                return Err(format!(
+                    "rule {:?} has min_amount greater than max_amount",
+                    rule.name
+                ));
+            }
+        }
+
+        for pair in &file.pairs {
+            pair.validate()?;
+        }
+
+        Ok(Self {
+            rules: file.rules,
+            merchants: file.merchants,
+            pairs: file.pairs,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.pairs.is_empty()
+    }
+
+    pub fn pairs(&self) -> &[PairRule] {
+        &self.pairs
+    }
+
+    /// Get the merchant name for a transaction's description.
+    pub fn merchant(&self, description: &str) -> Option<String> {
+        merchant::normalise_merchant(&self.merchants, description)
+    }
+
+    /// Find the first rule with a category that matches a transaction.
+    pub fn find(&self, card: &Card, transaction: &Transaction) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.category.is_some())
+            .find(|rule| rule.matches(card, transaction))
+    }
+
+    /// Find the first rule with a comment that matches a transaction.
+    pub fn find_comment(&self, card: &Card, transaction: &Transaction) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.comment.is_some())
+            .find(|rule| rule.matches(card, transaction))
+    }
+
+    /// Set the category and tags from the first matching rule, unless they were typed by hand.
+    ///
+    /// Values are treated as typed by hand if there is no rule name alongside them, or if they
+    /// differ from what the named rule sets.
+    pub fn apply(&self, card: &Card, transaction: &mut Transaction) {
+        if self.is_manual(transaction) {
+            transaction.rule = None;
+            return;
+        }
+
+        match self.find(card, transaction) {
+            Some(rule) => {
+                transaction.category = rule.category.clone();
+                transaction.tags = rule.tags_value();
+                transaction.rule = Some(rule.name.clone());
+            }
+            None => {
+                transaction.category = None;
+                transaction.tags = None;
+                transaction.rule = None;
+            }
+        }
+    }
+
+    fn is_manual(&self, transaction: &Transaction) -> bool {
+        let Some(name) = &transaction.rule else {
+            return transaction.category.is_some() || transaction.tags.is_some();
+        };
+
+        // Values from a rule that has since been removed can be replaced
+        self.rules
+            .iter()
+            .find(|rule| &rule.name == name)
+            .is_some_and(|rule| {
+                transaction.category != rule.category || transaction.tags != rule.tags_value()
+            })
+    }
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> std::result::Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pattern: Option<String> = Deserialize::deserialize(deserializer)?;
+    pattern
+        .map(|p| Regex::new(&p))
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::card::test_helpers::mock_card;
+    use crate::models::transaction::TransactionType;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    fn mock_rules() -> Rules {
+        Rules::parse(
+            r#"
+            [[rule]]
+            name = "groceries"
+            description = "(?i)tesco|sainsbury"
+            max_amount = 0
+            category = "Groceries"
+            tags = ["food", "weekly"]
+
+            [[rule]]
+            name = "refunds"
+            min_amount = 0.01
+            card = "ACC_123"
+            category = "Refunds"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn mock_debit(description: &str) -> Transaction {
+        Transaction {
+            description: description.to_string(),
+            ..mock_transaction(
+                "tx_1",
+                dec!(-12.34),
+                TransactionType::Debit,
+                mock_datetime(2024, 11, 23),
+            )
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        let rules = mock_rules();
+        let card = mock_card();
+
+        let rule = rules.find(&card, &mock_debit("TESCO STORES 1234")).unwrap();
+        assert_eq!(rule.name, "groceries");
+        assert!(rules.find(&card, &mock_debit("Cafe")).is_none());
+
+        let credit = Transaction {
+            amount: dec!(12.34),
+            ..mock_debit("TESCO STORES 1234")
+        };
+        let rule = rules.find(&card, &credit).unwrap();
+        assert_eq!(rule.name, "refunds", "amount range should exclude credits");
+
+        let other_card = Card {
+            id: "acc_456".to_string(),
+            ..mock_card()
+        };
+        assert!(rules.find(&other_card, &credit).is_none());
+    }
+
+    #[test]
+    fn test_apply_sets_category() {
+        let rules = mock_rules();
+        let mut transaction = mock_debit("Sainsbury's");
+        rules.apply(&mock_card(), &mut transaction);
+
+        assert_eq!(transaction.category.as_deref(), Some("Groceries"));
+        assert_eq!(transaction.tags.as_deref(), Some("food, weekly"));
+        assert_eq!(transaction.rule.as_deref(), Some("groceries"));
+
+        transaction.description = "Cafe".to_string();
+        rules.apply(&mock_card(), &mut transaction);
+        assert_eq!(
+            transaction.category, None,
+            "values from rules should be re-evaluated"
+        );
+        assert_eq!(transaction.rule, None);
+    }
+
+    #[test]
+    fn test_apply_keeps_manual_values() {
+        let rules = mock_rules();
+
+        let mut typed = Transaction {
+            category: Some("Eating out".to_string()),
+            ..mock_debit("Tesco")
+        };
+        rules.apply(&mock_card(), &mut typed);
+        assert_eq!(typed.category.as_deref(), Some("Eating out"));
+        assert_eq!(typed.rule, None);
+
+        let mut edited = Transaction {
+            category: Some("Household".to_string()),
+            tags: Some("food, weekly".to_string()),
+            rule: Some("groceries".to_string()),
+            ..mock_debit("Tesco")
+        };
+        rules.apply(&mock_card(), &mut edited);
+        assert_eq!(edited.category.as_deref(), Some("Household"));
+        assert_eq!(
+            edited.rule, None,
+            "edited values should no longer be attributed to the rule"
+        );
+
+        let mut removed_rule = Transaction {
+            category: Some("Old".to_string()),
+            rule: Some("old".to_string()),
+            ..mock_debit("Tesco")
+        };
+        rules.apply(&mock_card(), &mut removed_rule);
+        assert_eq!(removed_rule.category.as_deref(), Some("Groceries"));
+    }
+
+    #[test]
+    fn test_find_by_merchant() {
+        let rules = Rules::parse(
+            r#"
+            [[merchant]]
+            pattern = "(?i)^AMZN"
+            name = "Amazon Marketplace"
+
+            [[rule]]
+            name = "amazon"
+            merchant = "^Amazon"
+            category = "Shopping"
+            "#,
+        )
+        .unwrap();
+
+        let mut transaction = mock_debit("AMZNMKTPLACE*AB12CD34 AMAZON.CO.UK");
+        assert!(rules.find(&mock_card(), &transaction).is_none());
+
+        transaction.merchant = rules.merchant(&transaction.description);
+        assert_eq!(transaction.merchant.as_deref(), Some("Amazon Marketplace"));
+        assert_eq!(
+            rules.find(&mock_card(), &transaction).unwrap().name,
+            "amazon"
+        );
+    }
+
+    #[test]
+    fn test_find_comment() {
+        let rules = Rules::parse(
+            r#"
+            [[rule]]
+            name = "netflix"
+            description = "(?i)netflix"
+            comment = "Split with Sam"
+
+            [[rule]]
+            name = "everything"
+            category = "Other"
+            "#,
+        )
+        .unwrap();
+        let card = mock_card();
+        let transaction = mock_debit("NETFLIX.COM");
+
+        assert_eq!(
+            rules.find_comment(&card, &transaction).unwrap().name,
+            "netflix"
+        );
+        assert_eq!(
+            rules.find(&card, &transaction).unwrap().name,
+            "everything",
+            "rules without a category should be skipped when categorising"
+        );
+        assert!(rules.find_comment(&card, &mock_debit("Cafe")).is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(
+            Rules::parse("[[rule]]\nname = \"a\"\ndescription = \"(\"\ncategory = \"A\"").is_err()
+        );
+        assert!(
+            Rules::parse(
+                "[[rule]]\nname = \"a\"\ncategory = \"A\"\n[[rule]]\nname = \"a\"\ncategory = \"B\""
+            )
+            .is_err()
+        );
+        assert!(
+            Rules::parse(
+                "[[rule]]\nname = \"a\"\nmin_amount = 5\nmax_amount = 1\ncategory = \"A\""
+            )
+            .is_err()
+        );
+        assert!(
+            Rules::parse("[[rule]]\nname = \"a\"\ncategory = \"A\"\ncolour = \"red\"").is_err()
+        );
+        assert!(Rules::parse("[[merchant]]\npattern = \"(\"\nname = \"A\"").is_err());
+        assert!(Rules::parse("[[rule]]\nname = \"a\"").is_err());
+        assert!(Rules::parse("[[rule]]\nname = \"a\"\ncomment = \"A\"\ntags = [\"b\"]").is_err());
+        assert!(Rules::parse("").unwrap().is_empty());
+    }
+}
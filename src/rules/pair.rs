@@ -0,0 +1,183 @@
+use super::deserialize_regex;
+use crate::models::Transaction;
+use crate::models::transaction::TransactionType;
+use crate::sync::reconcile::{MatchedPair, match_confidence};
+use chrono::Duration;
+use regex::Regex;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+/// Pairs debits and credits whose descriptions match the given patterns, even if the amounts
+/// differ by up to the tolerance.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PairRule {
+    pub name: String,
+    /// Regular expression that must match somewhere in the debit's description
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub debit: Option<Regex>,
+    /// Regular expression that must match somewhere in the credit's description
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub credit: Option<Regex>,
+    /// Most days between the debit and credit
+    #[serde(default = "default_pair_days")]
+    pub days: i64,
+    /// Largest difference between the debit and credit amounts
+    #[serde(default)]
+    pub tolerance: Decimal,
+}
+
+fn default_pair_days() -> i64 {
+    60
+}
+
+impl PairRule {
+    pub(super) fn validate(&self) -> std::result::Result<(), String> {
+        if self.debit.is_none() || self.credit.is_none() {
+            return Err(format!(
+                "pair {:?} must set both debit and credit patterns",
+                self.name
+            ));
+        }
+        if self.days < 0 || self.tolerance.is_sign_negative() {
+            return Err(format!(
+                "pair {:?} must not have a negative days or tolerance",
+                self.name
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Pair each unmatched debit with the earliest unmatched credit that fits the rule, skipping
+    /// any pairs that `is_excluded` rejects, such as ones that have been reverted.
+    pub fn find_pairs(
+        &self,
+        transactions: &[Transaction],
+        is_excluded: impl Fn(&MatchedPair) -> bool,
+    ) -> Vec<MatchedPair> {
+        let is_match = |pattern: &Option<Regex>, transaction: &Transaction| {
+            pattern
+                .as_ref()
+                .is_some_and(|re| re.is_match(&transaction.description))
+        };
+
+        let mut credits: Vec<&Transaction> = transactions
+            .iter()
+            .filter(|t| t.matched_id.is_none() && t.type_ == TransactionType::Credit)
+            .filter(|t| is_match(&self.credit, t))
+            .collect();
+        credits.sort_by_key(|t| t.timestamp);
+
+        let mut debits: Vec<&Transaction> = transactions
+            .iter()
+            .filter(|t| t.matched_id.is_none() && t.type_ == TransactionType::Debit)
+            .filter(|t| is_match(&self.debit, t))
+            .collect();
+        debits.sort_by_key(|t| t.timestamp);
+
+        let pair = |debit: &Transaction, credit: &Transaction| MatchedPair {
+            debit_id: debit.id.clone(),
+            credit_id: credit.id.clone(),
+            confidence: match_confidence(debit.amount, credit.amount, self.tolerance),
+        };
+
+        let window = Duration::days(self.days);
+        let mut pairs = Vec::new();
+        for debit in debits {
+            let position = credits.iter().position(|credit| {
+                credit.id != debit.id
+                    && (debit.amount + credit.amount).abs() <= self.tolerance
+                    && credit
+                        .timestamp
+                        .signed_duration_since(debit.timestamp)
+                        .abs()
+                        <= window
+                    && !is_excluded(&pair(debit, credit))
+            });
+
+            if let Some(position) = position {
+                let credit = credits.remove(position);
+                pairs.push(pair(debit, credit));
+            }
+        }
+
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    fn mock_rule() -> PairRule {
+        PairRule {
+            name: "flights".to_string(),
+            debit: Regex::new("(?i)airways").ok(),
+            credit: Regex::new("(?i)expenses").ok(),
+            days: 30,
+            tolerance: dec!(1.00),
+        }
+    }
+
+    fn mock(id: &str, description: &str, amount: Decimal, day: u32) -> Transaction {
+        let type_ = match amount.is_sign_negative() {
+            true => TransactionType::Debit,
+            false => TransactionType::Credit,
+        };
+        Transaction {
+            description: description.to_string(),
+            ..mock_transaction(id, amount, type_, mock_datetime(2025, 1, day))
+        }
+    }
+
+    #[test]
+    fn test_find_pairs_within_tolerance() {
+        let transactions = vec![
+            mock("tx_flight", "BRITISH AIRWAYS", dec!(-250.00), 1),
+            mock("tx_other", "TESCO", dec!(249.50), 2),
+            mock("tx_far", "WORK EXPENSES", dec!(248.00), 3),
+            mock("tx_refund", "WORK EXPENSES", dec!(249.50), 10),
+        ];
+
+        let pairs = mock_rule().find_pairs(&transactions, |_| false);
+        assert_eq!(
+            pairs,
+            vec![MatchedPair {
+                debit_id: "tx_flight".to_string(),
+                credit_id: "tx_refund".to_string(),
+                confidence: dec!(0.75),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_pairs_skips_excluded_and_matched() {
+        let mut transactions = vec![
+            mock("tx_flight", "BRITISH AIRWAYS", dec!(-250.00), 1),
+            mock("tx_refund", "WORK EXPENSES", dec!(250.00), 10),
+        ];
+
+        let pairs = mock_rule().find_pairs(&transactions, |pair| pair.credit_id == "tx_refund");
+        assert!(pairs.is_empty());
+
+        transactions[1].matched_id = Some("tx_other".to_string());
+        let pairs = mock_rule().find_pairs(&transactions, |_| false);
+        assert!(pairs.is_empty());
+    }
+
+    #[test]
+    fn test_find_pairs_outside_window() {
+        let transactions = vec![
+            mock("tx_flight", "BRITISH AIRWAYS", dec!(-250.00), 1),
+            mock("tx_refund", "WORK EXPENSES", dec!(250.00), 31),
+        ];
+        let rule = PairRule {
+            days: 29,
+            ..mock_rule()
+        };
+        assert!(rule.find_pairs(&transactions, |_| false).is_empty());
+    }
+}
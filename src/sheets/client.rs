@@ -1,10 +1,16 @@
-use super::SheetOperations;
 use super::formatting::{bold_header_rule, freeze_header_rule, highlight_rules, protection_rules};
-use crate::config::GoogleConfig;
+use super::schema::{is_managed_sheet, migrate_rows, schema_version_rules, sheet_schema_version};
+use super::snapshots::Snapshots;
+use super::{SheetOperations, sheet_title};
+use crate::config::{Config, GoogleConfig, HighlightColours};
 use crate::error::{AppError, Result};
-use crate::models::{FromSheetRows, ToSheetRows, Transaction};
+use crate::metrics;
+use crate::models::audit::{AuditEntry, HISTORY_HEADERS};
+use crate::models::{SheetData, ToSheetRows, Transaction};
 use crate::sheets::auth::create_and_verify_authenticator;
+use crate::sync::archive::parse_archive_tab_name;
 use async_trait::async_trait;
+use chrono::Utc;
 use google_drive3::api::DriveHub;
 use google_sheets4::api::{
     AddSheetRequest, BatchUpdateSpreadsheetRequest, ClearValuesRequest, Request, Scope, Sheet,
@@ -13,6 +19,8 @@ use google_sheets4::api::{
 use hyper_rustls::HttpsConnector;
 use hyper_util::client::legacy::Client;
 use hyper_util::client::legacy::connect::HttpConnector;
+use std::collections::HashMap;
+use std::sync::Mutex;
 use tracing::{debug, instrument};
 
 // Access to files created or opened by the app
@@ -21,10 +29,50 @@ pub(crate) const AUTH_SCOPE: Scope = Scope::DriveFile;
 // Name of the spreadsheet file in Google Drive.
 const SPREADSHEET_NAME: &str = "Credit Card Transactions (credit-card-tracker)";
 
+// Number of search results to fetch, to detect when more than one spreadsheet has the same name.
+const SEARCH_PAGE_SIZE: i32 = 10;
+
+/// Name of the spreadsheet from the config, or the default name for the profile so that each
+/// profile has a separate spreadsheet.
+fn spreadsheet_name(config: &GoogleConfig, profile: Option<&str>) -> String {
+    match (&config.spreadsheet_name, profile) {
+        (Some(name), _) => name.clone(),
+        (None, Some(profile)) => format!("{} [{}]", SPREADSHEET_NAME, profile),
+        (None, None) => SPREADSHEET_NAME.to_string(),
+    }
+}
+
+/// Count a failed Google API request in the `api_errors_total` metric, by status code.
+fn count_api_error(e: &google_sheets4::Error) {
+    let status = match e {
+        google_sheets4::Error::Failure(response) => Some(response.status().as_u16()),
+        google_sheets4::Error::BadRequest(body) => body["error"]["code"]
+            .as_u64()
+            .and_then(|code| u16::try_from(code).ok()),
+        _ => None,
+    };
+    metrics::api_error("google", status);
+}
+
+fn api_error(message: &str, e: google_sheets4::Error) -> AppError {
+    count_api_error(&e);
+    AppError::Sheets(format!("{}: {}", message, e))
+}
+
+fn spreadsheet_url(id: &str) -> String {
+    format!("https://docs.google.com/spreadsheets/d/{}", id)
+}
+
 pub struct SheetsClient {
     hub: Sheets<HttpsConnector<HttpConnector>>,
     spreadsheet_id: String,
     spreadsheet_url: String,
+    snapshots: Option<Snapshots>,
+    /// The last data read from each tab, which is snapshotted when the tab is next written
+    /// instead of reading it again. Only kept if snapshots are enabled.
+    last_read: Mutex<HashMap<String, SheetData>>,
+    /// Client for the spreadsheet that old transactions are archived to, if it's separate
+    archive: Option<Box<SheetsClient>>,
 }
 
 impl SheetsClient {
@@ -46,29 +94,112 @@ impl SheetsClient {
         let drive_hub = DriveHub::new(client, auth);
 
         let (spreadsheet_id, spreadsheet_url) =
-            Self::get_or_create_spreadsheet(&sheets_hub, &drive_hub).await?;
+            Self::get_or_create_spreadsheet(config, &sheets_hub, &drive_hub).await?;
 
         Ok(Self {
             hub: sheets_hub,
             spreadsheet_id,
             spreadsheet_url,
+            snapshots: None,
+            last_read: Mutex::default(),
+            archive: None,
         })
     }
 
+    /// Take a snapshot of each sheet before it's written, if snapshots are enabled.
+    pub fn with_snapshots(self, snapshots: Snapshots) -> Self {
+        Self {
+            snapshots: Some(snapshots).filter(Snapshots::is_enabled),
+            ..self
+        }
+    }
+
+    /// Archive old transactions to a separate spreadsheet, instead of tabs in this one. Call
+    /// after `with_snapshots` so that the archive's tabs are snapshotted too.
+    pub async fn with_archive(self, spreadsheet_id: Option<&str>) -> Result<Self> {
+        let Some(id) = spreadsheet_id else {
+            return Ok(self);
+        };
+
+        let (spreadsheet_id, spreadsheet_url) = Self::open_spreadsheet_by_id(&self.hub, id).await?;
+        let archive = Self {
+            hub: self.hub.clone(),
+            spreadsheet_id,
+            spreadsheet_url,
+            snapshots: self.snapshots.clone(),
+            last_read: Mutex::default(),
+            archive: None,
+        };
+        Ok(Self {
+            archive: Some(Box::new(archive)),
+            ..self
+        })
+    }
+
+    /// Client for the spreadsheet that old transactions are archived to.
+    fn archive_client(&self) -> &SheetsClient {
+        self.archive.as_deref().unwrap_or(self)
+    }
+
     pub fn spreadsheet_url(&self) -> String {
         self.spreadsheet_url.to_string()
     }
 
+    /// URL of the archive spreadsheet, if archive tabs are kept out of the main spreadsheet.
+    pub fn archive_spreadsheet_url(&self) -> Option<String> {
+        self.archive
+            .as_ref()
+            .map(|archive| archive.spreadsheet_url())
+    }
+
     async fn get_or_create_spreadsheet(
+        config: &GoogleConfig,
         sheets: &Sheets<HttpsConnector<HttpConnector>>,
         drive: &DriveHub<HttpsConnector<HttpConnector>>,
     ) -> Result<(String, String)> {
-        if let Some(id) = Self::search_spreadsheet_by_name(drive, SPREADSHEET_NAME).await? {
-            let url = format!("https://docs.google.com/spreadsheets/d/{}", id);
+        if let Some(id) = &config.spreadsheet_id {
+            return Self::open_spreadsheet_by_id(sheets, id).await;
+        }
+
+        let name = spreadsheet_name(config, Config::profile());
+        if let Some(id) = Self::search_spreadsheet_by_name(drive, &name).await? {
+            let url = spreadsheet_url(&id);
             return Ok((id, url));
         }
 
-        Self::create_new_spreadsheet(sheets, SPREADSHEET_NAME).await
+        Self::create_new_spreadsheet(sheets, &name).await
+    }
+
+    #[instrument(name = "Opening spreadsheet", skip(sheets))]
+    async fn open_spreadsheet_by_id(
+        sheets: &Sheets<HttpsConnector<HttpConnector>>,
+        id: &str,
+    ) -> Result<(String, String)> {
+        // The drive.file scope only grants access to files created by the app, or opened with
+        // it through the Google Drive file picker, so explain how to fix it if access is denied.
+        let (_, spreadsheet) = sheets
+            .spreadsheets()
+            .get(id)
+            .include_grid_data(false)
+            .add_scope(AUTH_SCOPE)
+            .doit()
+            .await
+            .map_err(|e| {
+                count_api_error(&e);
+                AppError::Sheets(format!(
+                    "Failed to open spreadsheet {}: {}. credit-card-tracker can only access \
+                     spreadsheets that it created or that were opened with it from Google Drive, \
+                     so check that the ID is correct and the file has been granted to the app, \
+                     or remove google.spreadsheet_id to create a new spreadsheet",
+                    id, e
+                ))
+            })?;
+
+        let url = spreadsheet
+            .spreadsheet_url
+            .unwrap_or_else(|| spreadsheet_url(id));
+
+        Ok((id.to_string(), url))
     }
 
     #[instrument(name = "Finding existing spreadsheet", skip(drive))]
@@ -76,9 +207,10 @@ impl SheetsClient {
         drive: &DriveHub<HttpsConnector<HttpConnector>>,
         name: &str,
     ) -> Result<Option<String>> {
+        // Names are configurable, so escape them for the Drive query syntax
         let query = format!(
             "name='{}' and mimeType='application/vnd.google-apps.spreadsheet' and trashed=false",
-            name
+            name.replace('\\', "\\\\").replace('\'', "\\'")
         );
 
         let (_, file_list) = drive
@@ -86,18 +218,29 @@ impl SheetsClient {
             .list()
             .q(&query)
             .spaces("drive")
-            .page_size(1)
+            .page_size(SEARCH_PAGE_SIZE)
             .add_scope(AUTH_SCOPE)
             .doit()
             .await
-            .map_err(|e| AppError::Sheets(format!("Failed to search spreadsheet: {}", e)))?;
+            .map_err(|e| api_error("Failed to search spreadsheet", e))?;
 
-        let spreadsheet_id = file_list
+        let ids: Vec<String> = file_list
             .files
-            .and_then(|files| files.into_iter().next())
-            .map(|file| file.id.unwrap_or_default());
-
-        Ok(spreadsheet_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|file| file.id)
+            .collect();
+
+        match ids.as_slice() {
+            [] => Ok(None),
+            [id] => Ok(Some(id.clone())),
+            _ => Err(AppError::Sheets(format!(
+                "Found {} spreadsheets named {:?} ({}), set google.spreadsheet_id to choose one",
+                ids.len(),
+                name,
+                ids.join(", ")
+            ))),
+        }
     }
 
     #[instrument(name = "Creating new spreadsheet", skip(sheets))]
@@ -120,7 +263,7 @@ impl SheetsClient {
             .add_scope(AUTH_SCOPE)
             .doit()
             .await
-            .map_err(|e| AppError::Sheets(format!("Failed to create spreadsheet: {}", e)))?;
+            .map_err(|e| api_error("Failed to create spreadsheet", e))?;
 
         let spreadsheet_id = result
             .spreadsheet_id
@@ -133,7 +276,7 @@ impl SheetsClient {
         Ok((spreadsheet_id, spreadsheet_url))
     }
 
-    async fn get_sheet(&self, sheet_name: &str) -> Result<Option<Sheet>> {
+    async fn get_sheets(&self) -> Result<Vec<Sheet>> {
         let (_, spreadsheet) = self
             .hub
             .spreadsheets()
@@ -142,29 +285,28 @@ impl SheetsClient {
             .add_scope(AUTH_SCOPE)
             .doit()
             .await
-            .map_err(|e| AppError::Sheets(format!("Failed to get spreadsheet: {}", e)))?;
+            .map_err(|e| api_error("Failed to get spreadsheet", e))?;
 
-        let sheet = spreadsheet
-            .sheets
-            .unwrap_or_default()
+        Ok(spreadsheet.sheets.unwrap_or_default())
+    }
+
+    async fn get_sheet(&self, sheet_name: &str) -> Result<Option<Sheet>> {
+        let sheet = self
+            .get_sheets()
+            .await?
             .into_iter()
-            .find(|sheet| {
-                sheet
-                    .properties
-                    .as_ref()
-                    .map(|props| props.title.as_deref() == Some(sheet_name))
-                    .unwrap_or(false)
-            });
+            .find(|sheet| sheet_title(sheet) == Some(sheet_name));
 
         Ok(sheet)
     }
 
-    async fn create_sheet(&self, sheet_name: &str) -> Result<Sheet> {
+    async fn create_sheet(&self, sheet_name: &str, hidden: bool) -> Result<Sheet> {
         let request = Request {
             add_sheet: Some(AddSheetRequest {
                 properties: Some(SheetProperties {
                     title: Some(sheet_name.to_string()),
                     sheet_type: Some("GRID".to_string()),
+                    hidden: Some(hidden),
                     ..Default::default()
                 }),
             }),
@@ -185,7 +327,7 @@ impl SheetsClient {
             .add_scope(AUTH_SCOPE)
             .doit()
             .await
-            .map_err(|e| AppError::Sheets(format!("Failed to create sheet: {}", e)))?;
+            .map_err(|e| api_error("Failed to create sheet", e))?;
 
         let sheet_properties = response
             .replies
@@ -202,7 +344,13 @@ impl SheetsClient {
         })
     }
 
-    async fn apply_formatting(&self, sheet: &Sheet) -> Result<()> {
+    /// Format a sheet whose header row is `headers`, which is where the managed columns are.
+    async fn apply_formatting(
+        &self,
+        sheet: &Sheet,
+        headers: &[String],
+        highlight: &HighlightColours,
+    ) -> Result<()> {
         let sheet_id = sheet
             .properties
             .as_ref()
@@ -212,8 +360,9 @@ impl SheetsClient {
         let mut requests = Vec::new();
         requests.push(bold_header_rule(sheet_id));
         requests.push(freeze_header_rule(sheet_id));
-        requests.extend(highlight_rules(sheet_id, sheet)?);
-        requests.extend(protection_rules(sheet_id, sheet)?);
+        requests.extend(highlight_rules(sheet_id, sheet, headers, highlight)?);
+        requests.extend(protection_rules(sheet_id, sheet, headers)?);
+        requests.extend(schema_version_rules(sheet_id, sheet));
 
         let batch_update = BatchUpdateSpreadsheetRequest {
             requests: Some(requests),
@@ -226,18 +375,30 @@ impl SheetsClient {
             .add_scope(AUTH_SCOPE)
             .doit()
             .await
-            .map_err(|e| AppError::Sheets(format!("Failed to apply formatting: {}", e)))?;
+            .map_err(|e| api_error("Failed to apply formatting", e))?;
         Ok(())
     }
 }
 
 #[async_trait]
 impl SheetOperations for SheetsClient {
+    #[instrument(name = "Listing sheets", skip(self))]
+    async fn list_sheets(&self) -> Result<Vec<Sheet>> {
+        let sheets = self
+            .get_sheets()
+            .await?
+            .into_iter()
+            .filter(is_managed_sheet)
+            .collect();
+
+        Ok(sheets)
+    }
+
     #[instrument(name = "Ensuring sheet exists", skip(self))]
     async fn ensure_sheet(&self, sheet_name: &str) -> Result<Sheet> {
         let (sheet, created) = match self.get_sheet(sheet_name).await? {
             Some(sheet) => (sheet, false),
-            None => (self.create_sheet(sheet_name).await?, true),
+            None => (self.create_sheet(sheet_name, false).await?, true),
         };
         let sheet_id = sheet.properties.as_ref().and_then(|p| p.sheet_id);
         match created {
@@ -248,9 +409,9 @@ impl SheetOperations for SheetsClient {
         Ok(sheet)
     }
 
-    #[instrument(name = "Fetching sheet", skip(self))]
-    async fn read_sheet(&self, sheet_name: &str) -> Result<Vec<Transaction>> {
-        let range = format!("{}!A:Z", sheet_name);
+    #[instrument(name = "Fetching sheet", skip(self, sheet))]
+    async fn read_sheet(&self, sheet: &Sheet, sheet_name: &str) -> Result<SheetData> {
+        let range = sheet_range(sheet_name);
         let (_, response) = self
             .hub
             .spreadsheets()
@@ -261,24 +422,45 @@ impl SheetOperations for SheetsClient {
             .add_scope(AUTH_SCOPE)
             .doit()
             .await
-            .map_err(|e| {
-                AppError::Sheets(format!("Failed to read sheet '{}': {}", sheet_name, e))
-            })?;
+            .map_err(|e| api_error(&format!("Failed to read sheet '{}'", sheet_name), e))?;
 
         // Values are Option<Vec<Vec<serde_json::Value>>>
-        let values = response.values.unwrap_or_default();
-        Transaction::from_sheet_rows(&values)
+        let mut values = response.values.unwrap_or_default();
+        migrate_rows(&mut values, sheet_schema_version(sheet)?)?;
+
+        let data = SheetData::from_sheet_rows(&values)?;
+        if self.snapshots.is_some() {
+            self.last_read
+                .lock()
+                .unwrap()
+                .insert(sheet_name.to_string(), data.clone());
+        }
+        Ok(data)
     }
 
-    #[instrument(name = "Writing sheet", skip(self, sheet, transactions))]
+    #[instrument(name = "Writing sheet", skip(self, sheet, data, highlight))]
     async fn write_sheet(
         &self,
         sheet: &Sheet,
         sheet_name: &str,
-        transactions: &[Transaction],
+        data: &SheetData,
+        highlight: &HighlightColours,
     ) -> Result<()> {
+        // The sheet is cleared before it's written, so keep a copy of what was there. Syncs read
+        // each tab just before writing it, so that read is used rather than reading it again.
+        if let Some(snapshots) = &self.snapshots {
+            let last_read = self.last_read.lock().unwrap().remove(sheet_name);
+            let current = match last_read {
+                Some(data) => data,
+                None => self.read_sheet(sheet, sheet_name).await?,
+            };
+            snapshots.save(sheet_name, &current, Utc::now())?;
+            // Reading it again above would have kept it
+            self.last_read.lock().unwrap().remove(sheet_name);
+        }
+
         // Clear the entire sheet first
-        let range_to_clear = format!("{}!A:Z", sheet_name);
+        let range_to_clear = sheet_range(sheet_name);
         let clear_request = ClearValuesRequest::default();
 
         self.hub
@@ -287,11 +469,14 @@ impl SheetOperations for SheetsClient {
             .add_scope(AUTH_SCOPE)
             .doit()
             .await
-            .map_err(|e| AppError::Sheets(format!("Failed to clear sheet: {}", e)))?;
+            .map_err(|e| api_error("Failed to clear sheet", e))?;
 
-        let rows = transactions.to_sheet_rows()?;
+        let rows = data.to_sheet_rows()?;
+        let headers: Vec<String> = rows[0].iter().map(Transaction::value_to_string).collect();
+        metrics::sheet_rows_written(sheet_name, data.transactions.len());
+        debug!(rows = data.transactions.len(), "Writing rows");
 
-        let data_range = format!("{}!A1", sheet_name);
+        let data_range = format!("{}!A1", sheet_range(sheet_name));
         let value_range = ValueRange {
             major_dimension: Some("ROWS".to_string()),
             range: Some(data_range.clone()),
@@ -305,11 +490,133 @@ impl SheetOperations for SheetsClient {
             .add_scope(AUTH_SCOPE)
             .doit()
             .await
-            .map_err(|e| AppError::Sheets(format!("Failed to write transactions: {}", e)))?;
+            .map_err(|e| api_error("Failed to write transactions", e))?;
 
         // Apply formatting
-        self.apply_formatting(sheet).await?;
+        self.apply_formatting(sheet, &headers, highlight).await?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "Appending history", skip_all)]
+    async fn append_history(&self, entries: &[AuditEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        // The tab is hidden so that it doesn't clutter the cards' tabs
+        let mut rows = Vec::new();
+        if self.get_sheet(HISTORY_SHEET).await?.is_none() {
+            self.create_sheet(HISTORY_SHEET, true).await?;
+            debug!("Created history sheet");
+            rows.push(
+                HISTORY_HEADERS
+                    .iter()
+                    .map(|header| serde_json::Value::String(header.to_string()))
+                    .collect(),
+            );
+        }
+        rows.extend(entries.iter().map(AuditEntry::to_row));
+
+        let range = format!("{}!A1", sheet_range(HISTORY_SHEET));
+        let value_range = ValueRange {
+            major_dimension: Some("ROWS".to_string()),
+            range: Some(range.clone()),
+            values: Some(rows),
+        };
+        self.hub
+            .spreadsheets()
+            .values_append(value_range, &self.spreadsheet_id, &range)
+            .value_input_option("RAW")
+            .insert_data_option("INSERT_ROWS")
+            .add_scope(AUTH_SCOPE)
+            .doit()
+            .await
+            .map_err(|e| api_error("Failed to append history", e))?;
 
         Ok(())
     }
+
+    #[instrument(name = "Fetching archive", skip(self))]
+    async fn read_archive(&self, sheet_name: &str) -> Result<SheetData> {
+        let client = self.archive_client();
+        match client.get_sheet(sheet_name).await? {
+            Some(sheet) => client.read_sheet(&sheet, sheet_name).await,
+            None => Ok(SheetData::default()),
+        }
+    }
+
+    async fn list_archives(&self) -> Result<Vec<String>> {
+        let Some(archive) = &self.archive else {
+            return Ok(Vec::new());
+        };
+        let names = archive
+            .list_sheets()
+            .await?
+            .iter()
+            .filter_map(sheet_title)
+            .filter(|title| parse_archive_tab_name(title).is_some())
+            .map(str::to_string)
+            .collect();
+
+        Ok(names)
+    }
+
+    #[instrument(name = "Writing archive", skip(self, data, highlight))]
+    async fn write_archive(
+        &self,
+        sheet_name: &str,
+        data: &SheetData,
+        highlight: &HighlightColours,
+    ) -> Result<()> {
+        let client = self.archive_client();
+        let sheet = client.ensure_sheet(sheet_name).await?;
+        client
+            .write_sheet(&sheet, sheet_name, data, highlight)
+            .await
+    }
+}
+
+/// Name of the hidden tab that the audit log is appended to.
+const HISTORY_SHEET: &str = "History";
+
+/// A1 notation for every cell in a sheet, quoted in case the name contains spaces or symbols.
+fn sheet_range(sheet_name: &str) -> String {
+    format!("'{}'", sheet_name.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spreadsheet_name() {
+        let config = GoogleConfig::default();
+        assert_eq!(spreadsheet_name(&config, None), SPREADSHEET_NAME);
+        assert_eq!(
+            spreadsheet_name(&config, Some("business")),
+            "Credit Card Transactions (credit-card-tracker) [business]"
+        );
+
+        let config = GoogleConfig {
+            spreadsheet_name: Some("Household cards".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(spreadsheet_name(&config, None), "Household cards");
+        assert_eq!(
+            spreadsheet_name(&config, Some("business")),
+            "Household cards",
+            "configured name should take precedence over the profile"
+        );
+    }
+
+    #[test]
+    fn test_sheet_range() {
+        assert_eq!(sheet_range("Amex"), "'Amex'");
+        assert_eq!(
+            sheet_range("British Airways American Express® C"),
+            "'British Airways American Express® C'"
+        );
+        assert_eq!(sheet_range("Sam's Card"), "'Sam''s Card'");
+    }
 }
@@ -1,3 +1,4 @@
+use crate::config::{Colour, HighlightColours};
 use crate::error::{AppError, Result};
 use crate::models::Transaction;
 use google_sheets4::FieldMask;
@@ -54,17 +55,29 @@ pub(super) fn freeze_header_rule(sheet_id: i32) -> Request {
     }
 }
 
+/// Index of a column in the header row that was written, which may have the user's own columns
+/// between the managed ones.
+fn column_index(headers: &[String], name: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|header| header == name)
+        .ok_or_else(|| AppError::Sheets(format!("{} column not found", name)))
+}
+
 /// Highlight rows where "ID" is filled but "Matched ID" is blank.
 /// Unmatched debits are highlighted yellow, unmatched credits are highlighted green.
-pub(super) fn highlight_rules(sheet_id: i32, sheet: &Sheet) -> Result<Vec<Request>> {
+pub(super) fn highlight_rules(
+    sheet_id: i32,
+    sheet: &Sheet,
+    headers: &[String],
+    colours: &HighlightColours,
+) -> Result<Vec<Request>> {
     let mut requests = Vec::new();
 
-    let type_column = Transaction::get_column_letter("Type")
-        .ok_or_else(|| AppError::Sheets("Type column not found".to_string()))?;
-    let id_column = Transaction::get_column_letter("ID")
-        .ok_or_else(|| AppError::Sheets("ID column not found".to_string()))?;
-    let matched_id_column = Transaction::get_column_letter("Matched ID")
-        .ok_or_else(|| AppError::Sheets("Matched ID column not found".to_string()))?;
+    let type_column = Transaction::index_to_column_letter(column_index(headers, "Type")?);
+    let id_column = Transaction::index_to_column_letter(column_index(headers, "ID")?);
+    let matched_id_column =
+        Transaction::index_to_column_letter(column_index(headers, "Matched ID")?);
 
     for _ in sheet
         .conditional_formats
@@ -82,26 +95,8 @@ pub(super) fn highlight_rules(sheet_id: i32, sheet: &Sheet) -> Result<Vec<Reques
     }
 
     let rules = [
-        (
-            "Credit",
-            Color {
-                // Light green
-                red: Some(0.698),
-                green: Some(0.910),
-                blue: Some(0.698),
-                alpha: Some(1.0),
-            },
-        ),
-        (
-            "Debit",
-            Color {
-                // Light yellow
-                red: Some(0.988),
-                green: Some(0.910),
-                blue: Some(0.698),
-                alpha: Some(1.0),
-            },
-        ),
+        ("Credit", to_color(&colours.credit)),
+        ("Debit", to_color(&colours.debit)),
     ];
 
     // Add conditional format rules for each transaction type
@@ -143,8 +138,22 @@ pub(super) fn highlight_rules(sheet_id: i32, sheet: &Sheet) -> Result<Vec<Reques
     Ok(requests)
 }
 
-/// Protect all columns up to and including "ID" column.
-pub(super) fn protection_rules(sheet_id: i32, sheet: &Sheet) -> Result<Vec<Request>> {
+fn to_color(colour: &Colour) -> Color {
+    Color {
+        red: Some(colour.red as f32 / 255.0),
+        green: Some(colour.green as f32 / 255.0),
+        blue: Some(colour.blue as f32 / 255.0),
+        alpha: Some(1.0),
+    }
+}
+
+/// Protect the managed columns up to and including "ID" column, leaving any of the user's own
+/// columns between them editable.
+pub(super) fn protection_rules(
+    sheet_id: i32,
+    sheet: &Sheet,
+    headers: &[String],
+) -> Result<Vec<Request>> {
     let mut requests = Vec::new();
 
     sheet
@@ -162,26 +171,39 @@ pub(super) fn protection_rules(sheet_id: i32, sheet: &Sheet) -> Result<Vec<Reque
             });
         });
 
-    let id_col_idx = Transaction::get_column_index("ID")
-        .ok_or_else(|| AppError::Sheets("ID column not found".to_string()))?;
-    let end_col_index = (id_col_idx + 1) as i32;
-
-    requests.push(Request {
-        add_protected_range: Some(AddProtectedRangeRequest {
-            protected_range: Some(ProtectedRange {
-                range: Some(GridRange {
-                    sheet_id: Some(sheet_id),
-                    start_column_index: Some(0),
-                    end_column_index: Some(end_col_index),
+    let id_col_idx = column_index(headers, "ID")?;
+    let field_names = Transaction::get_field_names();
+
+    // Each run of managed columns is protected separately
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (idx, header) in headers.iter().enumerate().take(id_col_idx + 1) {
+        if !field_names.contains(header) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((_, end)) if *end == idx => *end = idx + 1,
+            _ => runs.push((idx, idx + 1)),
+        }
+    }
+
+    for (start, end) in runs {
+        requests.push(Request {
+            add_protected_range: Some(AddProtectedRangeRequest {
+                protected_range: Some(ProtectedRange {
+                    range: Some(GridRange {
+                        sheet_id: Some(sheet_id),
+                        start_column_index: Some(start as i32),
+                        end_column_index: Some(end as i32),
+                        ..Default::default()
+                    }),
+                    description: Some("Managed by credit-card-tracker".to_string()),
+                    warning_only: Some(true),
                     ..Default::default()
                 }),
-                description: Some("Managed by credit-card-tracker".to_string()),
-                warning_only: Some(true),
-                ..Default::default()
             }),
-        }),
-        ..Default::default()
-    });
+            ..Default::default()
+        });
+    }
 
     Ok(requests)
 }
@@ -189,6 +211,49 @@ pub(super) fn protection_rules(sheet_id: i32, sheet: &Sheet) -> Result<Vec<Reque
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::SyncConfig;
+
+    fn default_colours() -> HighlightColours {
+        let config = SyncConfig::default();
+        HighlightColours {
+            debit: config.highlight_debit,
+            credit: config.highlight_credit,
+        }
+    }
+
+    fn managed_headers() -> Vec<String> {
+        Transaction::get_field_names()
+    }
+
+    /// Headers with a user's column between Amount and Currency.
+    fn interleaved_headers() -> Vec<String> {
+        let mut headers = managed_headers();
+        let amount = headers.iter().position(|h| h == "Amount").unwrap();
+        headers.insert(amount + 1, "Notes".to_string());
+        headers
+    }
+
+    fn formula(request: &Request) -> &str {
+        request
+            .add_conditional_format_rule
+            .as_ref()
+            .unwrap()
+            .rule
+            .as_ref()
+            .unwrap()
+            .boolean_rule
+            .as_ref()
+            .unwrap()
+            .condition
+            .as_ref()
+            .unwrap()
+            .values
+            .as_ref()
+            .unwrap()[0]
+            .user_entered_value
+            .as_deref()
+            .unwrap()
+    }
 
     #[test]
     fn test_bold_header_rule() {
@@ -226,7 +291,7 @@ mod tests {
             ..Default::default()
         };
 
-        let reqs = highlight_rules(123, &sheet).unwrap();
+        let reqs = highlight_rules(123, &sheet, &managed_headers(), &default_colours()).unwrap();
         assert_eq!(reqs.len(), 4, "should have 4 requests (2 deletes + 2 adds)");
         let mut reqs = reqs.iter();
 
@@ -269,6 +334,13 @@ mod tests {
             .unwrap();
         assert!(formula.contains("ISBLANK"));
         assert!(formula.contains("Credit"));
+        let colour = boolean_rule
+            .format
+            .as_ref()
+            .unwrap()
+            .background_color
+            .as_ref();
+        assert_eq!(colour.unwrap().red, to_color(&default_colours().credit).red);
 
         // Second add request (Debit - yellow)
         let req = reqs
@@ -289,6 +361,22 @@ mod tests {
             .unwrap();
         assert!(formula.contains("ISBLANK"));
         assert!(formula.contains("Debit"));
+        let colour = boolean_rule
+            .format
+            .as_ref()
+            .unwrap()
+            .background_color
+            .as_ref();
+        assert_eq!(colour.unwrap().red, to_color(&default_colours().debit).red);
+    }
+
+    #[test]
+    fn test_to_color() {
+        let color = to_color(&Colour::new(0xff, 0x00, 0x80));
+        assert_eq!(color.red, Some(1.0));
+        assert_eq!(color.green, Some(0.0));
+        assert_eq!(color.blue, Some(128.0 / 255.0));
+        assert_eq!(color.alpha, Some(1.0));
     }
 
     #[test]
@@ -307,7 +395,7 @@ mod tests {
             ..Default::default()
         };
 
-        let reqs = protection_rules(111, &sheet).unwrap();
+        let reqs = protection_rules(111, &sheet, &managed_headers()).unwrap();
         assert_eq!(reqs.len(), 3, "should have 3 requests (2 deletes + 1 add)",);
         let mut reqs = reqs.iter();
 
@@ -339,4 +427,53 @@ mod tests {
         assert_eq!(range.start_column_index, Some(0));
         assert!(range.end_column_index.unwrap() > 1);
     }
+
+    #[test]
+    fn test_rules_follow_interleaved_user_columns() {
+        let headers = interleaved_headers();
+        let letter = |name: &str| {
+            Transaction::index_to_column_letter(headers.iter().position(|h| h == name).unwrap())
+        };
+
+        let reqs = highlight_rules(123, &Sheet::default(), &headers, &default_colours()).unwrap();
+        assert_eq!(
+            formula(&reqs[0]),
+            format!(
+                "=AND(NOT(ISBLANK(${}2)), ISBLANK(${}2), ${}2=\"Credit\")",
+                letter("ID"),
+                letter("Matched ID"),
+                letter("Type")
+            )
+        );
+        assert_ne!(
+            letter("ID"),
+            Transaction::get_column_letter("ID").unwrap(),
+            "the user's column should move the ID column"
+        );
+
+        let reqs = protection_rules(111, &Sheet::default(), &headers).unwrap();
+        let ranges: Vec<(Option<i32>, Option<i32>)> = reqs
+            .iter()
+            .map(|req| {
+                let range = req
+                    .add_protected_range
+                    .as_ref()
+                    .unwrap()
+                    .protected_range
+                    .as_ref()
+                    .unwrap()
+                    .range
+                    .as_ref()
+                    .unwrap();
+                (range.start_column_index, range.end_column_index)
+            })
+            .collect();
+        let notes = headers.iter().position(|h| h == "Notes").unwrap() as i32;
+        let id = headers.iter().position(|h| h == "ID").unwrap() as i32;
+        assert_eq!(
+            ranges,
+            vec![(Some(0), Some(notes)), (Some(notes + 1), Some(id + 1))],
+            "the user's column shouldn't be protected"
+        );
+    }
 }
@@ -1,27 +1,60 @@
 mod auth;
 mod client;
 mod formatting;
+mod schema;
+mod snapshots;
 
 pub use client::SheetsClient;
+pub use snapshots::Snapshots;
 
 // Re-export clear_tokens for CLI usage
 pub use auth::clear_tokens as clear_sheets_tokens;
 
+use crate::config::HighlightColours;
 use crate::error::Result;
-use crate::models::Transaction;
+use crate::models::SheetData;
+use crate::models::audit::AuditEntry;
 use async_trait::async_trait;
 use google_sheets4::api::Sheet;
 
 #[async_trait]
 pub trait SheetOperations {
+    /// List the sheets that have been written by a sync, in tab order.
+    async fn list_sheets(&self) -> Result<Vec<Sheet>>;
+
     async fn ensure_sheet(&self, sheet_name: &str) -> Result<Sheet>;
 
-    async fn read_sheet(&self, sheet_name: &str) -> Result<Vec<Transaction>>;
+    async fn read_sheet(&self, sheet: &Sheet, sheet_name: &str) -> Result<SheetData>;
 
     async fn write_sheet(
         &self,
         sheet: &Sheet,
         sheet_name: &str,
-        transactions: &[Transaction],
+        data: &SheetData,
+        highlight: &HighlightColours,
+    ) -> Result<()>;
+
+    /// Append entries of the audit log to the hidden History tab, creating it if needed.
+    async fn append_history(&self, entries: &[AuditEntry]) -> Result<()>;
+
+    /// Read a tab of archived transactions, from the archive spreadsheet if there is one. A tab
+    /// that doesn't exist yet has no transactions.
+    async fn read_archive(&self, sheet_name: &str) -> Result<SheetData>;
+
+    /// List the archive tabs in the archive spreadsheet. Empty when archive tabs are kept in the
+    /// main spreadsheet, as `list_sheets` already lists them.
+    async fn list_archives(&self) -> Result<Vec<String>>;
+
+    /// Write a tab of archived transactions, creating it if needed.
+    async fn write_archive(
+        &self,
+        sheet_name: &str,
+        data: &SheetData,
+        highlight: &HighlightColours,
     ) -> Result<()>;
 }
+
+/// Get the title of a sheet, which is the name of its tab.
+pub fn sheet_title(sheet: &Sheet) -> Option<&str> {
+    sheet.properties.as_ref().and_then(|p| p.title.as_deref())
+}
@@ -0,0 +1,381 @@
+use crate::error::{AppError, Result};
+use google_sheets4::api::{
+    CreateDeveloperMetadataRequest, DataFilter, DeleteDeveloperMetadataRequest, DeveloperMetadata,
+    DeveloperMetadataLocation, DeveloperMetadataLookup, Request, Sheet,
+};
+use serde_json::Value;
+use tracing::info;
+
+/// Version of the sheet column layout.
+///
+/// Columns are mapped by header name and always written in the order of the `Transaction`
+/// fields. Adding a field needs a `Migration` that inserts its column, so that columns added by
+/// the user keep their place beside the managed ones, and renaming a field needs one so that
+/// existing values are carried over to the new header.
+pub(super) const SCHEMA_VERSION: u32 = 7;
+
+/// Sheets written before the schema was versioned are treated as this version.
+const UNVERSIONED_SCHEMA_VERSION: u32 = 1;
+
+/// Developer metadata key used to store the schema version against each sheet.
+const SCHEMA_VERSION_KEY: &str = "credit-card-tracker.schema-version";
+
+/// Header changes needed to upgrade a sheet to `version` from the previous version.
+struct Migration {
+    version: u32,
+    /// Pairs of (old header, new header).
+    renames: &'static [(&'static str, &'static str)],
+    /// Pairs of (new header, header it follows), or `None` to insert as the first column.
+    inserts: &'static [(&'static str, Option<&'static str>)],
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        renames: &[],
+        inserts: &[
+            ("Category", Some("Comments")),
+            ("Tags", Some("Category")),
+            ("Rule", Some("Tags")),
+        ],
+    },
+    Migration {
+        version: 3,
+        renames: &[],
+        inserts: &[("Merchant", Some("Description"))],
+    },
+    Migration {
+        version: 4,
+        renames: &[],
+        inserts: &[("Match Confidence", Some("Matched ID"))],
+    },
+    Migration {
+        version: 5,
+        renames: &[],
+        inserts: &[("Converted Amount", Some("Currency"))],
+    },
+    Migration {
+        version: 6,
+        renames: &[],
+        inserts: &[
+            ("Statement", Some("Rule")),
+            ("Statement Total", Some("Statement")),
+        ],
+    },
+    Migration {
+        version: 7,
+        renames: &[],
+        inserts: &[("Age (days)", Some("Match Confidence"))],
+    },
+];
+
+/// Get the schema version that a sheet was last written with.
+pub(super) fn sheet_schema_version(sheet: &Sheet) -> Result<u32> {
+    let Some(metadata) = schema_metadata(sheet) else {
+        return Ok(UNVERSIONED_SCHEMA_VERSION);
+    };
+
+    metadata
+        .metadata_value
+        .as_deref()
+        .unwrap_or_default()
+        .parse()
+        .map_err(|e| AppError::Sheets(format!("Invalid sheet schema version: {}", e)))
+}
+
+/// Whether a sheet has been written by credit-card-tracker, as opposed to added by the user.
+pub(super) fn is_managed_sheet(sheet: &Sheet) -> bool {
+    schema_metadata(sheet).is_some()
+}
+
+/// Upgrade the rows of a sheet written with an older schema version.
+///
+/// Headers are renamed, and new columns are inserted with empty cells beside the columns that
+/// they follow, so that the positions of the user's own columns still line up when written.
+pub(super) fn migrate_rows(rows: &mut [Vec<Value>], from_version: u32) -> Result<()> {
+    migrate_rows_with(MIGRATIONS, SCHEMA_VERSION, rows, from_version)
+}
+
+fn migrate_rows_with(
+    migrations: &[Migration],
+    to_version: u32,
+    rows: &mut [Vec<Value>],
+    from_version: u32,
+) -> Result<()> {
+    if from_version > to_version {
+        return Err(AppError::Sheets(format!(
+            "Sheet schema version {} is newer than supported version {}, please upgrade",
+            from_version, to_version
+        )));
+    }
+
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    for migration in migrations
+        .iter()
+        .filter(|m| m.version > from_version && m.version <= to_version)
+    {
+        info!(version = migration.version, "Migrating sheet schema");
+        for (old, new) in migration.renames {
+            rows[0]
+                .iter_mut()
+                .filter(|header| header.as_str() == Some(old))
+                .for_each(|header| *header = Value::String(new.to_string()));
+        }
+
+        for (header, after) in migration.inserts {
+            let headers = &rows[0];
+            if headers.iter().any(|h| h.as_str() == Some(header)) {
+                continue;
+            }
+            let idx = match after {
+                Some(after) => headers
+                    .iter()
+                    .position(|h| h.as_str() == Some(after))
+                    .map_or(headers.len(), |idx| idx + 1),
+                None => 0,
+            };
+
+            rows[0].insert(idx, Value::String(header.to_string()));
+            for row in rows.iter_mut().skip(1).filter(|row| row.len() >= idx) {
+                row.insert(idx, Value::Null);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Record the current schema version against the sheet, replacing any previous version.
+pub(super) fn schema_version_rules(sheet_id: i32, sheet: &Sheet) -> Vec<Request> {
+    let mut requests = Vec::new();
+
+    if let Some(metadata_id) = schema_metadata(sheet).and_then(|m| m.metadata_id) {
+        requests.push(Request {
+            delete_developer_metadata: Some(DeleteDeveloperMetadataRequest {
+                data_filter: Some(DataFilter {
+                    developer_metadata_lookup: Some(DeveloperMetadataLookup {
+                        metadata_id: Some(metadata_id),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+            }),
+            ..Default::default()
+        });
+    }
+
+    requests.push(Request {
+        create_developer_metadata: Some(CreateDeveloperMetadataRequest {
+            developer_metadata: Some(DeveloperMetadata {
+                metadata_key: Some(SCHEMA_VERSION_KEY.to_string()),
+                metadata_value: Some(SCHEMA_VERSION.to_string()),
+                location: Some(DeveloperMetadataLocation {
+                    sheet_id: Some(sheet_id),
+                    ..Default::default()
+                }),
+                visibility: Some("DOCUMENT".to_string()),
+                ..Default::default()
+            }),
+        }),
+        ..Default::default()
+    });
+
+    requests
+}
+
+fn schema_metadata(sheet: &Sheet) -> Option<&DeveloperMetadata> {
+    sheet
+        .developer_metadata
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .find(|m| m.metadata_key.as_deref() == Some(SCHEMA_VERSION_KEY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sheet_with_version(version: &str) -> Sheet {
+        Sheet {
+            developer_metadata: Some(vec![DeveloperMetadata {
+                metadata_id: Some(42),
+                metadata_key: Some(SCHEMA_VERSION_KEY.to_string()),
+                metadata_value: Some(version.to_string()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sheet_schema_version() {
+        assert_eq!(
+            sheet_schema_version(&Sheet::default()).unwrap(),
+            UNVERSIONED_SCHEMA_VERSION
+        );
+        assert_eq!(sheet_schema_version(&sheet_with_version("3")).unwrap(), 3);
+        assert!(sheet_schema_version(&sheet_with_version("three")).is_err());
+    }
+
+    #[test]
+    fn test_is_managed_sheet() {
+        assert!(!is_managed_sheet(&Sheet::default()));
+        assert!(is_managed_sheet(&sheet_with_version("1")));
+    }
+
+    #[test]
+    fn test_migrate_rows_renames_headers() {
+        let migrations = [
+            Migration {
+                version: 2,
+                renames: &[("Comments", "Notes")],
+                inserts: &[],
+            },
+            Migration {
+                version: 3,
+                renames: &[("Notes", "Memo"), ("Type", "Kind")],
+                inserts: &[],
+            },
+        ];
+        let mut rows = vec![
+            vec![json!("Type"), json!("Comments"), json!("Category")],
+            vec![json!("Debit"), json!("Comments"), json!("Groceries")],
+        ];
+
+        migrate_rows_with(&migrations, 3, &mut rows, 1).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![json!("Kind"), json!("Memo"), json!("Category")],
+                vec![json!("Debit"), json!("Comments"), json!("Groceries")],
+            ],
+            "only headers should be renamed"
+        );
+    }
+
+    #[test]
+    fn test_migrate_rows_inserts_columns() {
+        let migrations = [Migration {
+            version: 2,
+            renames: &[],
+            inserts: &[
+                ("Merchant", Some("Description")),
+                ("Category", Some("Comments")),
+                ("ID", None),
+            ],
+        }];
+        let mut rows = vec![
+            vec![json!("Description"), json!("Notes"), json!("Comments")],
+            vec![json!("Coffee"), json!("Work"), json!("Paid back")],
+            vec![json!("Tea")],
+        ];
+
+        migrate_rows_with(&migrations, 2, &mut rows, 1).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    json!("ID"),
+                    json!("Description"),
+                    json!("Merchant"),
+                    json!("Notes"),
+                    json!("Comments"),
+                    json!("Category"),
+                ],
+                vec![
+                    Value::Null,
+                    json!("Coffee"),
+                    Value::Null,
+                    json!("Work"),
+                    json!("Paid back"),
+                    Value::Null,
+                ],
+                vec![Value::Null, json!("Tea"), Value::Null],
+            ],
+            "values should stay under their headers"
+        );
+    }
+
+    #[test]
+    fn test_migrations_insert_every_column() {
+        let mut rows = vec![
+            [
+                "Timestamp",
+                "Description",
+                "Amount",
+                "Currency",
+                "Type",
+                "ID",
+            ]
+            .into_iter()
+            .chain(["Matched ID", "Comments", "Notes"])
+            .map(|h| json!(h))
+            .collect::<Vec<_>>(),
+        ];
+
+        migrate_rows(&mut rows, UNVERSIONED_SCHEMA_VERSION).unwrap();
+        let mut expected: Vec<Value> = crate::models::Transaction::get_field_names()
+            .into_iter()
+            .map(Value::String)
+            .collect();
+        expected.push(json!("Notes"));
+        assert_eq!(rows[0], expected);
+    }
+
+    #[test]
+    fn test_migrate_rows_skips_applied_migrations() {
+        let migrations = [Migration {
+            version: 2,
+            renames: &[("Comments", "Notes")],
+            inserts: &[],
+        }];
+        let mut rows = vec![vec![json!("Comments")]];
+
+        migrate_rows_with(&migrations, 2, &mut rows, 2).unwrap();
+        assert_eq!(rows, vec![vec![json!("Comments")]]);
+    }
+
+    #[test]
+    fn test_migrate_rows_rejects_newer_version() {
+        let mut rows = vec![];
+        assert!(migrate_rows_with(&[], 1, &mut rows, 2).is_err());
+    }
+
+    #[test]
+    fn test_schema_version_rules() {
+        let reqs = schema_version_rules(123, &Sheet::default());
+        assert_eq!(reqs.len(), 1, "should only create when there is no version");
+
+        let reqs = schema_version_rules(123, &sheet_with_version("1"));
+        assert_eq!(reqs.len(), 2, "should delete the old version and create");
+
+        let req = reqs[0].delete_developer_metadata.as_ref().unwrap();
+        let lookup = req
+            .data_filter
+            .as_ref()
+            .unwrap()
+            .developer_metadata_lookup
+            .as_ref()
+            .unwrap();
+        assert_eq!(lookup.metadata_id, Some(42));
+
+        let metadata = reqs[1]
+            .create_developer_metadata
+            .as_ref()
+            .unwrap()
+            .developer_metadata
+            .as_ref()
+            .unwrap();
+        assert_eq!(metadata.metadata_key.as_deref(), Some(SCHEMA_VERSION_KEY));
+        assert_eq!(
+            metadata.metadata_value.as_deref(),
+            Some(SCHEMA_VERSION.to_string().as_str())
+        );
+        assert_eq!(metadata.location.as_ref().unwrap().sheet_id, Some(123));
+    }
+}
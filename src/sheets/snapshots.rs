@@ -0,0 +1,349 @@
+use super::schema::{SCHEMA_VERSION, migrate_rows};
+use crate::config::{Config, SnapshotConfig};
+use crate::error::{AppError, Result};
+use crate::models::{SheetData, ToSheetRows, Transaction};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Format of snapshot IDs, which are the time that they were taken.
+const ID_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// Contents of a snapshot file.
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotFile {
+    card: String,
+    taken_at: DateTime<Utc>,
+    schema_version: u32,
+    rows: Vec<Vec<Value>>,
+}
+
+/// A snapshot of a card's sheet, taken before it was written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub id: String,
+    /// Sheet tab of the card
+    pub card: String,
+    pub taken_at: DateTime<Utc>,
+    pub transactions: usize,
+}
+
+/// Local JSON copies of each sheet, one directory per tab, keeping the newest `retain` of each.
+#[derive(Debug, Clone)]
+pub struct Snapshots {
+    dir: PathBuf,
+    retain: usize,
+}
+
+/// Directory name for a tab, as tab names can contain characters that aren't allowed in paths.
+fn tab_dir_name(sheet_name: &str) -> String {
+    sheet_name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+impl Snapshots {
+    pub fn new(dir: &Path, retain: usize) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            retain,
+        }
+    }
+
+    /// Snapshots in the data directory, with the retention from the config.
+    pub fn from_config(config: &SnapshotConfig) -> Result<Self> {
+        Ok(Self::new(&Config::data_file("snapshots")?, config.retain))
+    }
+
+    /// Whether snapshots should be taken, which they aren't if none are retained.
+    pub fn is_enabled(&self) -> bool {
+        self.retain > 0
+    }
+
+    fn tab_dir(&self, sheet_name: &str) -> PathBuf {
+        self.dir.join(tab_dir_name(sheet_name))
+    }
+
+    /// Save a sheet's data, then remove its oldest snapshots beyond the retention. Empty sheets
+    /// and sheets that haven't changed since their latest snapshot, other than the ages that
+    /// each sync updates, aren't saved. Returns the ID of the snapshot.
+    pub fn save(
+        &self,
+        sheet_name: &str,
+        data: &SheetData,
+        taken_at: DateTime<Utc>,
+    ) -> Result<Option<String>> {
+        if data.transactions.is_empty() {
+            return Ok(None);
+        }
+
+        let rows = data.to_sheet_rows()?;
+        let dir = self.tab_dir(sheet_name);
+        if let Some(latest) = snapshot_paths(&dir)?.into_iter().max()
+            && let Ok(mut latest) = read_snapshot(&latest)
+            && migrate_rows(&mut latest.rows, latest.schema_version).is_ok()
+            && let Ok(previous) = SheetData::from_sheet_rows(&latest.rows)
+            && same_except_ages(&previous, data)
+        {
+            debug!(card = sheet_name, "Sheet unchanged since its last snapshot");
+            return Ok(None);
+        }
+
+        let id = taken_at.format(ID_FORMAT).to_string();
+        let file = SnapshotFile {
+            card: sheet_name.to_string(),
+            taken_at,
+            schema_version: SCHEMA_VERSION,
+            rows,
+        };
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join(format!("{}.json", id)),
+            serde_json::to_string(&file)?,
+        )?;
+        debug!(card = sheet_name, id, "Snapshot saved");
+
+        self.prune(sheet_name)?;
+        Ok(Some(id))
+    }
+
+    /// Remove the oldest snapshots of a tab beyond the retention.
+    fn prune(&self, sheet_name: &str) -> Result<()> {
+        let mut paths = snapshot_paths(&self.tab_dir(sheet_name))?;
+        if paths.len() <= self.retain {
+            return Ok(());
+        }
+
+        // IDs sort in time order, so the oldest are first
+        paths.sort();
+        for path in &paths[..paths.len() - self.retain] {
+            fs::remove_file(path)?;
+            debug!(path = %path.display(), "Old snapshot removed");
+        }
+        Ok(())
+    }
+
+    /// List the snapshots of every tab, or only of `sheet_name`, oldest first.
+    pub fn list(&self, sheet_name: Option<&str>) -> Result<Vec<Snapshot>> {
+        let dirs = match sheet_name {
+            Some(sheet_name) => vec![self.tab_dir(sheet_name)],
+            None if self.dir.exists() => fs::read_dir(&self.dir)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+
+        let mut snapshots = Vec::new();
+        for dir in dirs {
+            for path in snapshot_paths(&dir)? {
+                match read_snapshot(&path) {
+                    Ok(file) => snapshots.push(Snapshot {
+                        id: snapshot_id(&path),
+                        card: file.card,
+                        taken_at: file.taken_at,
+                        // The first row is the header
+                        transactions: file.rows.len().saturating_sub(1),
+                    }),
+                    Err(e) => warn!(path = %path.display(), error = %e, "Failed to read snapshot"),
+                }
+            }
+        }
+
+        snapshots.sort_by(|a, b| a.taken_at.cmp(&b.taken_at).then(a.card.cmp(&b.card)));
+        Ok(snapshots)
+    }
+
+    /// Load a tab's snapshot, by ID or "latest".
+    pub fn load(&self, sheet_name: &str, id: &str) -> Result<SheetData> {
+        let dir = self.tab_dir(sheet_name);
+        let path = match id {
+            "latest" => snapshot_paths(&dir)?.into_iter().max(),
+            id => Some(dir.join(format!("{}.json", id))).filter(|path| path.exists()),
+        }
+        .ok_or_else(|| {
+            AppError::Config(format!(
+                "No snapshot {} of {}, see `snapshots list`",
+                id, sheet_name
+            ))
+        })?;
+
+        let mut file = read_snapshot(&path)?;
+        migrate_rows(&mut file.rows, file.schema_version)?;
+        SheetData::from_sheet_rows(&file.rows)
+    }
+}
+
+/// Whether two versions of a sheet are the same apart from the Age (days) column, which changes
+/// every day.
+fn same_except_ages(a: &SheetData, b: &SheetData) -> bool {
+    a.extra_columns == b.extra_columns
+        && a.transactions.len() == b.transactions.len()
+        && a.transactions.iter().zip(&b.transactions).all(|(a, b)| {
+            *a == Transaction {
+                age_days: a.age_days,
+                ..b.clone()
+            }
+        })
+}
+
+fn read_snapshot(path: &Path) -> Result<SnapshotFile> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+fn snapshot_id(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Snapshot files in a tab's directory, which may not exist yet.
+fn snapshot_paths(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use chrono::Duration;
+    use rust_decimal::prelude::dec;
+
+    fn mock_data() -> SheetData {
+        mock_data_with_comment("Split with Sam")
+    }
+
+    fn mock_data_with_comment(comment: &str) -> SheetData {
+        SheetData {
+            transactions: vec![Transaction {
+                comments: Some(comment.to_string()),
+                ..mock_transaction(
+                    "tx_1",
+                    dec!(-10.00),
+                    TransactionType::Debit,
+                    mock_datetime(2025, 1, 1),
+                )
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_save_list_load_and_prune() {
+        let dir = std::env::temp_dir().join(format!(
+            "credit-card-tracker-snapshots-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let snapshots = Snapshots::new(&dir, 2);
+        let taken_at = mock_datetime(2025, 3, 1);
+
+        assert_eq!(
+            snapshots
+                .save("Amex/Gold", &SheetData::default(), taken_at)
+                .unwrap(),
+            None,
+            "empty sheets shouldn't be saved"
+        );
+        for minutes in 0..3 {
+            snapshots
+                .save(
+                    "Amex/Gold",
+                    &mock_data_with_comment(&format!("Edit {}", minutes)),
+                    taken_at + Duration::minutes(minutes),
+                )
+                .unwrap();
+        }
+        snapshots.save("Visa", &mock_data(), taken_at).unwrap();
+
+        let listed = snapshots.list(Some("Amex/Gold")).unwrap();
+        assert_eq!(
+            listed
+                .iter()
+                .map(|snapshot| snapshot.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["20250301T100100.000Z", "20250301T100200.000Z"],
+            "the oldest snapshot should be removed"
+        );
+        assert_eq!(listed[0].card, "Amex/Gold");
+        assert_eq!(listed[0].transactions, 1);
+        assert_eq!(snapshots.list(None).unwrap().len(), 3);
+
+        assert_eq!(
+            snapshots.load("Amex/Gold", "20250301T100100.000Z").unwrap(),
+            mock_data_with_comment("Edit 1")
+        );
+        assert_eq!(snapshots.load("Visa", "latest").unwrap(), mock_data());
+        assert!(snapshots.load("Visa", "20250101T000000.000Z").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_skips_unchanged_sheet() {
+        let dir = std::env::temp_dir().join(format!(
+            "credit-card-tracker-snapshots-unchanged-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let snapshots = Snapshots::new(&dir, 5);
+        let taken_at = mock_datetime(2025, 3, 1);
+
+        assert!(
+            snapshots
+                .save("Amex", &mock_data(), taken_at)
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(
+            snapshots
+                .save("Amex", &mock_data(), taken_at + Duration::minutes(1))
+                .unwrap(),
+            None,
+            "an unchanged sheet shouldn't be saved again"
+        );
+        let mut older = mock_data();
+        older.transactions[0].age_days = Some(30);
+        assert_eq!(
+            snapshots
+                .save("Amex", &older, taken_at + Duration::days(1))
+                .unwrap(),
+            None,
+            "a sheet whose transactions are only older shouldn't be saved again"
+        );
+        assert!(
+            snapshots
+                .save(
+                    "Amex",
+                    &mock_data_with_comment("Paid back"),
+                    taken_at + Duration::minutes(2)
+                )
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(snapshots.list(Some("Amex")).unwrap().len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
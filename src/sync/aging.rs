@@ -0,0 +1,114 @@
+use crate::models::Transaction;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// Unmatched transactions on a card that are older than its age threshold.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AgeAlert {
+    pub tab_name: String,
+    pub threshold_days: u32,
+    pub count: usize,
+    pub oldest_days: i64,
+}
+
+/// Set the age of every unmatched debit and credit as of `today`, and clear it on matched ones.
+pub fn set_ages(transactions: &mut [Transaction], today: NaiveDate) {
+    for t in transactions {
+        t.age_days = match t.matched_id {
+            Some(_) => None,
+            None => Some((today - t.timestamp.date_naive()).num_days().max(0)),
+        };
+    }
+}
+
+/// Find the unmatched transactions older than `threshold_days`, using the ages set by
+/// [`set_ages`].
+pub fn find_overdue(
+    tab_name: &str,
+    transactions: &[Transaction],
+    threshold_days: u32,
+) -> Option<AgeAlert> {
+    let overdue: Vec<i64> = transactions
+        .iter()
+        .filter_map(|t| t.age_days)
+        .filter(|age| *age > i64::from(threshold_days))
+        .collect();
+
+    Some(AgeAlert {
+        tab_name: tab_name.to_string(),
+        threshold_days,
+        count: overdue.len(),
+        oldest_days: *overdue.iter().max()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    fn mock_transactions() -> Vec<Transaction> {
+        vec![
+            mock_transaction(
+                "tx_1",
+                dec!(-10.00),
+                TransactionType::Debit,
+                mock_datetime(2025, 1, 1),
+            ),
+            Transaction {
+                matched_id: Some("tx_3".to_string()),
+                age_days: Some(5),
+                ..mock_transaction(
+                    "tx_2",
+                    dec!(-20.00),
+                    TransactionType::Debit,
+                    mock_datetime(2025, 1, 5),
+                )
+            },
+            mock_transaction(
+                "tx_3",
+                dec!(20.00),
+                TransactionType::Credit,
+                mock_datetime(2025, 1, 25),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_set_ages() {
+        let mut transactions = mock_transactions();
+        set_ages(
+            &mut transactions,
+            NaiveDate::from_ymd_opt(2025, 2, 10).unwrap(),
+        );
+
+        let ages: Vec<_> = transactions.iter().map(|t| t.age_days).collect();
+        assert_eq!(
+            ages,
+            vec![Some(40), None, Some(16)],
+            "matched transactions shouldn't have an age"
+        );
+    }
+
+    #[test]
+    fn test_find_overdue() {
+        let mut transactions = mock_transactions();
+        set_ages(
+            &mut transactions,
+            NaiveDate::from_ymd_opt(2025, 2, 10).unwrap(),
+        );
+
+        assert_eq!(
+            find_overdue("Amex", &transactions, 15),
+            Some(AgeAlert {
+                tab_name: "Amex".to_string(),
+                threshold_days: 15,
+                count: 2,
+                oldest_days: 40,
+            })
+        );
+        assert_eq!(find_overdue("Amex", &transactions, 40), None);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::models::Transaction;
+use chrono::{DateTime, Datelike, Months, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+/// Name of the tab that a card's transactions from `year` are archived to.
+pub fn archive_tab_name(tab_name: &str, year: i32) -> String {
+    format!("{} ({})", tab_name, year)
+}
+
+/// The card's tab and the year of an archive tab, or `None` if it isn't one.
+pub fn parse_archive_tab_name(name: &str) -> Option<(&str, i32)> {
+    let (tab_name, year) = name.strip_suffix(')')?.rsplit_once(" (")?;
+    if year.len() != 4 || !year.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((tab_name, year.parse().ok()?))
+}
+
+/// Transactions older than this are archived, `months` before `now`.
+pub fn archive_cutoff(now: DateTime<Utc>, months: u32) -> DateTime<Utc> {
+    now.checked_sub_months(Months::new(months))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+/// Remove the transactions that can be archived and return them grouped by year.
+///
+/// Only pairs of matched transactions that are both older than `cutoff` and in the same tab are
+/// archived, and both sides are moved together, so that the Matched ID of a transaction left in
+/// any tab never refers to an archived one. A transaction matched with one on another card, or
+/// with one that can't be found, is never archived, and neither is its counterpart.
+pub fn take_archivable(
+    transactions: &mut Vec<Transaction>,
+    cutoff: DateTime<Utc>,
+) -> BTreeMap<i32, Vec<Transaction>> {
+    let by_id: HashMap<&str, &Transaction> =
+        transactions.iter().map(|t| (t.id.as_str(), t)).collect();
+    let archivable: Vec<bool> = transactions
+        .iter()
+        .map(|t| {
+            t.timestamp < cutoff
+                && t.matched_id
+                    .as_deref()
+                    .and_then(|id| by_id.get(id))
+                    .is_some_and(|counterpart| {
+                        counterpart.timestamp < cutoff
+                            && counterpart.matched_id.as_deref() == Some(t.id.as_str())
+                    })
+        })
+        .collect();
+
+    let mut archived: BTreeMap<i32, Vec<Transaction>> = BTreeMap::new();
+    let mut archivable = archivable.into_iter();
+    transactions.retain(|t| {
+        if archivable.next() != Some(true) {
+            return true;
+        }
+        archived
+            .entry(t.timestamp.year())
+            .or_default()
+            .push(t.clone());
+        false
+    });
+
+    archived
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    #[test]
+    fn test_archive_tab_name() {
+        assert_eq!(archive_tab_name("Amex", 2024), "Amex (2024)");
+        assert_eq!(
+            parse_archive_tab_name("Amex (Gold) (2024)"),
+            Some(("Amex (Gold)", 2024))
+        );
+        assert_eq!(parse_archive_tab_name("Amex (Gold)"), None);
+        assert_eq!(parse_archive_tab_name("Amex"), None);
+    }
+
+    #[test]
+    fn test_archive_cutoff() {
+        assert_eq!(
+            archive_cutoff(mock_datetime(2025, 3, 31), 1),
+            mock_datetime(2025, 2, 28)
+        );
+    }
+
+    #[test]
+    fn test_take_archivable_moves_pairs_together() {
+        let matched = |id: &str, matched_id: &str, timestamp| Transaction {
+            matched_id: Some(matched_id.to_string()),
+            ..mock_transaction(id, dec!(-10.00), TransactionType::Debit, timestamp)
+        };
+        let mut transactions = vec![
+            matched("tx_1", "tx_2", mock_datetime(2023, 11, 1)),
+            matched("tx_2", "tx_1", mock_datetime(2024, 12, 1)),
+            // The counterpart of this one is newer than the cutoff
+            matched("tx_3", "tx_4", mock_datetime(2024, 12, 1)),
+            matched("tx_4", "tx_3", mock_datetime(2025, 2, 1)),
+            // Not linked back by its counterpart
+            matched("tx_5", "tx_1", mock_datetime(2024, 6, 1)),
+            mock_transaction(
+                "tx_6",
+                dec!(-5.00),
+                TransactionType::Debit,
+                mock_datetime(2023, 1, 1),
+            ),
+        ];
+
+        let archived = take_archivable(&mut transactions, mock_datetime(2025, 1, 1));
+
+        let ids = |transactions: &[Transaction]| -> Vec<String> {
+            transactions.iter().map(|t| t.id.clone()).collect()
+        };
+        assert_eq!(
+            archived
+                .iter()
+                .map(|(year, transactions)| (*year, ids(transactions)))
+                .collect::<Vec<_>>(),
+            vec![
+                (2023, vec!["tx_1".to_string()]),
+                (2024, vec!["tx_2".to_string()]),
+            ]
+        );
+        assert_eq!(ids(&transactions), vec!["tx_3", "tx_4", "tx_5", "tx_6"]);
+    }
+
+    #[test]
+    fn test_take_archivable_keeps_cross_card_pairs() {
+        // A payment on a card's tab matched by hand with a charge on another card, as seen from
+        // each card's tab
+        let payment = Transaction {
+            matched_id: Some("tx_charge".to_string()),
+            ..mock_transaction(
+                "tx_payment",
+                dec!(10.00),
+                TransactionType::Credit,
+                mock_datetime(2023, 6, 2),
+            )
+        };
+        let charge = Transaction {
+            matched_id: Some("tx_payment".to_string()),
+            ..mock_transaction(
+                "tx_charge",
+                dec!(-10.00),
+                TransactionType::Debit,
+                mock_datetime(2023, 6, 1),
+            )
+        };
+
+        for transaction in [payment, charge] {
+            let mut transactions = vec![transaction.clone()];
+            let archived = take_archivable(&mut transactions, mock_datetime(2025, 1, 1));
+            assert!(
+                archived.is_empty(),
+                "{} shouldn't be archived without its counterpart",
+                transaction.id
+            );
+            assert_eq!(transactions, vec![transaction]);
+        }
+    }
+}
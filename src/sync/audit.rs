@@ -0,0 +1,99 @@
+use crate::error::Result;
+use crate::models::audit::AuditEntry;
+use crate::sync::report::CardReport;
+use chrono::{DateTime, Utc};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// ID of a sync or import, from the time that it started.
+pub fn run_id(started: DateTime<Utc>) -> String {
+    started.format("%Y%m%dT%H%M%S%.3fZ").to_string()
+}
+
+/// Audit entries for the changes made to each card, as of `timestamp`.
+pub fn audit_entries(
+    run_id: &str,
+    timestamp: DateTime<Utc>,
+    cards: &[CardReport],
+) -> Vec<AuditEntry> {
+    cards
+        .iter()
+        .flat_map(|card| {
+            card.changes.iter().map(|change| AuditEntry {
+                timestamp,
+                run_id: run_id.to_string(),
+                card: card.card.clone(),
+                change: change.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Append entries to the audit log, one JSON object per line. Existing entries are never
+/// rewritten.
+pub fn append_audit_log(path: &Path, entries: &[AuditEntry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let mut contents = String::new();
+    for entry in entries {
+        contents.push_str(&serde_json::to_string(entry)?);
+        contents.push('\n');
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(contents.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::audit::{Change, ChangeReason};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_append_audit_log() {
+        let timestamp = Utc.with_ymd_and_hms(2025, 3, 1, 9, 0, 0).unwrap();
+        let run_id = run_id(timestamp);
+        assert_eq!(run_id, "20250301T090000.000Z");
+
+        let cards = vec![CardReport {
+            card: "Amex".to_string(),
+            changes: vec![Change {
+                transaction_id: "tx_1".to_string(),
+                field: "Category".to_string(),
+                old_value: None,
+                new_value: Some("Groceries".to_string()),
+                reason: ChangeReason::Rule,
+                rule: Some("supermarkets".to_string()),
+            }],
+            ..Default::default()
+        }];
+        let entries = audit_entries(&run_id, timestamp, &cards);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].card, "Amex");
+
+        let path = std::env::temp_dir().join(format!(
+            "credit-card-tracker-audit-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        append_audit_log(&path, &entries).unwrap();
+        append_audit_log(&path, &entries).unwrap();
+        append_audit_log(&path, &[]).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<AuditEntry> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, vec![entries[0].clone(), entries[0].clone()]);
+    }
+}
@@ -0,0 +1,398 @@
+use crate::models::Transaction;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// A column that users edit in the sheet, and that a sync may also change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UserField {
+    MatchedId,
+    Comments,
+    Category,
+    Tags,
+}
+
+impl UserField {
+    const ALL: [Self; 4] = [
+        UserField::MatchedId,
+        UserField::Comments,
+        UserField::Category,
+        UserField::Tags,
+    ];
+
+    /// Header of the column.
+    fn name(self) -> &'static str {
+        match self {
+            UserField::MatchedId => "Matched ID",
+            UserField::Comments => "Comments",
+            UserField::Category => "Category",
+            UserField::Tags => "Tags",
+        }
+    }
+
+    fn get(self, t: &Transaction) -> &Option<String> {
+        match self {
+            UserField::MatchedId => &t.matched_id,
+            UserField::Comments => &t.comments,
+            UserField::Category => &t.category,
+            UserField::Tags => &t.tags,
+        }
+    }
+
+    fn get_mut(self, t: &mut Transaction) -> &mut Option<String> {
+        match self {
+            UserField::MatchedId => &mut t.matched_id,
+            UserField::Comments => &mut t.comments,
+            UserField::Category => &mut t.category,
+            UserField::Tags => &mut t.tags,
+        }
+    }
+}
+
+/// A column that was edited in the sheet while a sync changed it to something else. The
+/// user's value is kept.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Conflict {
+    pub transaction_id: String,
+    /// Header of the column
+    pub field: String,
+    /// Value when the sheet was read at the start of the sync
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    /// Value the sync would have written
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ours: Option<String>,
+    /// Value edited in the sheet during the sync, which was kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theirs: Option<String>,
+}
+
+/// Three-way merge the user-editable columns of `transactions`, which were computed from `base`,
+/// with `latest`, which was read again just before writing.
+///
+/// Edits made in the sheet since `base` are kept, as are the sync's changes to columns that
+/// weren't edited. Where both changed a column to different values the user's value is kept and
+/// the conflict is returned.
+///
+/// Rows added to the sheet since `base` belong to the user, so they're kept as they are. Rows
+/// deleted since `base` stay deleted, unless they're in `fetched`, the IDs fetched by this sync,
+/// which are written back as the provider still has them. Counterparts left linked to a deleted
+/// row are unlinked.
+///
+/// When the user's Matched ID is kept, the counterparts are changed to match, so that pairs stay
+/// linked both ways: the transaction it was matched with is unlinked, and the one the user
+/// matched it with is linked back to it, unless that one's Matched ID was edited too.
+pub fn merge_concurrent_edits(
+    base: &[Transaction],
+    latest: &[Transaction],
+    transactions: &mut Vec<Transaction>,
+    fetched: &HashSet<String>,
+) -> Vec<Conflict> {
+    let base: HashMap<&str, &Transaction> = base.iter().map(|t| (t.id.as_str(), t)).collect();
+    let latest_by_id: HashMap<&str, &Transaction> =
+        latest.iter().map(|t| (t.id.as_str(), t)).collect();
+
+    let deleted: HashSet<String> = transactions
+        .iter()
+        .map(|t| t.id.as_str())
+        .filter(|id| {
+            base.contains_key(id) && !latest_by_id.contains_key(id) && !fetched.contains(*id)
+        })
+        .map(str::to_string)
+        .collect();
+    transactions.retain(|t| !deleted.contains(&t.id));
+    for t in transactions.iter_mut() {
+        if t.matched_id.as_ref().is_some_and(|id| deleted.contains(id)) {
+            t.matched_id = None;
+            t.match_confidence = None;
+        }
+    }
+    let ours: HashSet<String> = transactions.iter().map(|t| t.id.clone()).collect();
+    transactions.extend(
+        latest
+            .iter()
+            .filter(|t| !base.contains_key(t.id.as_str()) && !ours.contains(&t.id))
+            .cloned(),
+    );
+
+    let mut conflicts = Vec::new();
+    let mut relinked = Vec::new();
+    for t in transactions.iter_mut() {
+        let (Some(base), Some(theirs)) = (base.get(t.id.as_str()), latest_by_id.get(t.id.as_str()))
+        else {
+            continue;
+        };
+
+        for field in UserField::ALL {
+            let base_value = field.get(base);
+            let their_value = field.get(theirs);
+            let our_value = field.get(t);
+            if their_value == base_value || their_value == our_value {
+                continue;
+            }
+
+            if our_value != base_value {
+                conflicts.push(Conflict {
+                    transaction_id: t.id.clone(),
+                    field: field.name().to_string(),
+                    base: base_value.clone(),
+                    ours: our_value.clone(),
+                    theirs: their_value.clone(),
+                });
+            }
+            if field == UserField::MatchedId {
+                relinked.push(Relink {
+                    id: t.id.clone(),
+                    old: our_value.clone(),
+                    new: their_value.clone(),
+                    confidence: theirs.match_confidence,
+                });
+                t.match_confidence = theirs.match_confidence;
+            }
+            *field.get_mut(t) = their_value.clone();
+        }
+    }
+
+    relink_counterparts(transactions, &relinked);
+    conflicts
+}
+
+/// A Matched ID that was changed to the user's value by the merge.
+struct Relink {
+    id: String,
+    /// Value before the merge
+    old: Option<String>,
+    /// The user's value
+    new: Option<String>,
+    confidence: Option<Decimal>,
+}
+
+/// Make the counterparts of the Matched IDs changed by the merge agree with them.
+fn relink_counterparts(transactions: &mut [Transaction], relinked: &[Relink]) {
+    let index: HashMap<String, usize> = transactions
+        .iter()
+        .enumerate()
+        .map(|(idx, t)| (t.id.clone(), idx))
+        .collect();
+    let edited: HashSet<&str> = relinked.iter().map(|r| r.id.as_str()).collect();
+
+    // Unlink a transaction from `id`, if it's still linked to it
+    let unlink = |transactions: &mut [Transaction], counterpart: &str, id: &str| {
+        if let Some(&idx) = index.get(counterpart)
+            && transactions[idx].matched_id.as_deref() == Some(id)
+        {
+            transactions[idx].matched_id = None;
+            transactions[idx].match_confidence = None;
+        }
+    };
+
+    for relink in relinked {
+        if let Some(old) = &relink.old
+            && relink.new.as_ref() != Some(old)
+            && !edited.contains(old.as_str())
+        {
+            unlink(transactions, old, &relink.id);
+        }
+
+        let Some(new) = &relink.new else {
+            continue;
+        };
+        let Some(&idx) = index.get(new) else {
+            continue;
+        };
+        if edited.contains(new.as_str())
+            || transactions[idx].matched_id.as_deref() == Some(&relink.id)
+        {
+            continue;
+        }
+
+        if let Some(previous) = transactions[idx].matched_id.clone() {
+            unlink(transactions, &previous, new);
+        }
+        transactions[idx].matched_id = Some(relink.id.clone());
+        transactions[idx].match_confidence = relink.confidence;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::TransactionType;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    fn transaction(id: &str) -> Transaction {
+        mock_transaction(
+            id,
+            dec!(-10.00),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 1),
+        )
+    }
+
+    #[test]
+    fn test_merge_concurrent_edits() {
+        let base = vec![
+            Transaction {
+                category: Some("Shopping".to_string()),
+                ..transaction("tx_1")
+            },
+            transaction("tx_2"),
+        ];
+        let latest = vec![
+            // Edited in the sheet during the sync
+            Transaction {
+                matched_id: Some("tx_9".to_string()),
+                match_confidence: Some(dec!(1)),
+                comments: Some("Refunded by Sam".to_string()),
+                category: Some("Gifts".to_string()),
+                ..base[0].clone()
+            },
+            base[1].clone(),
+        ];
+        let mut transactions = vec![
+            // Categorised by a rule during the sync
+            Transaction {
+                category: Some("Groceries".to_string()),
+                tags: Some("food".to_string()),
+                ..base[0].clone()
+            },
+            Transaction {
+                comments: Some("Added by a rule".to_string()),
+                ..base[1].clone()
+            },
+            transaction("tx_3"),
+        ];
+
+        let conflicts = merge_concurrent_edits(&base, &latest, &mut transactions, &HashSet::new());
+
+        assert_eq!(
+            conflicts,
+            vec![Conflict {
+                transaction_id: "tx_1".to_string(),
+                field: "Category".to_string(),
+                base: Some("Shopping".to_string()),
+                ours: Some("Groceries".to_string()),
+                theirs: Some("Gifts".to_string()),
+            }]
+        );
+        assert_eq!(
+            transactions[0],
+            Transaction {
+                matched_id: Some("tx_9".to_string()),
+                match_confidence: Some(dec!(1)),
+                comments: Some("Refunded by Sam".to_string()),
+                category: Some("Gifts".to_string()),
+                tags: Some("food".to_string()),
+                ..base[0].clone()
+            },
+            "the user's edits should be kept alongside the sync's changes"
+        );
+        assert_eq!(transactions[1].comments.as_deref(), Some("Added by a rule"));
+        assert_eq!(transactions[2], transaction("tx_3"));
+    }
+
+    #[test]
+    fn test_merge_concurrent_edits_keeps_pairs_symmetric() {
+        let matched = |id: &str, matched_id: &str| Transaction {
+            matched_id: Some(matched_id.to_string()),
+            match_confidence: Some(Decimal::ONE),
+            ..transaction(id)
+        };
+        let base = vec![
+            transaction("tx_1"),
+            transaction("tx_2"),
+            transaction("tx_3"),
+            matched("tx_4", "tx_5"),
+            matched("tx_5", "tx_4"),
+        ];
+        // The user matches tx_1 with tx_4 by hand, in that row only
+        let latest = vec![
+            Transaction {
+                matched_id: Some("tx_4".to_string()),
+                match_confidence: None,
+                ..base[0].clone()
+            },
+            base[1].clone(),
+            base[2].clone(),
+            base[3].clone(),
+            base[4].clone(),
+        ];
+        // While the sync matches tx_1 with tx_2
+        let mut transactions = vec![
+            matched("tx_1", "tx_2"),
+            matched("tx_2", "tx_1"),
+            transaction("tx_3"),
+            base[3].clone(),
+            base[4].clone(),
+        ];
+
+        let conflicts = merge_concurrent_edits(&base, &latest, &mut transactions, &HashSet::new());
+
+        assert_eq!(conflicts.len(), 1);
+        let matched_ids: Vec<Option<&str>> = transactions
+            .iter()
+            .map(|t| t.matched_id.as_deref())
+            .collect();
+        assert_eq!(
+            matched_ids,
+            vec![Some("tx_4"), None, None, Some("tx_1"), None],
+            "the sync's counterpart and the user's counterpart's old match should be unlinked"
+        );
+        assert_eq!(transactions[1].match_confidence, None);
+        assert_eq!(transactions[3].match_confidence, None);
+    }
+
+    #[test]
+    fn test_merge_concurrent_edits_keeps_rows_added_during_sync() {
+        let base = vec![transaction("tx_1")];
+        // Typed into the sheet by hand during the sync
+        let added = Transaction {
+            comments: Some("Cash withdrawal".to_string()),
+            ..transaction("manual_1")
+        };
+        let latest = vec![base[0].clone(), added.clone()];
+        let mut transactions = vec![transaction("tx_1"), transaction("tx_2")];
+
+        let conflicts = merge_concurrent_edits(&base, &latest, &mut transactions, &HashSet::new());
+
+        assert_eq!(conflicts, vec![]);
+        assert_eq!(
+            transactions,
+            vec![transaction("tx_1"), transaction("tx_2"), added],
+            "the user's row should be kept as it is"
+        );
+    }
+
+    #[test]
+    fn test_merge_concurrent_edits_keeps_rows_deleted_during_sync() {
+        let matched = |id: &str, matched_id: &str| Transaction {
+            matched_id: Some(matched_id.to_string()),
+            match_confidence: Some(Decimal::ONE),
+            ..transaction(id)
+        };
+        let base = vec![
+            transaction("tx_1"),
+            transaction("tx_2"),
+            transaction("tx_3"),
+        ];
+        // The user deletes tx_2 and tx_3 during the sync
+        let latest = vec![base[0].clone()];
+        // While the sync matches tx_1 with tx_2, and fetches tx_3 again
+        let mut transactions = vec![
+            matched("tx_1", "tx_2"),
+            matched("tx_2", "tx_1"),
+            transaction("tx_3"),
+        ];
+        let fetched = HashSet::from(["tx_3".to_string()]);
+
+        merge_concurrent_edits(&base, &latest, &mut transactions, &fetched);
+
+        assert_eq!(
+            transactions,
+            vec![transaction("tx_1"), transaction("tx_3")],
+            "the deleted row should stay deleted unless it was fetched, and its counterpart \
+             unlinked"
+        );
+    }
+}
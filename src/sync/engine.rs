@@ -1,17 +1,32 @@
-use crate::config::SyncConfig;
+use crate::config::{CardSettings, Config, HighlightColours};
+use crate::currency::CurrencyConverter;
 use crate::error::Result;
 use crate::models::Card;
-use crate::models::Transaction;
+use crate::models::audit::{AuditEntry, Change, ChangeReason};
+use crate::models::sheet::ExtraColumns;
+use crate::models::statement::{
+    StatementCurrency, StatementCycle, StatementDays, clear_statements, main_currency,
+};
+use crate::models::{SheetData, Transaction};
+use crate::rules::{Action, ActionLog, Rules};
 use crate::sheets::SheetOperations;
-use crate::sync::reconcile::reconcile_transactions;
+use crate::sync::aging::{find_overdue, set_ages};
+use crate::sync::archive::{archive_cutoff, archive_tab_name, take_archivable};
+use crate::sync::conflicts::merge_concurrent_edits;
+use crate::sync::reconcile::{MatchedPair, reconcile_transactions};
+use crate::sync::report::{CardReport, SyncReport};
 use crate::truelayer::TrueLayerOperations;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Months, Utc};
 use indicatif::ProgressStyle;
-use tracing::{Span, debug, info, instrument};
+use std::collections::{BTreeSet, HashSet};
+use std::time::Instant;
+use tracing::{Span, debug, error, info, instrument, warn};
 use tracing_indicatif::span_ext::IndicatifSpanExt;
 
 pub struct SyncEngine<TLC, SC> {
-    config: SyncConfig,
+    config: Config,
+    rules: Rules,
+    converter: Option<CurrencyConverter>,
     truelayer_client: TLC,
     sheets_client: SC,
 }
@@ -21,16 +36,34 @@ where
     TLC: TrueLayerOperations + Sync,
     SC: SheetOperations + Sync,
 {
-    pub fn new(config: SyncConfig, truelayer_client: TLC, sheets_client: SC) -> Self {
+    pub fn new(
+        config: Config,
+        rules: Rules,
+        converter: Option<CurrencyConverter>,
+        truelayer_client: TLC,
+        sheets_client: SC,
+    ) -> Self {
         Self {
             config,
+            rules,
+            converter,
             truelayer_client,
             sheets_client,
         }
     }
 
+    /// Sync every card, recording any changes made by rules in `actions`, any statement days
+    /// learned from the cards' balances in `statement_days` and what happened on each card in
+    /// `report`. A card that fails is recorded in the report and the other cards are still
+    /// synced. Warns at the end about unmatched transactions older than each card's
+    /// `age_warning_days`, and about transactions that couldn't be converted.
     #[instrument(name = "Sync", skip_all)]
-    pub async fn sync(&self) -> Result<()> {
+    pub async fn sync(
+        &self,
+        actions: &mut ActionLog,
+        statement_days: &mut StatementDays,
+        report: &mut SyncReport,
+    ) -> Result<()> {
         let span = Span::current();
         span.pb_set_style(
             &ProgressStyle::with_template(
@@ -40,15 +73,7 @@ where
         );
         span.pb_set_message("Syncing cards");
 
-        // Normalize to start of day (00:00:00 UTC) to align with API daily resolution and avoid overlaps
         let to_date = Utc::now();
-        let from_date = (to_date - self.config.fetch_days)
-            .date_naive()
-            .and_hms_opt(0, 0, 0)
-            .ok_or_else(|| {
-                crate::error::AppError::Config("Failed to calculate from_date".to_string())
-            })?
-            .and_utc();
 
         let cards = self.truelayer_client.get_cards().await?;
         if cards.is_empty() {
@@ -57,118 +82,627 @@ where
             ));
         }
 
+        match self.truelayer_client.get_consent_expiry().await {
+            Ok(expires_at) => report.consent_expires_at = expires_at,
+            Err(e) => debug!(error = %e, "Failed to get consent expiry"),
+        }
+
         span.pb_set_length(cards.len() as u64);
         for card in &cards {
-            self.sync_card(card, from_date, to_date).await?;
+            let mut settings = self.config.card_settings(card);
+            if settings.statement_day.is_none() {
+                settings.statement_day = self
+                    .learn_statement_day(card, &settings.tab_name, statement_days)
+                    .await;
+            }
+
+            // Normalize to start of day (00:00:00 UTC) to align with API daily resolution and avoid overlaps
+            let from_date = (to_date - settings.fetch_days)
+                .date_naive()
+                .and_hms_opt(0, 0, 0)
+                .ok_or_else(|| {
+                    crate::error::AppError::Config("Failed to calculate from_date".to_string())
+                })?
+                .and_utc();
+
+            let started = Instant::now();
+            let mut card_report = match self
+                .sync_card(card, &settings, from_date, to_date, actions)
+                .await
+            {
+                Ok(card_report) => card_report,
+                Err(e) => {
+                    error!(card = card.name, error = %e, "Failed to sync card");
+                    CardReport {
+                        card: settings.tab_name.clone(),
+                        error: Some(e.to_string()),
+                        ..Default::default()
+                    }
+                }
+            };
+            card_report.duration_ms =
+                u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+            report.cards.push(card_report);
             span.pb_inc(1);
         }
 
+        for alert in report.cards.iter().filter_map(|card| card.overdue.as_ref()) {
+            warn!(
+                card = alert.tab_name,
+                count = alert.count,
+                oldest_days = alert.oldest_days,
+                threshold_days = alert.threshold_days,
+                "Unmatched transactions are older than the age threshold"
+            );
+        }
+        warn_unconverted(&report.cards, self.converter.as_ref());
+
         Ok(())
     }
 
-    #[instrument(name = "Syncing card", skip_all, fields(card = %card.name))]
+    /// Append audit entries to the History tab if `history_tab` is set. Failures are logged
+    /// rather than returned, as the entries are also in the local audit log.
+    pub async fn append_history(&self, entries: &[AuditEntry]) {
+        if !self.config.sync.history_tab {
+            return;
+        }
+        if let Err(e) = self.sheets_client.append_history(entries).await {
+            warn!(error = %e, "Failed to append to the History tab");
+        }
+    }
+
+    /// Learn the day that a card's statements close from its last statement, falling back to
+    /// the day learned before if the balance doesn't say.
+    async fn learn_statement_day(
+        &self,
+        card: &Card,
+        tab_name: &str,
+        statement_days: &mut StatementDays,
+    ) -> Option<u32> {
+        match self.truelayer_client.get_card_balance(&card.id).await {
+            Ok(balance) => {
+                if let Some(date) = balance.last_statement_date {
+                    statement_days.learn(tab_name, date);
+                }
+            }
+            // Not every provider gives balances, so carry on without them
+            Err(e) => debug!(error = %e, "Failed to get card balance"),
+        }
+
+        statement_days.get(tab_name)
+    }
+
+    #[instrument(name = "Syncing card", skip_all, fields(card = %card.name, tab = %settings.tab_name))]
     async fn sync_card(
         &self,
         card: &Card,
+        settings: &CardSettings,
         from_date: DateTime<Utc>,
         to_date: DateTime<Utc>,
-    ) -> Result<()> {
+        actions: &mut ActionLog,
+    ) -> Result<CardReport> {
         let transactions = self
             .truelayer_client
             .get_card_transactions(&card.id, from_date, to_date)
             .await?;
 
-        let sheet_name = &card.name;
-        let sheet = self.sheets_client.ensure_sheet(sheet_name).await?;
+        merge_transactions(
+            &self.sheets_client,
+            card,
+            settings,
+            &self.rules,
+            self.converter.as_ref(),
+            actions,
+            transactions,
+        )
+        .await
+    }
+}
 
-        let existing_transactions = self.sheets_client.read_sheet(sheet_name).await?;
-        let existing_transactions_count = existing_transactions.len();
+/// Upsert transactions into a card's sheet, keeping the matches, comments and categories of
+/// existing transactions, then apply rules, reconcile and write the sheet.
+///
+/// Comments and matches made by rules are recorded in `actions` so that they can be reverted.
+/// Amounts are converted into the base currency if there is a `converter`. The sheet is read
+/// again before it's written, and any matches, comments, categories or tags edited in the
+/// meantime are merged rather than overwritten. If the card has `archive_after_months`, matched
+/// transactions older than that are moved to a tab for each year. Returns what changed,
+/// including any edits that conflicted with the sync and any unmatched transactions older than
+/// the card's `age_warning_days`.
+pub async fn merge_transactions(
+    sheets_client: &(impl SheetOperations + Sync),
+    card: &Card,
+    settings: &CardSettings,
+    rules: &Rules,
+    converter: Option<&CurrencyConverter>,
+    actions: &mut ActionLog,
+    transactions: Vec<Transaction>,
+) -> Result<CardReport> {
+    let sheet_name = &settings.tab_name;
+    let sheet = sheets_client.ensure_sheet(sheet_name).await?;
 
-        let mut transaction_map: std::collections::HashMap<String, Transaction> =
-            existing_transactions
+    let existing = sheets_client.read_sheet(&sheet, sheet_name).await?;
+    let base = existing.transactions.clone();
+    let fetched_count = transactions.len();
+
+    // Transactions that are fetched again after they were archived stay in the archive
+    let cutoff = settings
+        .archive_after_months
+        .map(|months| archive_cutoff(Utc::now(), months));
+    let transactions = match cutoff {
+        Some(cutoff) => {
+            let archived_ids =
+                find_archived(sheets_client, sheet_name, &transactions, cutoff).await?;
+            transactions
                 .into_iter()
-                .map(|t| (t.id.clone(), t))
-                .collect();
-
-        for mut t in transactions {
-            // If this transaction already exists in the sheet, preserve its matched_id and comments
-            if let Some(existing) = transaction_map.get(&t.id) {
-                t.matched_id = existing.matched_id.clone();
-                t.comments = existing.comments.clone();
+                .filter(|t| !archived_ids.contains(&t.id))
+                .collect()
+        }
+        None => transactions,
+    };
+
+    let mut transaction_map: std::collections::HashMap<String, Transaction> = existing
+        .transactions
+        .into_iter()
+        .map(|t| (t.id.clone(), t))
+        .collect();
+
+    let fetched_ids: HashSet<String> = transactions.iter().map(|t| t.id.clone()).collect();
+    let mut new_ids = HashSet::new();
+    let mut updated_count = 0;
+    let mut changes = Vec::new();
+    for mut t in transactions {
+        // If this transaction already exists in the sheet, preserve the values from the sheet
+        if let Some(existing) = transaction_map.get(&t.id) {
+            if !t.same_details(existing) {
+                updated_count += 1;
+                changes.extend(Change::diff(
+                    existing,
+                    &t,
+                    Transaction::DETAIL_FIELDS,
+                    ChangeReason::Updated,
+                    None,
+                ));
+            }
+            t.converted_amount = existing.converted_amount;
+            t.matched_id = existing.matched_id.clone();
+            t.match_confidence = existing.match_confidence;
+            t.comments = existing.comments.clone();
+            t.category = existing.category.clone();
+            t.tags = existing.tags.clone();
+            t.rule = existing.rule.clone();
+        } else {
+            changes.push(Change::added(&t));
+            new_ids.insert(t.id.clone());
+        }
+
+        // Upsert: Overwrite existing entry (to get latest data) or insert new one
+        transaction_map.insert(t.id.clone(), t);
+    }
+
+    let mut all_transactions: Vec<Transaction> = transaction_map.into_values().collect();
+    all_transactions.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
+
+    let mut unconverted_count = 0;
+    for t in &mut all_transactions {
+        match converter {
+            Some(converter) => {
+                // Older transactions may be from before the rates loaded, so keep their amounts
+                if let Some(amount) = converter.convert(t) {
+                    t.converted_amount = Some(amount);
+                }
+                if t.converted_amount.is_none() {
+                    unconverted_count += 1;
+                }
             }
+            // Amounts converted into a base currency that's no longer configured are cleared
+            None => t.converted_amount = None,
+        }
+
+        t.merchant = rules.merchant(&t.description);
+        let before = t.clone();
+        rules.apply(card, t);
+        changes.extend(Change::diff(
+            &before,
+            t,
+            &["Category", "Tags", "Rule"],
+            ChangeReason::Rule,
+            t.rule.as_deref().or(before.rule.as_deref()),
+        ));
+
+        if new_ids.contains(&t.id)
+            && t.comments.is_none()
+            && let Some(rule) = rules.find_comment(card, t)
+        {
+            t.comments = rule.comment.clone();
+            changes.push(Change {
+                transaction_id: t.id.clone(),
+                field: "Comments".to_string(),
+                old_value: None,
+                new_value: rule.comment.clone(),
+                reason: ChangeReason::Rule,
+                rule: Some(rule.name.clone()),
+            });
+            actions.record(
+                sheet_name,
+                &rule.name,
+                Action::Comment {
+                    transaction_id: t.id.clone(),
+                    comment: rule.comment.clone().unwrap_or_default(),
+                },
+            );
+        }
+    }
+
+    // After converting, so that statements in the base currency total the converted amounts.
+    // Statements that have been partly archived still total every transaction in them.
+    let cycle = settings.statement_day.map(StatementCycle::new);
+    let card_currency = main_currency(&all_transactions)
+        .unwrap_or_default()
+        .to_string();
+    let currency = StatementCurrency::new(converter.map(CurrencyConverter::base), &card_currency);
+    let mut archived = match (cutoff, cycle) {
+        (Some(cutoff), Some(_)) => {
+            read_archived(sheets_client, sheet_name, &all_transactions, cutoff).await?
+        }
+        _ => Vec::new(),
+    };
+    match cycle {
+        Some(cycle) => cycle.assign(&mut all_transactions, &archived, currency),
+        None => clear_statements(&mut all_transactions),
+    }
+
+    let mut matches = reconcile_transactions(
+        &all_transactions,
+        settings.reconcile_days,
+        settings.reconcile_strategy,
+        settings.reconcile_tolerance,
+    );
+    for pair in &matches {
+        debug!(?pair, "Matched transaction pair");
+        changes.extend(apply_match(
+            &mut all_transactions,
+            pair,
+            ChangeReason::AutoMatch,
+            None,
+        ));
+    }
 
-            // Upsert: Overwrite existing entry (to get latest data) or insert new one
-            transaction_map.insert(t.id.clone(), t);
+    for pair_rule in rules.pairs() {
+        let pairs = pair_rule.find_pairs(&all_transactions, |pair| {
+            actions.is_reverted_match(sheet_name, &pair.debit_id, &pair.credit_id)
+        });
+        for pair in pairs {
+            debug!(
+                ?pair,
+                rule = pair_rule.name,
+                "Matched transaction pair by rule"
+            );
+            changes.extend(apply_match(
+                &mut all_transactions,
+                &pair,
+                ChangeReason::Rule,
+                Some(&pair_rule.name),
+            ));
+            actions.record(
+                sheet_name,
+                &pair_rule.name,
+                Action::Match {
+                    debit_id: pair.debit_id.clone(),
+                    credit_id: pair.credit_id.clone(),
+                },
+            );
+            matches.push(pair);
         }
+    }
 
-        let mut all_transactions: Vec<Transaction> = transaction_map.into_values().collect();
+    // The sheet may have been edited since it was read, so keep those edits
+    let latest = sheets_client.read_sheet(&sheet, sheet_name).await?;
+    let conflicts = merge_concurrent_edits(
+        &base,
+        &latest.transactions,
+        &mut all_transactions,
+        &fetched_ids,
+    );
+    all_transactions.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
+    for conflict in &conflicts {
+        warn!(
+            transaction_id = conflict.transaction_id,
+            field = conflict.field,
+            ours = conflict.ours,
+            theirs = conflict.theirs,
+            "Sheet was edited during the sync, so the edit was kept"
+        );
+    }
+    // Rows deleted in the sheet during the sync aren't written back, so nor are their changes
+    let kept: HashSet<&str> = all_transactions.iter().map(|t| t.id.as_str()).collect();
+    changes.retain(|change| kept.contains(change.transaction_id.as_str()));
+    changes.retain(|change| {
+        !conflicts.iter().any(|conflict| {
+            conflict.transaction_id == change.transaction_id && conflict.field == change.field
+        })
+    });
+    // Matches may have been relinked to agree with the user's, so drop the ones undone
+    changes.retain(|change| {
+        change.field != "Matched ID"
+            || all_transactions
+                .iter()
+                .find(|t| t.id == change.transaction_id)
+                .is_none_or(|t| t.matched_id == change.new_value)
+    });
+
+    matches.retain(|pair| {
         all_transactions
-            .sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
+            .iter()
+            .any(|t| t.id == pair.debit_id && t.matched_id.as_ref() == Some(&pair.credit_id))
+    });
 
-        let matches = reconcile_transactions(&all_transactions, self.config.reconcile_days);
-        for pair in &matches {
-            debug!(?pair, "Matched transaction pair");
-            all_transactions
-                .iter_mut()
-                .find(|t| t.id == pair.debit_id)
-                .expect("debit_id from reconcile must exist in all_transactions")
-                .matched_id = Some(pair.credit_id.clone());
-            all_transactions
-                .iter_mut()
-                .find(|t| t.id == pair.credit_id)
-                .expect("credit_id from reconcile must exist in all_transactions")
-                .matched_id = Some(pair.debit_id.clone());
+    // After merging, so that matches made or undone in the sheet don't leave a stale age
+    set_ages(&mut all_transactions, Utc::now().date_naive());
+
+    // Archives are written before the card's tab, so that a failure never leaves a transaction
+    // in neither
+    if let Some(cutoff) = cutoff {
+        changes.extend(
+            archive_transactions(
+                sheets_client,
+                sheet_name,
+                &mut all_transactions,
+                &latest.extra_columns,
+                cutoff,
+                &settings.highlight,
+                &mut archived,
+            )
+            .await?,
+        );
+        // The last transaction of a statement may have been archived along with its total
+        if let Some(cycle) = cycle {
+            cycle.assign(&mut all_transactions, &archived, currency);
         }
+    }
+
+    let report = CardReport {
+        fetched: fetched_count,
+        added: new_ids.len(),
+        updated: updated_count,
+        matched: matches.len(),
+        overdue: settings
+            .age_warning_days
+            .and_then(|days| find_overdue(sheet_name, &all_transactions, days)),
+        archived: changes
+            .iter()
+            .filter(|change| change.reason == ChangeReason::Archived)
+            .count(),
+        unconverted: unconverted_count,
+        changes,
+        conflicts,
+        ..CardReport::new(sheet_name, &all_transactions, &new_ids)
+    };
+
+    let data = SheetData {
+        transactions: all_transactions,
+        // Extra columns are only edited by users, so the latest are always kept
+        extra_columns: latest.extra_columns,
+    };
+    sheets_client
+        .write_sheet(&sheet, sheet_name, &data, &settings.highlight)
+        .await?;
+
+    if report.added > 0 {
+        info!(count = report.added, "New transactions added");
+    }
+
+    if report.matched > 0 {
+        info!(count = report.matched, "Transaction pairss matched");
+    }
+
+    Ok(report)
+}
 
-        self.sheets_client
-            .write_sheet(&sheet, sheet_name, &all_transactions)
+/// Warn once about the transactions on every card that couldn't be converted into the base
+/// currency.
+pub fn warn_unconverted(cards: &[CardReport], converter: Option<&CurrencyConverter>) {
+    let count: usize = cards.iter().map(|card| card.unconverted).sum();
+    if count > 0 {
+        warn!(
+            count,
+            base = converter.map(CurrencyConverter::base),
+            "No exchange rate for transactions, so they weren't converted"
+        );
+    }
+}
+
+/// IDs of the fetched transactions old enough to be archived that are in their year's archive
+/// tab.
+async fn find_archived(
+    sheets_client: &(impl SheetOperations + Sync),
+    tab_name: &str,
+    transactions: &[Transaction],
+    cutoff: DateTime<Utc>,
+) -> Result<HashSet<String>> {
+    let years: BTreeSet<i32> = transactions
+        .iter()
+        .filter(|t| t.timestamp < cutoff)
+        .map(|t| t.timestamp.year())
+        .collect();
+
+    let mut ids = HashSet::new();
+    for year in years {
+        let archive = sheets_client
+            .read_archive(&archive_tab_name(tab_name, year))
             .await?;
+        ids.extend(archive.transactions.into_iter().map(|t| t.id));
+    }
+    Ok(ids)
+}
 
-        let new_transactions_count = all_transactions.len() - existing_transactions_count;
-        if new_transactions_count > 0 {
-            info!(count = new_transactions_count, "New transactions added");
-        }
+/// Transactions in the archive tabs that can share a statement with the card's transactions,
+/// leaving out any that are still in the card's tab.
+async fn read_archived(
+    sheets_client: &(impl SheetOperations + Sync),
+    tab_name: &str,
+    transactions: &[Transaction],
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<Transaction>> {
+    let Some(oldest) = transactions.iter().map(|t| t.timestamp).min() else {
+        return Ok(Vec::new());
+    };
+    // A statement is never longer than a month
+    let from = oldest
+        .checked_sub_months(Months::new(1))
+        .unwrap_or(oldest)
+        .year();
+
+    let ids: HashSet<&str> = transactions.iter().map(|t| t.id.as_str()).collect();
+    let mut archived = Vec::new();
+    for year in from..=cutoff.year() {
+        let archive = sheets_client
+            .read_archive(&archive_tab_name(tab_name, year))
+            .await?;
+        archived.extend(
+            archive
+                .transactions
+                .into_iter()
+                .filter(|t| !ids.contains(t.id.as_str())),
+        );
+    }
+    Ok(archived)
+}
 
-        let matches_count = matches.len();
-        if matches_count > 0 {
-            info!(count = matches_count, "Transaction pairss matched");
+/// Move the transactions that can be archived, with their extra columns, into the archive tab
+/// for their year, adding them to `archived`. Returns a change for each transaction moved.
+async fn archive_transactions(
+    sheets_client: &(impl SheetOperations + Sync),
+    tab_name: &str,
+    transactions: &mut Vec<Transaction>,
+    extra_columns: &ExtraColumns,
+    cutoff: DateTime<Utc>,
+    highlight: &HighlightColours,
+    archived: &mut Vec<Transaction>,
+) -> Result<Vec<Change>> {
+    let mut changes = Vec::new();
+    for (year, moved) in take_archivable(transactions, cutoff) {
+        let archive_tab = archive_tab_name(tab_name, year);
+        let mut archive = sheets_client.read_archive(&archive_tab).await?;
+        let count = moved.len();
+        for t in moved {
+            changes.push(Change {
+                transaction_id: t.id.clone(),
+                field: "Transaction".to_string(),
+                old_value: Some(tab_name.to_string()),
+                new_value: Some(archive_tab.clone()),
+                reason: ChangeReason::Archived,
+                rule: None,
+            });
+            archive.extra_columns.copy_row(extra_columns, &t.id);
+            // Replace any copy left by an earlier sync that failed to write the card's tab
+            archive.transactions.retain(|a| a.id != t.id);
+            archived.push(t.clone());
+            archive.transactions.push(t);
         }
+        archive
+            .transactions
+            .sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
 
-        Ok(())
+        sheets_client
+            .write_archive(&archive_tab, &archive, highlight)
+            .await?;
+        info!(count, tab = archive_tab, "Transactions archived");
+    }
+    Ok(changes)
+}
+
+/// Link both transactions of a pair to each other, returning the changes to their Matched ID.
+fn apply_match(
+    transactions: &mut [Transaction],
+    pair: &MatchedPair,
+    reason: ChangeReason,
+    rule: Option<&str>,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (id, matched_id) in [
+        (&pair.debit_id, &pair.credit_id),
+        (&pair.credit_id, &pair.debit_id),
+    ] {
+        let t = transactions
+            .iter_mut()
+            .find(|t| &t.id == id)
+            .expect("ids of a pair must exist in all_transactions");
+        if t.matched_id.as_ref() != Some(matched_id) {
+            changes.push(Change {
+                transaction_id: id.clone(),
+                field: "Matched ID".to_string(),
+                old_value: t.matched_id.clone(),
+                new_value: Some(matched_id.clone()),
+                reason,
+                rule: rule.map(str::to_string),
+            });
+        }
+        t.matched_id = Some(matched_id.clone());
+        t.match_confidence = Some(pair.confidence);
     }
+    changes
 }
 
 #[cfg(test)]
 mod mocks {
     use super::*;
     use crate::models::card::test_helpers::mock_card;
-    use crate::models::{Card, Transaction};
+    use crate::models::{Card, CardBalance, Transaction};
     use async_trait::async_trait;
     use chrono::Duration;
     use google_sheets4::api::{Sheet, SheetProperties};
+    use rust_decimal::Decimal;
+    use std::collections::HashMap;
     use std::sync::{Arc, Mutex};
 
     pub(crate) async fn sync_against_mocks(
         sheet_transactions: Vec<Transaction>,
         truelayer_transactions: Vec<Transaction>,
+    ) -> Result<MockSheetsClient> {
+        let sheet_data = SheetData {
+            transactions: sheet_transactions,
+            extra_columns: ExtraColumns::default(),
+        };
+        sync_data_against_mocks(sheet_data, truelayer_transactions, Rules::default(), None).await
+    }
+
+    pub(crate) async fn sync_data_against_mocks(
+        sheet_data: SheetData,
+        truelayer_transactions: Vec<Transaction>,
+        rules: Rules,
+        converter: Option<CurrencyConverter>,
     ) -> Result<MockSheetsClient> {
         let card = mock_card();
         let truelayer_client = MockTrueLayerClient {
-            cards: vec![card],
+            cards: vec![card.clone()],
             transactions: truelayer_transactions,
+            failing_cards: Vec::new(),
         };
         let sheets_client = MockSheetsClient {
-            sheet_transactions: Arc::new(Mutex::new(sheet_transactions)),
+            sheet_data: Arc::new(Mutex::new(sheet_data)),
             replaced_transactions: Arc::new(Mutex::new(Vec::new())),
+            replaced_extra_columns: Arc::new(Mutex::new(ExtraColumns::default())),
+            edited_sheet_data: Arc::new(Mutex::new(None)),
+            archives: Arc::new(Mutex::new(HashMap::new())),
         };
 
         let engine = SyncEngine::new(
-            SyncConfig::default(),
+            Config::default(),
+            rules,
+            converter,
             truelayer_client,
             sheets_client.clone(),
         );
+        let settings = engine.config.card_settings(&card);
         engine
-            .sync_card(&mock_card(), Utc::now() - Duration::days(30), Utc::now())
+            .sync_card(
+                &card,
+                &settings,
+                Utc::now() - Duration::days(30),
+                Utc::now(),
+                &mut ActionLog::default(),
+            )
             .await?;
         Ok(sheets_client)
     }
@@ -176,6 +710,8 @@ mod mocks {
     pub(crate) struct MockTrueLayerClient {
         pub cards: Vec<Card>,
         pub transactions: Vec<Transaction>,
+        /// IDs of cards whose transactions fail to be fetched
+        pub failing_cards: Vec<String>,
     }
 
     #[async_trait]
@@ -184,24 +720,51 @@ mod mocks {
             Ok(self.cards.clone())
         }
 
+        async fn get_card_balance(&self, _card_id: &str) -> Result<CardBalance> {
+            Ok(CardBalance {
+                current: Decimal::ZERO,
+                currency: "GBP".to_string(),
+                last_statement_date: None,
+                last_statement_balance: None,
+            })
+        }
+
+        async fn get_consent_expiry(&self) -> Result<Option<DateTime<Utc>>> {
+            Ok(None)
+        }
+
         async fn get_card_transactions(
             &self,
-            _card_id: &str,
+            card_id: &str,
             _from: DateTime<Utc>,
             _to: DateTime<Utc>,
         ) -> Result<Vec<Transaction>> {
+            if self.failing_cards.iter().any(|id| id == card_id) {
+                return Err(crate::error::AppError::TrueLayer(
+                    "Failed to get card transactions".to_string(),
+                ));
+            }
             Ok(self.transactions.clone())
         }
     }
 
     #[derive(Clone)]
     pub(crate) struct MockSheetsClient {
-        pub sheet_transactions: Arc<Mutex<Vec<Transaction>>>,
+        pub sheet_data: Arc<Mutex<SheetData>>,
         pub replaced_transactions: Arc<Mutex<Vec<Transaction>>>,
+        pub replaced_extra_columns: Arc<Mutex<ExtraColumns>>,
+        /// Sheet data after the sheet's first read, as if it was edited during the sync
+        pub edited_sheet_data: Arc<Mutex<Option<SheetData>>>,
+        /// Archive tabs, by name
+        pub archives: Arc<Mutex<HashMap<String, SheetData>>>,
     }
 
     #[async_trait]
     impl SheetOperations for MockSheetsClient {
+        async fn list_sheets(&self) -> Result<Vec<Sheet>> {
+            Ok(vec![self.ensure_sheet(&mock_card().name).await?])
+        }
+
         async fn ensure_sheet(&self, _sheet_name: &str) -> Result<Sheet> {
             Ok(Sheet {
                 properties: Some(SheetProperties {
@@ -212,18 +775,55 @@ mod mocks {
             })
         }
 
-        async fn read_sheet(&self, _sheet_name: &str) -> Result<Vec<Transaction>> {
-            Ok(self.sheet_transactions.lock().unwrap().clone())
+        async fn read_sheet(&self, _sheet: &Sheet, _sheet_name: &str) -> Result<SheetData> {
+            let mut sheet_data = self.sheet_data.lock().unwrap();
+            let data = sheet_data.clone();
+            if let Some(edited) = self.edited_sheet_data.lock().unwrap().take() {
+                *sheet_data = edited;
+            }
+            Ok(data)
         }
 
         async fn write_sheet(
             &self,
             _sheet: &Sheet,
             _sheet_name: &str,
-            transactions: &[Transaction],
+            data: &SheetData,
+            _highlight: &HighlightColours,
         ) -> Result<()> {
-            let mut replaced = self.replaced_transactions.lock().unwrap();
-            *replaced = transactions.to_vec();
+            *self.replaced_transactions.lock().unwrap() = data.transactions.clone();
+            *self.replaced_extra_columns.lock().unwrap() = data.extra_columns.clone();
+            Ok(())
+        }
+
+        async fn append_history(&self, _entries: &[AuditEntry]) -> Result<()> {
+            Ok(())
+        }
+
+        async fn read_archive(&self, sheet_name: &str) -> Result<SheetData> {
+            Ok(self
+                .archives
+                .lock()
+                .unwrap()
+                .get(sheet_name)
+                .cloned()
+                .unwrap_or_default())
+        }
+
+        async fn list_archives(&self) -> Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+
+        async fn write_archive(
+            &self,
+            sheet_name: &str,
+            data: &SheetData,
+            _highlight: &HighlightColours,
+        ) -> Result<()> {
+            self.archives
+                .lock()
+                .unwrap()
+                .insert(sheet_name.to_string(), data.clone());
             Ok(())
         }
     }
@@ -232,10 +832,28 @@ mod mocks {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::currency::ExchangeRates;
     use crate::models::transaction::TransactionType;
     use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
     use chrono::Duration;
+    use rust_decimal::Decimal;
     use rust_decimal::prelude::dec;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// Clear the merchants and ages that are set during sync, to compare with the input
+    /// transactions.
+    fn without_derived(transactions: &[Transaction]) -> Vec<Transaction> {
+        transactions
+            .iter()
+            .cloned()
+            .map(|t| Transaction {
+                merchant: None,
+                age_days: None,
+                ..t
+            })
+            .collect()
+    }
 
     #[tokio::test]
     async fn test_sync_updates_unmatched_transactions() {
@@ -262,10 +880,14 @@ mod tests {
         let final_transactions = mock_sheets_client.replaced_transactions.lock().unwrap();
 
         assert_eq!(
-            *final_transactions,
+            without_derived(&final_transactions),
             vec![tx_truelayer],
             "transactions that don't have matches should be updated with latest data from TrueLayer"
         );
+        assert!(
+            final_transactions[0].age_days.is_some_and(|age| age > 0),
+            "unmatched transactions should have an age"
+        );
     }
 
     #[tokio::test]
@@ -297,15 +919,17 @@ mod tests {
 
         let tx_sheet_matched = Transaction {
             matched_id: Some(tx_truelayer.id.clone()),
+            match_confidence: Some(Decimal::ONE),
             ..tx_sheet.clone()
         };
         let tx_truelayer_matched = Transaction {
             matched_id: Some(tx_sheet.id.clone()),
+            match_confidence: Some(Decimal::ONE),
             ..tx_truelayer.clone()
         };
 
         assert_eq!(
-            *final_transactions,
+            without_derived(&final_transactions),
             vec![tx_sheet_matched, tx_truelayer_matched],
             "historical transactions outside sync window should be preserved and matched with new transactions"
         );
@@ -343,12 +967,287 @@ mod tests {
 
         let final_transactions = mock_sheets_client.replaced_transactions.lock().unwrap();
         assert_eq!(
-            *final_transactions,
+            without_derived(&final_transactions),
             vec![tx_sheet_with_metadata, tx_truelayer],
             "existing matched_id and comments should be preserved during sync"
         );
     }
 
+    #[tokio::test]
+    async fn test_sync_preserves_extra_columns() {
+        let base_datetime = mock_datetime(2025, 1, 1);
+
+        let tx_sheet = mock_transaction(
+            "tx_sheet",
+            dec!(-10.0),
+            TransactionType::Debit,
+            base_datetime,
+        );
+        let tx_truelayer = mock_transaction(
+            "tx_truelayer",
+            dec!(-20.0),
+            TransactionType::Debit,
+            base_datetime,
+        );
+        let extra_columns = ExtraColumns {
+            headers: vec!["Who".to_string()],
+            values: HashMap::from([("tx_sheet".to_string(), vec![json!("Sam")])]),
+            ..Default::default()
+        };
+
+        let sheet_data = SheetData {
+            transactions: vec![tx_sheet.clone()],
+            extra_columns: extra_columns.clone(),
+        };
+        let truelayer_transactions = vec![tx_sheet, tx_truelayer];
+
+        let mock_sheets_client = mocks::sync_data_against_mocks(
+            sheet_data,
+            truelayer_transactions,
+            Rules::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let final_extra_columns = mock_sheets_client.replaced_extra_columns.lock().unwrap();
+        assert_eq!(
+            *final_extra_columns, extra_columns,
+            "user-added columns should be written back unchanged"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_applies_rules() {
+        let base_datetime = mock_datetime(2025, 1, 1);
+
+        let tx_typed = Transaction {
+            category: Some("Eating out".to_string()),
+            ..mock_transaction(
+                "tx_typed",
+                dec!(-10.0),
+                TransactionType::Debit,
+                base_datetime,
+            )
+        };
+        let tx_new = mock_transaction("tx_new", dec!(-20.0), TransactionType::Debit, base_datetime);
+        let rules = Rules::parse(
+            r#"
+            [[rule]]
+            name = "everything"
+            category = "Shopping"
+            "#,
+        )
+        .unwrap();
+
+        let sheet_data = SheetData {
+            transactions: vec![tx_typed.clone()],
+            extra_columns: ExtraColumns::default(),
+        };
+        let truelayer_transactions = vec![tx_typed.clone(), tx_new.clone()];
+
+        let mock_sheets_client =
+            mocks::sync_data_against_mocks(sheet_data, truelayer_transactions, rules, None)
+                .await
+                .unwrap();
+
+        let final_transactions = mock_sheets_client.replaced_transactions.lock().unwrap();
+        assert_eq!(
+            without_derived(&final_transactions)[1],
+            tx_typed,
+            "categories typed by hand should be kept"
+        );
+        assert_eq!(final_transactions[0].id, tx_new.id);
+        assert_eq!(final_transactions[0].category.as_deref(), Some("Shopping"));
+        assert_eq!(final_transactions[0].rule.as_deref(), Some("everything"));
+        assert_eq!(
+            final_transactions[0].merchant.as_deref(),
+            Some("Mock Transaction: Tx_new")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_applies_comment_and_pair_rules() {
+        let tx_debit = mock_transaction(
+            "tx_1",
+            dec!(-10.00),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 1),
+        );
+        let tx_credit = mock_transaction(
+            "tx_2",
+            dec!(9.50),
+            TransactionType::Credit,
+            mock_datetime(2025, 1, 3),
+        );
+        let rules = Rules::parse(
+            r#"
+            [[rule]]
+            name = "note"
+            description = "tx_1"
+            comment = "Split with Sam"
+
+            [[pair]]
+            name = "sam"
+            debit = "tx_1"
+            credit = "tx_2"
+            tolerance = "1.00"
+            "#,
+        )
+        .unwrap();
+
+        let mock_sheets_client = mocks::sync_data_against_mocks(
+            SheetData::default(),
+            vec![tx_debit, tx_credit],
+            rules,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let final_transactions = mock_sheets_client.replaced_transactions.lock().unwrap();
+        assert_eq!(
+            final_transactions[0].comments.as_deref(),
+            Some("Split with Sam")
+        );
+        assert_eq!(final_transactions[0].matched_id.as_deref(), Some("tx_2"));
+        assert_eq!(final_transactions[1].matched_id.as_deref(), Some("tx_1"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_converts_to_base_currency() {
+        let tx_usd = Transaction {
+            currency: "USD".to_string(),
+            ..mock_transaction(
+                "tx_usd",
+                dec!(-12.50),
+                TransactionType::Debit,
+                mock_datetime(2025, 1, 2),
+            )
+        };
+        let tx_unknown = Transaction {
+            currency: "JPY".to_string(),
+            ..mock_transaction(
+                "tx_unknown",
+                dec!(-1000),
+                TransactionType::Debit,
+                mock_datetime(2025, 1, 3),
+            )
+        };
+        let tx_gbp = mock_transaction(
+            "tx_gbp",
+            dec!(-5.00),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 4),
+        );
+
+        let rates =
+            ExchangeRates::from_csv("date,currency,rate\n2025-01-02,USD,1.25\n", "GBP").unwrap();
+        let converter = CurrencyConverter::new("GBP", rates);
+
+        let mock_sheets_client = mocks::sync_data_against_mocks(
+            SheetData::default(),
+            vec![tx_usd, tx_unknown, tx_gbp],
+            Rules::default(),
+            Some(converter),
+        )
+        .await
+        .unwrap();
+
+        let final_transactions = mock_sheets_client.replaced_transactions.lock().unwrap();
+        let converted: Vec<_> = final_transactions
+            .iter()
+            .map(|t| t.converted_amount)
+            .collect();
+        assert_eq!(converted, vec![Some(dec!(-10.00)), None, Some(dec!(-5.00))]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_counts_unconverted_transactions() {
+        use crate::models::card::test_helpers::mock_card;
+        use std::sync::{Arc, Mutex};
+
+        let jpy = |id, timestamp| Transaction {
+            currency: "JPY".to_string(),
+            ..mock_transaction(id, dec!(-1000), TransactionType::Debit, timestamp)
+        };
+        // Converted by an earlier sync, with rates from before the ones loaded now
+        let tx_old = Transaction {
+            converted_amount: Some(dec!(-5.25)),
+            ..jpy("tx_old", mock_datetime(2024, 1, 2))
+        };
+        let tx_new = jpy("tx_new", mock_datetime(2025, 1, 2));
+
+        let rates =
+            ExchangeRates::from_csv("date,currency,rate\n2025-01-02,USD,1.25\n", "GBP").unwrap();
+        let converter = CurrencyConverter::new("GBP", rates);
+        let card = mock_card();
+        let sheets_client = mocks::MockSheetsClient {
+            sheet_data: Arc::new(Mutex::new(SheetData {
+                transactions: vec![tx_old],
+                extra_columns: ExtraColumns::default(),
+            })),
+            replaced_transactions: Arc::new(Mutex::new(Vec::new())),
+            replaced_extra_columns: Arc::new(Mutex::new(ExtraColumns::default())),
+            edited_sheet_data: Arc::new(Mutex::new(None)),
+            archives: Arc::new(Mutex::new(HashMap::new())),
+        };
+        let report = merge_transactions(
+            &sheets_client,
+            &card,
+            &Config::default().card_settings(&card),
+            &Rules::default(),
+            Some(&converter),
+            &mut ActionLog::default(),
+            vec![tx_new],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.unconverted, 1);
+        let converted: Vec<_> = sheets_client
+            .replaced_transactions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|t| t.converted_amount)
+            .collect();
+        assert_eq!(
+            converted,
+            vec![Some(dec!(-5.25)), None],
+            "an amount converted before should be kept when there's no rate for it now"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_clears_converted_amounts_without_base_currency() {
+        let tx_converted = Transaction {
+            currency: "USD".to_string(),
+            converted_amount: Some(dec!(-10.00)),
+            ..mock_transaction(
+                "tx_usd",
+                dec!(-12.50),
+                TransactionType::Debit,
+                mock_datetime(2025, 1, 2),
+            )
+        };
+
+        let mock_sheets_client = mocks::sync_data_against_mocks(
+            SheetData {
+                transactions: vec![tx_converted.clone()],
+                extra_columns: ExtraColumns::default(),
+            },
+            vec![tx_converted],
+            Rules::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let final_transactions = mock_sheets_client.replaced_transactions.lock().unwrap();
+        assert_eq!(final_transactions[0].converted_amount, None);
+    }
+
     #[tokio::test]
     async fn test_sync_sorting_is_deterministic() {
         let base_datetime = mock_datetime(2025, 1, 1);
@@ -368,9 +1267,480 @@ mod tests {
         let final_transactions = mock_sheets_client.replaced_transactions.lock().unwrap();
 
         assert_eq!(
-            *final_transactions,
+            without_derived(&final_transactions),
             vec![tx_a, tx_b],
             "transactions with same timestamp should be sorted by ID"
         );
     }
+
+    #[tokio::test]
+    async fn test_sync_reports_each_card_and_continues_after_failures() {
+        use crate::models::card::test_helpers::mock_card;
+        use std::sync::{Arc, Mutex};
+
+        let base_datetime = mock_datetime(2025, 1, 1);
+        let tx_existing = mock_transaction(
+            "tx_existing",
+            dec!(-10.0),
+            TransactionType::Debit,
+            base_datetime,
+        );
+        let tx_updated = Transaction {
+            description: "Updated description".to_string(),
+            ..tx_existing.clone()
+        };
+        let tx_new = mock_transaction(
+            "tx_new",
+            dec!(-20.0),
+            TransactionType::Debit,
+            base_datetime + Duration::days(1),
+        );
+
+        let failing_card = Card {
+            id: "acc_failing".to_string(),
+            name: "Failing Card".to_string(),
+            ..mock_card()
+        };
+        let truelayer_client = mocks::MockTrueLayerClient {
+            cards: vec![failing_card.clone(), mock_card()],
+            transactions: vec![tx_updated, tx_new],
+            failing_cards: vec![failing_card.id.clone()],
+        };
+        let sheets_client = mocks::MockSheetsClient {
+            sheet_data: Arc::new(Mutex::new(SheetData {
+                transactions: vec![tx_existing],
+                extra_columns: ExtraColumns::default(),
+            })),
+            replaced_transactions: Arc::new(Mutex::new(Vec::new())),
+            replaced_extra_columns: Arc::new(Mutex::new(ExtraColumns::default())),
+            edited_sheet_data: Arc::new(Mutex::new(None)),
+            archives: Arc::new(Mutex::new(HashMap::new())),
+        };
+        let engine = SyncEngine::new(
+            Config::default(),
+            Rules::default(),
+            None,
+            truelayer_client,
+            sheets_client.clone(),
+        );
+
+        let mut report = SyncReport::default();
+        engine
+            .sync(
+                &mut ActionLog::default(),
+                &mut StatementDays::default(),
+                &mut report,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(report.cards.len(), 2);
+        assert_eq!(report.cards[0].card, "Failing Card");
+        assert_eq!(
+            report.cards[0].error.as_deref(),
+            Some("TrueLayer API error: Failed to get card transactions")
+        );
+        assert!(!report.is_success());
+
+        let card = &report.cards[1];
+        assert_eq!(card.card, "Amex Card");
+        assert_eq!(card.error, None);
+        assert_eq!(
+            (card.fetched, card.added, card.updated),
+            (2, 1, 1),
+            "the card after a failing card should still be synced"
+        );
+        assert_eq!(sheets_client.replaced_transactions.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_merge_records_changes() {
+        use crate::models::card::test_helpers::mock_card;
+        use std::sync::{Arc, Mutex};
+
+        let tx_existing = mock_transaction(
+            "tx_1",
+            dec!(-10.00),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 1),
+        );
+        let tx_updated = Transaction {
+            description: "Updated description".to_string(),
+            ..tx_existing.clone()
+        };
+        let tx_refund = mock_transaction(
+            "tx_2",
+            dec!(10.00),
+            TransactionType::Credit,
+            mock_datetime(2025, 1, 2),
+        );
+        let tx_coffee = mock_transaction(
+            "tx_3",
+            dec!(-3.50),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 3),
+        );
+        let rules = Rules::parse(
+            r#"
+            [[rule]]
+            name = "coffee"
+            description = "tx_3"
+            category = "Coffee"
+            "#,
+        )
+        .unwrap();
+
+        let sheets_client = mocks::MockSheetsClient {
+            sheet_data: Arc::new(Mutex::new(SheetData {
+                transactions: vec![tx_existing],
+                extra_columns: ExtraColumns::default(),
+            })),
+            replaced_transactions: Arc::new(Mutex::new(Vec::new())),
+            replaced_extra_columns: Arc::new(Mutex::new(ExtraColumns::default())),
+            edited_sheet_data: Arc::new(Mutex::new(None)),
+            archives: Arc::new(Mutex::new(HashMap::new())),
+        };
+        let card = mock_card();
+        let report = merge_transactions(
+            &sheets_client,
+            &card,
+            &Config::default().card_settings(&card),
+            &rules,
+            None,
+            &mut ActionLog::default(),
+            vec![tx_updated, tx_refund, tx_coffee],
+        )
+        .await
+        .unwrap();
+
+        let changes: Vec<(&str, &str, ChangeReason, Option<&str>)> = report
+            .changes
+            .iter()
+            .map(|change| {
+                (
+                    change.transaction_id.as_str(),
+                    change.field.as_str(),
+                    change.reason,
+                    change.rule.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("tx_1", "Description", ChangeReason::Updated, None),
+                ("tx_2", "Transaction", ChangeReason::Added, None),
+                ("tx_3", "Transaction", ChangeReason::Added, None),
+                ("tx_3", "Category", ChangeReason::Rule, Some("coffee")),
+                ("tx_3", "Rule", ChangeReason::Rule, Some("coffee")),
+                ("tx_1", "Matched ID", ChangeReason::AutoMatch, None),
+                ("tx_2", "Matched ID", ChangeReason::AutoMatch, None),
+            ]
+        );
+        assert_eq!(
+            report.changes[0].new_value.as_deref(),
+            Some("Updated description")
+        );
+        assert_eq!(report.changes[5].new_value.as_deref(), Some("tx_2"));
+    }
+
+    #[tokio::test]
+    async fn test_merge_keeps_edits_made_during_sync() {
+        use crate::models::card::test_helpers::mock_card;
+        use std::sync::{Arc, Mutex};
+
+        let tx_debit = mock_transaction(
+            "tx_1",
+            dec!(-10.00),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 1),
+        );
+        let tx_credit = mock_transaction(
+            "tx_2",
+            dec!(10.00),
+            TransactionType::Credit,
+            mock_datetime(2025, 1, 2),
+        );
+        let extra_columns = ExtraColumns {
+            headers: vec!["Notes".to_string()],
+            values: HashMap::from([("tx_1".to_string(), vec![json!("Cash")])]),
+            ..Default::default()
+        };
+
+        // While the sync runs, the debit is matched by hand and commented on
+        let sheets_client = mocks::MockSheetsClient {
+            sheet_data: Arc::new(Mutex::new(SheetData {
+                transactions: vec![tx_debit.clone(), tx_credit.clone()],
+                extra_columns: ExtraColumns::default(),
+            })),
+            replaced_transactions: Arc::new(Mutex::new(Vec::new())),
+            replaced_extra_columns: Arc::new(Mutex::new(ExtraColumns::default())),
+            edited_sheet_data: Arc::new(Mutex::new(Some(SheetData {
+                transactions: vec![
+                    Transaction {
+                        matched_id: Some("tx_9".to_string()),
+                        comments: Some("Paid back in cash".to_string()),
+                        ..tx_debit.clone()
+                    },
+                    tx_credit.clone(),
+                ],
+                extra_columns: extra_columns.clone(),
+            }))),
+            archives: Arc::new(Mutex::new(HashMap::new())),
+        };
+        let card = mock_card();
+        let report = merge_transactions(
+            &sheets_client,
+            &card,
+            &Config::default().card_settings(&card),
+            &Rules::default(),
+            None,
+            &mut ActionLog::default(),
+            vec![tx_debit, tx_credit],
+        )
+        .await
+        .unwrap();
+
+        let replaced = sheets_client.replaced_transactions.lock().unwrap();
+        assert_eq!(replaced[0].matched_id.as_deref(), Some("tx_9"));
+        assert_eq!(replaced[0].comments.as_deref(), Some("Paid back in cash"));
+        assert_eq!(replaced[0].age_days, None);
+        assert_eq!(
+            replaced[1].matched_id, None,
+            "the sync's match should be undone on the credit too"
+        );
+        assert!(replaced[1].age_days.is_some());
+        assert_eq!(
+            *sheets_client.replaced_extra_columns.lock().unwrap(),
+            extra_columns
+        );
+
+        assert_eq!(report.matched, 0);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].transaction_id, "tx_1");
+        assert_eq!(report.conflicts[0].field, "Matched ID");
+        assert_eq!(report.conflicts[0].ours.as_deref(), Some("tx_2"));
+        assert_eq!(report.conflicts[0].theirs.as_deref(), Some("tx_9"));
+        assert!(
+            !report
+                .changes
+                .iter()
+                .any(|change| change.field == "Matched ID"),
+            "the discarded match shouldn't be in the audit log"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_archives_old_matched_pairs() {
+        use crate::models::card::test_helpers::mock_card;
+        use std::sync::{Arc, Mutex};
+
+        let matched = |id: &str, matched_id: &str, amount, type_, timestamp| Transaction {
+            matched_id: Some(matched_id.to_string()),
+            ..mock_transaction(id, amount, type_, timestamp)
+        };
+        let tx_debit = matched(
+            "tx_1",
+            "tx_2",
+            dec!(-10.00),
+            TransactionType::Debit,
+            mock_datetime(2024, 3, 1),
+        );
+        let tx_credit = matched(
+            "tx_2",
+            "tx_1",
+            dec!(10.00),
+            TransactionType::Credit,
+            mock_datetime(2024, 3, 2),
+        );
+        let tx_recent = mock_transaction(
+            "tx_3",
+            dec!(-5.00),
+            TransactionType::Debit,
+            Utc::now() - Duration::days(3),
+        );
+        let tx_archived = matched(
+            "tx_0",
+            "tx_other",
+            dec!(-1.00),
+            TransactionType::Debit,
+            mock_datetime(2024, 1, 1),
+        );
+        // Matched by hand with a payment on another card, which this card can't archive with it
+        let tx_cross_card = matched(
+            "tx_4",
+            "tx_other_card",
+            dec!(-20.00),
+            TransactionType::Debit,
+            mock_datetime(2024, 2, 1),
+        );
+
+        let card = mock_card();
+        let settings = CardSettings {
+            archive_after_months: Some(12),
+            ..Config::default().card_settings(&card)
+        };
+        let archive_tab = format!("{} (2024)", settings.tab_name);
+        let sheets_client = mocks::MockSheetsClient {
+            sheet_data: Arc::new(Mutex::new(SheetData {
+                transactions: vec![
+                    tx_debit.clone(),
+                    tx_credit.clone(),
+                    tx_recent.clone(),
+                    tx_cross_card.clone(),
+                ],
+                extra_columns: ExtraColumns {
+                    headers: vec!["Notes".to_string()],
+                    values: HashMap::from([("tx_1".to_string(), vec![json!("Taxi")])]),
+                    ..Default::default()
+                },
+            })),
+            replaced_transactions: Arc::new(Mutex::new(Vec::new())),
+            replaced_extra_columns: Arc::new(Mutex::new(ExtraColumns::default())),
+            edited_sheet_data: Arc::new(Mutex::new(None)),
+            archives: Arc::new(Mutex::new(HashMap::from([(
+                archive_tab.clone(),
+                SheetData {
+                    transactions: vec![tx_archived.clone()],
+                    extra_columns: ExtraColumns::default(),
+                },
+            )]))),
+        };
+        let report = merge_transactions(
+            &sheets_client,
+            &card,
+            &settings,
+            &Rules::default(),
+            None,
+            &mut ActionLog::default(),
+            vec![tx_archived.clone(), tx_recent.clone()],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            without_derived(&sheets_client.replaced_transactions.lock().unwrap()),
+            vec![tx_cross_card, tx_recent],
+            "the archived transaction fetched again shouldn't be added back, and the one matched \
+             on another card should be kept"
+        );
+        let archives = sheets_client.archives.lock().unwrap();
+        let archive = &archives[&archive_tab];
+        assert_eq!(
+            archive
+                .transactions
+                .iter()
+                .map(|t| t.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["tx_0", "tx_1", "tx_2"]
+        );
+        assert_eq!(
+            archive.extra_columns.values.get("tx_1"),
+            Some(&vec![json!("Taxi")])
+        );
+
+        assert_eq!(report.added, 0);
+        assert_eq!(report.archived, 2);
+        assert!(report.changes.iter().any(|change| {
+            change.transaction_id == "tx_1"
+                && change.reason == ChangeReason::Archived
+                && change.new_value.as_deref() == Some(archive_tab.as_str())
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_merge_totals_statements_with_archived_transactions() {
+        use crate::models::card::test_helpers::mock_card;
+        use std::sync::{Arc, Mutex};
+
+        let matched = |id: &str, matched_id: &str, amount, type_, timestamp| Transaction {
+            matched_id: Some(matched_id.to_string()),
+            ..mock_transaction(id, amount, type_, timestamp)
+        };
+        // Archived by an earlier sync
+        let tx_archived = matched(
+            "tx_0",
+            "tx_other",
+            dec!(-1.00),
+            TransactionType::Debit,
+            mock_datetime(2024, 3, 1),
+        );
+        // Archived by this sync, along with the statement's total
+        let tx_debit = matched(
+            "tx_1",
+            "tx_2",
+            dec!(-10.00),
+            TransactionType::Debit,
+            mock_datetime(2024, 3, 2),
+        );
+        let tx_credit = matched(
+            "tx_2",
+            "tx_1",
+            dec!(10.00),
+            TransactionType::Credit,
+            mock_datetime(2024, 3, 14),
+        );
+        let tx_unmatched = mock_transaction(
+            "tx_3",
+            dec!(-5.00),
+            TransactionType::Debit,
+            mock_datetime(2024, 3, 10),
+        );
+
+        let card = mock_card();
+        let settings = CardSettings {
+            archive_after_months: Some(12),
+            statement_day: Some(15),
+            ..Config::default().card_settings(&card)
+        };
+        let archive_tab = format!("{} (2024)", settings.tab_name);
+        let sheets_client = mocks::MockSheetsClient {
+            sheet_data: Arc::new(Mutex::new(SheetData {
+                transactions: vec![tx_debit, tx_unmatched, tx_credit],
+                extra_columns: ExtraColumns::default(),
+            })),
+            replaced_transactions: Arc::new(Mutex::new(Vec::new())),
+            replaced_extra_columns: Arc::new(Mutex::new(ExtraColumns::default())),
+            edited_sheet_data: Arc::new(Mutex::new(None)),
+            archives: Arc::new(Mutex::new(HashMap::from([(
+                archive_tab.clone(),
+                SheetData {
+                    transactions: vec![tx_archived],
+                    extra_columns: ExtraColumns::default(),
+                },
+            )]))),
+        };
+        merge_transactions(
+            &sheets_client,
+            &card,
+            &settings,
+            &Rules::default(),
+            None,
+            &mut ActionLog::default(),
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+
+        let written = sheets_client.replaced_transactions.lock().unwrap();
+        assert_eq!(
+            written
+                .iter()
+                .map(|t| (t.id.as_str(), t.statement_total))
+                .collect::<Vec<_>>(),
+            vec![("tx_3", Some(dec!(-6.00)))],
+            "the statement's total should include the archived transactions, on the transaction \
+             left in the card's tab"
+        );
+        let archives = sheets_client.archives.lock().unwrap();
+        let archive_totals: Vec<_> = archives[&archive_tab]
+            .transactions
+            .iter()
+            .map(|t| (t.id.as_str(), t.statement_total))
+            .collect();
+        assert_eq!(
+            archive_totals,
+            vec![("tx_0", None), ("tx_1", None), ("tx_2", Some(dec!(-6.00)))]
+        );
+    }
 }
@@ -1,4 +1,9 @@
+pub mod aging;
+pub mod archive;
+pub mod audit;
+pub mod conflicts;
 pub mod engine;
 pub mod reconcile;
+pub mod report;
 
-pub use engine::SyncEngine;
+pub use engine::{SyncEngine, merge_transactions, warn_unconverted};
@@ -1,7 +1,9 @@
+use crate::config::{ReconcileStrategy, Tolerance};
 use crate::models::transaction::{Transaction, TransactionType};
 use chrono::Duration;
 use rust_decimal::Decimal;
-use std::collections::HashMap;
+use rust_decimal::prelude::dec;
+use std::collections::{HashMap, HashSet};
 use tracing::instrument;
 
 /// Represents a matched pair of transactions (Debit <-> Credit)
@@ -9,85 +11,192 @@ use tracing::instrument;
 pub struct MatchedPair {
     pub debit_id: String,
     pub credit_id: String,
+    /// 1 when the amounts are equal, down to 0.5 at the edge of the tolerance
+    pub confidence: Decimal,
 }
 
-/// Reconciles transactions by matching Debits and Credits with identical amounts
-/// within a configurable time window.
+/// Reconciles transactions by matching Debits and Credits within a configurable time window,
+/// whose amounts are equal or within the tolerance.
+///
+/// Transactions in the same currency are compared by amount, and those in different currencies
+/// by their converted amounts in the base currency, if both have one. Exact matches in the same
+/// currency are made first, grouped by amount, so that an approximate match can't take a credit
+/// that exactly matches another debit. Only then are the rest compared with the tolerance and
+/// across currencies, with the credits within the window of each debit.
 #[instrument(name = "Reconciling transactions", skip_all)]
-pub fn reconcile_transactions(transactions: &[Transaction], window: Duration) -> Vec<MatchedPair> {
-    // Identify candidates (unmatched)
-    let candidates: Vec<&Transaction> = transactions
+pub fn reconcile_transactions(
+    transactions: &[Transaction],
+    window: Duration,
+    strategy: ReconcileStrategy,
+    tolerance: Tolerance,
+) -> Vec<MatchedPair> {
+    if strategy == ReconcileStrategy::None {
+        return Vec::new();
+    }
+
+    // Candidates are the unmatched transactions, sorted by timestamp to ensure we match the
+    // earliest possible pairs
+    let mut group: Vec<&Transaction> = transactions
         .iter()
         .filter(|t| t.matched_id.is_none())
         .collect();
-
-    // Group candidates by absolute amount
-    let mut by_amount: HashMap<Decimal, Vec<&Transaction>> = HashMap::new();
-    for t in candidates {
-        let key = t.amount.abs();
-        by_amount.entry(key).or_default().push(t);
+    group.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
+
+    // Group candidates by currency and absolute amount, keeping them sorted
+    let mut by_amount: HashMap<(&str, Decimal), Vec<usize>> = HashMap::new();
+    for (idx, t) in group.iter().enumerate() {
+        by_amount
+            .entry((t.currency.as_str(), t.amount.abs()))
+            .or_default()
+            .push(idx);
     }
 
     let mut matches = Vec::new();
+    let mut matched = HashSet::new();
+    // Sorted by the first candidate, so that the matches don't depend on the hash order
+    let mut exact_groups: Vec<Vec<usize>> = by_amount.into_values().collect();
+    exact_groups.sort();
+    for candidates in &exact_groups {
+        match_group(
+            &group,
+            candidates,
+            window,
+            strategy,
+            Tolerance::Exact,
+            &mut matched,
+            &mut matches,
+        );
+    }
 
-    for (_, mut group) in by_amount {
-        // Sort group by timestamp to ensure we match the earliest possible pairs
-        group.sort_by_key(|t| t.timestamp);
+    let multiple_currencies = group.iter().any(|t| t.currency != group[0].currency);
+    if tolerance != Tolerance::Exact || multiple_currencies {
+        let candidates: Vec<usize> = (0..group.len()).collect();
+        match_group(
+            &group,
+            &candidates,
+            window,
+            strategy,
+            tolerance,
+            &mut matched,
+            &mut matches,
+        );
+    }
 
-        let mut matched_indexes = vec![false; group.len()];
+    matches
+}
 
-        for i in 0..group.len() {
-            if matched_indexes[i] {
+/// The amounts of a debit and credit in a currency that they can be compared in: their own if
+/// it's the same, or else the base currency that they were converted to.
+fn comparable_amounts(debit: &Transaction, credit: &Transaction) -> Option<(Decimal, Decimal)> {
+    if debit.currency == credit.currency {
+        return Some((debit.amount, credit.amount));
+    }
+
+    Some((debit.converted_amount?, credit.converted_amount?))
+}
+
+/// Match the debits among the `candidates`, indexes of a group of transactions sorted by
+/// timestamp, skipping any that are already in `matched`.
+fn match_group(
+    group: &[&Transaction],
+    candidates: &[usize],
+    window: Duration,
+    strategy: ReconcileStrategy,
+    tolerance: Tolerance,
+    matched: &mut HashSet<usize>,
+    matches: &mut Vec<MatchedPair>,
+) {
+    for &i in candidates {
+        if matched.contains(&i) {
+            continue;
+        }
+
+        let tx_a = group[i];
+
+        // We only trigger matching from Debits to avoid double counting
+        if tx_a.type_ != TransactionType::Debit {
+            continue;
+        }
+
+        // Find matching Credit among the candidates within the date window
+        let start = candidates.partition_point(|&j| group[j].timestamp < tx_a.timestamp - window);
+        let end = candidates.partition_point(|&j| group[j].timestamp <= tx_a.timestamp + window);
+        let mut candidate: Option<(usize, Duration, Decimal)> = None;
+        for &j in &candidates[start..end] {
+            if i == j || matched.contains(&j) {
                 continue;
             }
 
-            let tx_a = group[i];
+            let tx_b = group[j];
 
-            // We only trigger matching from Debits to avoid double counting
-            if tx_a.type_ != TransactionType::Debit {
+            if tx_b.type_ != TransactionType::Credit {
                 continue;
             }
 
-            // Find matching Credit in the group.
-            for j in 0..group.len() {
-                if i == j || matched_indexes[j] {
-                    continue;
-                }
+            let Some((debit_amount, credit_amount)) = comparable_amounts(tx_a, tx_b) else {
+                continue;
+            };
+            let allowance = tolerance.allowance(debit_amount);
+            if (debit_amount + credit_amount).abs() > allowance {
+                continue;
+            }
+            let confidence = match_confidence(debit_amount, credit_amount, allowance);
 
-                let tx_b = group[j];
+            // Prevent self-match
+            if tx_a.id == tx_b.id {
+                continue;
+            }
 
-                if tx_b.type_ != TransactionType::Credit {
-                    continue;
-                }
+            let diff = tx_b.timestamp.signed_duration_since(tx_a.timestamp).abs();
 
-                // Explicitly check amount to guard against hash collisions
-                if tx_a.amount + tx_b.amount != Decimal::ZERO {
-                    continue;
-                }
+            if strategy == ReconcileStrategy::Merchant && !same_merchant(tx_a, tx_b) {
+                continue;
+            }
 
-                // Prevent self-match
-                if tx_a.id == tx_b.id {
-                    continue;
+            match strategy {
+                ReconcileStrategy::Nearest => {
+                    if candidate.is_none_or(|(_, best, _)| diff < best) {
+                        candidate = Some((j, diff, confidence));
+                    }
                 }
-
-                // Check date window
-                let diff = tx_b.timestamp.signed_duration_since(tx_a.timestamp);
-                if diff.abs() <= window {
-                    matched_indexes[i] = true;
-                    matched_indexes[j] = true;
-
-                    matches.push(MatchedPair {
-                        debit_id: tx_a.id.clone(),
-                        credit_id: tx_b.id.clone(),
-                    });
-
-                    break; // Proceed to next Debit.
+                _ => {
+                    candidate = Some((j, diff, confidence));
+                    break; // Group is sorted, so this is the earliest.
                 }
             }
         }
+
+        if let Some((j, _, confidence)) = candidate {
+            matched.insert(i);
+            matched.insert(j);
+
+            matches.push(MatchedPair {
+                debit_id: tx_a.id.clone(),
+                credit_id: group[j].id.clone(),
+                confidence,
+            });
+        }
     }
+}
 
-    matches
+/// Score how closely a debit and credit's amounts agree, given the largest difference allowed.
+pub(crate) fn match_confidence(debit: Decimal, credit: Decimal, allowance: Decimal) -> Decimal {
+    let difference = (debit + credit).abs();
+    if difference.is_zero() || allowance.is_zero() {
+        return Decimal::ONE;
+    }
+
+    (Decimal::ONE - difference / allowance * dec!(0.5))
+        .max(dec!(0.5))
+        .round_dp(2)
+}
+
+/// Whether both transactions have the same merchant, ignoring case.
+fn same_merchant(a: &Transaction, b: &Transaction) -> bool {
+    match (&a.merchant, &b.merchant) {
+        (Some(a), Some(b)) => a.to_lowercase() == b.to_lowercase(),
+        _ => false,
+    }
 }
 
 #[cfg(test)]
@@ -116,10 +225,16 @@ mod tests {
         );
 
         let input = vec![tx_debit, tx_credit];
-        let matches = reconcile_transactions(&input, Duration::days(TEST_RECONCILE_DAYS));
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Exact,
+        );
         let expected = vec![MatchedPair {
             debit_id: "tx_debit".to_string(),
             credit_id: "tx_credit".to_string(),
+            confidence: Decimal::ONE,
         }];
         assert_eq!(matches, expected);
     }
@@ -140,10 +255,16 @@ mod tests {
         );
 
         let input = vec![tx_credit, tx_debit];
-        let matches = reconcile_transactions(&input, Duration::days(TEST_RECONCILE_DAYS));
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Exact,
+        );
         let expected = vec![MatchedPair {
             debit_id: "debit_id_1".to_string(),
             credit_id: "credit_id_1".to_string(),
+            confidence: Decimal::ONE,
         }];
         assert_eq!(matches, expected);
     }
@@ -170,14 +291,110 @@ mod tests {
         );
 
         let input = vec![tx_debit, tx_other, tx_credit];
-        let matches = reconcile_transactions(&input, Duration::days(TEST_RECONCILE_DAYS));
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Exact,
+        );
         let expected = vec![MatchedPair {
             debit_id: "tx_debit".to_string(),
             credit_id: "tx3".to_string(),
+            confidence: Decimal::ONE,
         }];
         assert_eq!(matches, expected);
     }
 
+    #[test]
+    fn test_reconcile_nearest_candidate_by_date() {
+        let tx_debit = mock_transaction(
+            "tx_debit",
+            dec!(-25.0),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 10),
+        );
+        let tx_earliest = mock_transaction(
+            "tx_earliest",
+            dec!(25.0),
+            TransactionType::Credit,
+            tx_debit.timestamp - Duration::days(5),
+        );
+        let tx_nearest = mock_transaction(
+            "tx_nearest",
+            dec!(25.0),
+            TransactionType::Credit,
+            tx_debit.timestamp + Duration::days(2),
+        );
+
+        let input = vec![tx_debit, tx_earliest, tx_nearest];
+
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Exact,
+        );
+        assert_eq!(matches[0].credit_id, "tx_earliest");
+
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Nearest,
+            Tolerance::Exact,
+        );
+        assert_eq!(matches[0].credit_id, "tx_nearest");
+
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::None,
+            Tolerance::Exact,
+        );
+        assert_eq!(matches, vec![]);
+    }
+
+    #[test]
+    fn test_reconcile_same_merchant() {
+        let merchant = |name: &str| Some(name.to_string());
+        let tx_debit = Transaction {
+            merchant: merchant("Amazon"),
+            ..mock_transaction(
+                "tx_debit",
+                dec!(-25.0),
+                TransactionType::Debit,
+                mock_datetime(2025, 1, 10),
+            )
+        };
+        let tx_other = Transaction {
+            merchant: merchant("Tesco"),
+            ..mock_transaction(
+                "tx_other",
+                dec!(25.0),
+                TransactionType::Credit,
+                tx_debit.timestamp + Duration::days(1),
+            )
+        };
+        let tx_refund = Transaction {
+            merchant: merchant("AMAZON"),
+            ..mock_transaction(
+                "tx_refund",
+                dec!(25.0),
+                TransactionType::Credit,
+                tx_debit.timestamp + Duration::days(5),
+            )
+        };
+
+        let input = vec![tx_debit, tx_other, tx_refund];
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Merchant,
+            Tolerance::Exact,
+        );
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].credit_id, "tx_refund");
+    }
+
     #[test]
     fn test_reconcile_ignores_already_matched() {
         let mut tx_debit = mock_transaction(
@@ -195,7 +412,12 @@ mod tests {
         );
 
         let input = vec![tx_debit, tx_credit];
-        let matches = reconcile_transactions(&input, Duration::days(TEST_RECONCILE_DAYS));
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Exact,
+        );
         assert_eq!(matches, vec![]);
     }
 
@@ -215,7 +437,12 @@ mod tests {
         );
 
         let input = vec![tx_debit, tx_credit];
-        let matches = reconcile_transactions(&input, Duration::days(TEST_RECONCILE_DAYS));
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Exact,
+        );
         assert_eq!(matches, vec![]);
     }
 
@@ -235,10 +462,258 @@ mod tests {
         );
 
         let input = vec![tx_debit, tx_credit];
-        let matches = reconcile_transactions(&input, Duration::days(TEST_RECONCILE_DAYS));
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Exact,
+        );
+        assert_eq!(matches, vec![]);
+    }
+
+    #[test]
+    fn test_reconcile_within_tolerance() {
+        let tx_debit = mock_transaction(
+            "tx_debit",
+            dec!(-50.00),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 1),
+        );
+        let tx_refund = mock_transaction(
+            "tx_refund",
+            dec!(49.50),
+            TransactionType::Credit,
+            tx_debit.timestamp + Duration::days(1),
+        );
+
+        let input = vec![tx_debit, tx_refund];
+        let window = Duration::days(TEST_RECONCILE_DAYS);
+
+        let matches = reconcile_transactions(
+            &input,
+            window,
+            ReconcileStrategy::Earliest,
+            Tolerance::Absolute(dec!(1.00)),
+        );
+        assert_eq!(
+            matches,
+            vec![MatchedPair {
+                debit_id: "tx_debit".to_string(),
+                credit_id: "tx_refund".to_string(),
+                confidence: dec!(0.75),
+            }]
+        );
+
+        let matches = reconcile_transactions(
+            &input,
+            window,
+            ReconcileStrategy::Earliest,
+            Tolerance::Percent(dec!(2)),
+        );
+        assert_eq!(matches[0].confidence, dec!(0.75));
+
+        let matches = reconcile_transactions(
+            &input,
+            window,
+            ReconcileStrategy::Earliest,
+            Tolerance::Percent(dec!(0.5)),
+        );
+        assert_eq!(matches, vec![]);
+    }
+
+    #[test]
+    fn test_reconcile_prefers_exact_matches() {
+        let tx_debit_1 = mock_transaction(
+            "tx_debit_1",
+            dec!(-50.00),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 1),
+        );
+        let tx_debit_2 = mock_transaction(
+            "tx_debit_2",
+            dec!(-49.80),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 2),
+        );
+        let tx_credit = mock_transaction(
+            "tx_credit",
+            dec!(49.80),
+            TransactionType::Credit,
+            mock_datetime(2025, 1, 3),
+        );
+
+        let input = vec![tx_debit_1, tx_debit_2, tx_credit];
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Absolute(dec!(1.00)),
+        );
+        assert_eq!(
+            matches,
+            vec![MatchedPair {
+                debit_id: "tx_debit_2".to_string(),
+                credit_id: "tx_credit".to_string(),
+                confidence: Decimal::ONE,
+            }],
+            "an approximate match shouldn't take a credit that matches another debit exactly"
+        );
+    }
+
+    #[test]
+    fn test_reconcile_ignores_other_currencies() {
+        let tx_debit = mock_transaction(
+            "tx_debit",
+            dec!(-50.0),
+            TransactionType::Debit,
+            mock_datetime(2025, 1, 1),
+        );
+        let tx_credit = Transaction {
+            currency: "USD".to_string(),
+            ..mock_transaction(
+                "tx_credit",
+                dec!(50.0),
+                TransactionType::Credit,
+                tx_debit.timestamp + Duration::days(1),
+            )
+        };
+
+        let input = vec![tx_debit, tx_credit];
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Absolute(dec!(1.00)),
+        );
         assert_eq!(matches, vec![]);
     }
 
+    #[test]
+    fn test_reconcile_converted_amounts_across_currencies() {
+        let tx_debit = Transaction {
+            currency: "EUR".to_string(),
+            converted_amount: Some(dec!(-42.50)),
+            ..mock_transaction(
+                "tx_debit",
+                dec!(-50.0),
+                TransactionType::Debit,
+                mock_datetime(2025, 1, 1),
+            )
+        };
+        // Refunded in the base currency at a later exchange rate
+        let tx_refund = Transaction {
+            converted_amount: Some(dec!(42.00)),
+            ..mock_transaction(
+                "tx_refund",
+                dec!(42.00),
+                TransactionType::Credit,
+                tx_debit.timestamp + Duration::days(5),
+            )
+        };
+
+        let input = vec![tx_debit, tx_refund];
+        let window = Duration::days(TEST_RECONCILE_DAYS);
+        let matches = reconcile_transactions(
+            &input,
+            window,
+            ReconcileStrategy::Earliest,
+            Tolerance::Absolute(dec!(1.00)),
+        );
+        assert_eq!(
+            matches,
+            vec![MatchedPair {
+                debit_id: "tx_debit".to_string(),
+                credit_id: "tx_refund".to_string(),
+                confidence: dec!(0.75),
+            }]
+        );
+
+        let matches = reconcile_transactions(
+            &input,
+            window,
+            ReconcileStrategy::Earliest,
+            Tolerance::Exact,
+        );
+        assert_eq!(matches, vec![], "the converted amounts differ");
+    }
+
+    #[test]
+    fn test_reconcile_within_tolerance_ignores_outside_window() {
+        let tx_debit = mock_transaction(
+            "tx_debit",
+            dec!(-50.00),
+            TransactionType::Debit,
+            mock_datetime(2025, 3, 1),
+        );
+        let tx_before = mock_transaction(
+            "tx_before",
+            dec!(49.90),
+            TransactionType::Credit,
+            tx_debit.timestamp - Duration::days(61),
+        );
+        let tx_after = mock_transaction(
+            "tx_after",
+            dec!(49.80),
+            TransactionType::Credit,
+            tx_debit.timestamp + Duration::days(60),
+        );
+
+        let input = vec![tx_before, tx_debit, tx_after];
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Absolute(dec!(1.00)),
+        );
+        assert_eq!(
+            matches
+                .iter()
+                .map(|pair| pair.credit_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["tx_after"],
+            "the window should be inclusive, and an earlier credit outside it ignored"
+        );
+    }
+
+    #[test]
+    fn test_reconcile_equal_converted_amounts_without_tolerance() {
+        let tx_debit = Transaction {
+            currency: "EUR".to_string(),
+            converted_amount: Some(dec!(-42.50)),
+            ..mock_transaction(
+                "tx_debit",
+                dec!(-50.0),
+                TransactionType::Debit,
+                mock_datetime(2025, 1, 1),
+            )
+        };
+        let tx_refund = Transaction {
+            converted_amount: Some(dec!(42.50)),
+            ..mock_transaction(
+                "tx_refund",
+                dec!(42.50),
+                TransactionType::Credit,
+                tx_debit.timestamp + Duration::days(5),
+            )
+        };
+
+        let input = vec![tx_debit, tx_refund];
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Exact,
+        );
+        assert_eq!(
+            matches,
+            vec![MatchedPair {
+                debit_id: "tx_debit".to_string(),
+                credit_id: "tx_refund".to_string(),
+                confidence: Decimal::ONE,
+            }]
+        );
+    }
+
     #[test]
     fn test_reconcile_multiple() {
         let base_date = mock_datetime(2025, 1, 1);
@@ -309,19 +784,27 @@ mod tests {
             tx3_credit,
         ];
 
-        let matches = reconcile_transactions(&input, Duration::days(TEST_RECONCILE_DAYS));
+        let matches = reconcile_transactions(
+            &input,
+            Duration::days(TEST_RECONCILE_DAYS),
+            ReconcileStrategy::Earliest,
+            Tolerance::Exact,
+        );
         let expected = vec![
             MatchedPair {
                 debit_id: "tx1_debit".to_string(),
                 credit_id: "tx1_credit".to_string(),
+                confidence: Decimal::ONE,
             },
             MatchedPair {
                 debit_id: "tx2_debit".to_string(),
                 credit_id: "tx2_credit".to_string(),
+                confidence: Decimal::ONE,
             },
             MatchedPair {
                 debit_id: "tx3_debit".to_string(),
                 credit_id: "tx3_credit".to_string(),
+                confidence: Decimal::ONE,
             },
         ];
 
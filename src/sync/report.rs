@@ -0,0 +1,152 @@
+use crate::models::Transaction;
+use crate::models::audit::Change;
+use crate::models::transaction::TransactionType;
+use crate::sync::aging::AgeAlert;
+use crate::sync::conflicts::Conflict;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// What a sync did on each card, for notifications and `sync --output json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SyncReport {
+    /// ID of the sync in the audit log
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub run_id: String,
+    pub cards: Vec<CardReport>,
+    /// Errors that stopped the whole sync, rather than a single card
+    pub errors: Vec<String>,
+    /// When the TrueLayer consent expires, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consent_expires_at: Option<DateTime<Utc>>,
+}
+
+impl SyncReport {
+    pub fn added(&self) -> usize {
+        self.cards.iter().map(|card| card.added).sum()
+    }
+
+    pub fn matched(&self) -> usize {
+        self.cards.iter().map(|card| card.matched).sum()
+    }
+
+    /// Cards that failed to sync.
+    pub fn failed_cards(&self) -> impl Iterator<Item = &CardReport> {
+        self.cards.iter().filter(|card| card.error.is_some())
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.errors.is_empty() && self.failed_cards().next().is_none()
+    }
+}
+
+/// What a sync or import did on one card's sheet.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CardReport {
+    /// Sheet tab of the card
+    pub card: String,
+    /// Transactions fetched from the provider or read from the imported file
+    pub fetched: usize,
+    /// Transactions that weren't in the sheet before
+    pub added: usize,
+    /// Transactions already in the sheet whose details have changed
+    pub updated: usize,
+    /// Pairs of transactions matched
+    pub matched: usize,
+    /// Matched transactions moved to an archive tab
+    pub archived: usize,
+    /// Transactions that couldn't be converted into the base currency
+    pub unconverted: usize,
+    /// Debits that haven't been matched
+    pub unmatched_count: usize,
+    /// Total of the unmatched debits, which is negative
+    pub unmatched_total: Decimal,
+    /// Debits added by the sync that haven't been matched
+    pub new_unmatched_count: usize,
+    /// Total of the new unmatched debits, which is negative
+    pub new_unmatched_total: Decimal,
+    /// Unmatched transactions older than the card's `age_warning_days`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overdue: Option<AgeAlert>,
+    /// Error that stopped the card from syncing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    /// Edits made in the sheet during the sync that conflicted with its changes, and were kept
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<Conflict>,
+    /// Changes made to the sheet, for the audit log
+    #[serde(skip)]
+    pub changes: Vec<Change>,
+}
+
+impl CardReport {
+    /// Count the unmatched debits, and those of them in `new_ids`, which the sync added.
+    pub fn new(card: &str, transactions: &[Transaction], new_ids: &HashSet<String>) -> Self {
+        let unmatched: Vec<&Transaction> = transactions
+            .iter()
+            .filter(|t| t.type_ == TransactionType::Debit && t.matched_id.is_none())
+            .collect();
+        let new_unmatched: Vec<&Transaction> = unmatched
+            .iter()
+            .copied()
+            .filter(|t| new_ids.contains(&t.id))
+            .collect();
+
+        Self {
+            card: card.to_string(),
+            unmatched_count: unmatched.len(),
+            unmatched_total: unmatched.iter().map(|t| t.amount).sum(),
+            new_unmatched_count: new_unmatched.len(),
+            new_unmatched_total: new_unmatched.iter().map(|t| t.amount).sum(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transaction::test_helpers::{mock_datetime, mock_transaction};
+    use rust_decimal::prelude::dec;
+
+    #[test]
+    fn test_card_report_counts_unmatched_debits() {
+        let transactions = vec![
+            mock_transaction(
+                "tx_1",
+                dec!(-10.00),
+                TransactionType::Debit,
+                mock_datetime(2025, 1, 1),
+            ),
+            Transaction {
+                matched_id: Some("tx_3".to_string()),
+                ..mock_transaction(
+                    "tx_2",
+                    dec!(-20.00),
+                    TransactionType::Debit,
+                    mock_datetime(2025, 1, 2),
+                )
+            },
+            mock_transaction(
+                "tx_4",
+                dec!(-5.50),
+                TransactionType::Debit,
+                mock_datetime(2025, 1, 3),
+            ),
+            mock_transaction(
+                "tx_5",
+                dec!(7.00),
+                TransactionType::Credit,
+                mock_datetime(2025, 1, 4),
+            ),
+        ];
+
+        let report = CardReport::new("Amex", &transactions, &HashSet::from(["tx_4".to_string()]));
+        assert_eq!(report.unmatched_count, 2);
+        assert_eq!(report.unmatched_total, dec!(-15.50));
+        assert_eq!(report.new_unmatched_count, 1);
+        assert_eq!(report.new_unmatched_total, dec!(-5.50));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::config::TrueLayerConfig;
 use crate::error::{AppError, Result};
+use crate::metrics;
 use oauth2::{
     AuthUrl, AuthorizationCode, Client, ClientId, ClientSecret, CsrfToken, EndpointNotSet,
     EndpointSet, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RefreshToken, Scope,
@@ -287,10 +288,12 @@ impl TrueLayerAuth {
 
         match self.refresh_access_token(&tokens.refresh_token).await {
             Ok(refreshed_tokens) => {
+                metrics::token_refresh("truelayer", true);
                 debug!("Token refresh successful");
                 Ok(refreshed_tokens)
             }
             Err(e) => {
+                metrics::token_refresh("truelayer", false);
                 debug!("Token refresh failed ({}), re-authenticating...", e);
                 self.authenticate().await
             }
@@ -1,14 +1,23 @@
 use super::TrueLayerOperations;
 use crate::config::TrueLayerConfig;
 use crate::error::{AppError, Result};
-use crate::models::{Card, Transaction};
+use crate::metrics;
+use crate::models::{Card, CardBalance, Transaction};
 use crate::truelayer::auth::TrueLayerAuth;
-use crate::truelayer::types::{CardsResponse, TransactionsResponse};
+use crate::truelayer::types::{BalanceResponse, CardsResponse, MeResponse, TransactionsResponse};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use reqwest::Client;
+use reqwest::{Client, Response};
 use tracing::instrument;
 
+/// Error for a failed request, counted in the `api_errors_total` metric by status code.
+async fn request_error(message: &str, response: Response) -> AppError {
+    let status = response.status();
+    metrics::api_error("truelayer", Some(status.as_u16()));
+    let body = response.text().await.unwrap_or_default();
+    AppError::TrueLayer(format!("{}: {} - {}", message, status, body))
+}
+
 pub struct TrueLayerClient {
     client: Client,
     access_token: String,
@@ -48,12 +57,7 @@ impl TrueLayerOperations for TrueLayerClient {
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(AppError::TrueLayer(format!(
-                "Failed to list cards: {} - {}",
-                status, body
-            )));
+            return Err(request_error("Failed to list cards", response).await);
         }
 
         let cards: CardsResponse = response.json().await?;
@@ -61,6 +65,55 @@ impl TrueLayerOperations for TrueLayerClient {
         Ok(cards.results.into_iter().map(Into::into).collect())
     }
 
+    #[instrument(name = "Fetching card balance", skip_all, fields(card_id))]
+    async fn get_card_balance(&self, card_id: &str) -> Result<CardBalance> {
+        let url = format!("{}/data/v1/cards/{}/balance", self.api_base_url, card_id);
+
+        let response = self
+            .client
+            .get(&url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(request_error("Failed to get card balance", response).await);
+        }
+
+        let balance: BalanceResponse = response.json().await?;
+
+        balance
+            .results
+            .into_iter()
+            .next()
+            .map(Into::into)
+            .ok_or_else(|| AppError::TrueLayer("No balance returned for card".to_string()))
+    }
+
+    #[instrument(name = "Fetching consent", skip_all)]
+    async fn get_consent_expiry(&self) -> Result<Option<DateTime<Utc>>> {
+        let url = format!("{}/data/v1/me", self.api_base_url);
+
+        let response = self
+            .client
+            .get(&url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(request_error("Failed to get connection metadata", response).await);
+        }
+
+        let me: MeResponse = response.json().await?;
+
+        Ok(me
+            .results
+            .into_iter()
+            .filter_map(|connection| connection.consent_expires_at)
+            .min())
+    }
+
     #[instrument(name = "Fetching card transactions", skip_all, fields(card_id))]
     async fn get_card_transactions(
         &self,
@@ -85,12 +138,7 @@ impl TrueLayerOperations for TrueLayerClient {
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(AppError::TrueLayer(format!(
-                "Failed to get card transactions: {} - {}",
-                status, body
-            )));
+            return Err(request_error("Failed to get card transactions", response).await);
         }
 
         let transactions: TransactionsResponse = response.json().await?;
@@ -5,7 +5,7 @@ pub use auth::clear_tokens as clear_truelayer_tokens;
 pub use client::TrueLayerClient;
 
 use crate::error::Result;
-use crate::models::{Card, Transaction};
+use crate::models::{Card, CardBalance, Transaction};
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
@@ -14,6 +14,11 @@ use chrono::{DateTime, Utc};
 pub trait TrueLayerOperations {
     async fn get_cards(&self) -> Result<Vec<Card>>;
 
+    async fn get_card_balance(&self, card_id: &str) -> Result<CardBalance>;
+
+    /// When the consent to access the cards expires, if the provider says.
+    async fn get_consent_expiry(&self) -> Result<Option<DateTime<Utc>>>;
+
     async fn get_card_transactions(
         &self,
         card_id: &str,
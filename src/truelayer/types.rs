@@ -22,6 +22,35 @@ pub struct TrueLayerProvider {
     pub display_name: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub(super) struct BalanceResponse {
+    pub(super) results: Vec<TrueLayerCardBalance>,
+}
+
+// https://docs.truelayer.com/reference/getcardbalance
+#[derive(Debug, Deserialize)]
+pub struct TrueLayerCardBalance {
+    pub current: Decimal,
+    pub currency: String,
+    // Dates are sometimes sent without a timezone, so only the date is parsed
+    #[serde(default)]
+    pub last_statement_date: Option<String>,
+    #[serde(default)]
+    pub last_statement_balance: Option<Decimal>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct MeResponse {
+    pub(super) results: Vec<TrueLayerConnection>,
+}
+
+// https://docs.truelayer.com/reference/getmetadata
+#[derive(Debug, Deserialize)]
+pub struct TrueLayerConnection {
+    #[serde(default)]
+    pub consent_expires_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Deserialize)]
 pub(super) struct TransactionsResponse {
     pub(super) results: Vec<TrueLayerTransaction>,